@@ -0,0 +1,71 @@
+//! Shader compilation build script
+//! Compiles every shader in `shaders/` directory to SPIR-V and generates `shaders.rs`
+//! with the embedded shader table, used by `render::ShaderModule::embedded`.
+
+use std::{fmt::Write, path::PathBuf};
+
+#[path = "src/render/shader_compiler.rs"]
+#[allow(dead_code)]
+mod shader_compiler;
+
+fn main() {
+    let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
+    let shader_root = manifest_dir.join("shaders");
+    let shader_out_dir = out_dir.join("shaders");
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/render/shader_compiler.rs");
+    println!("cargo:rerun-if-changed=shaders");
+
+    let sources = if shader_root.is_dir() {
+        shader_compiler::find_shaders(&shader_root).expect("failed to read shader directory")
+    } else {
+        Vec::new()
+    };
+
+    std::fs::create_dir_all(&shader_out_dir).expect("failed to create shader output directory");
+
+    let mut shaders = Vec::new();
+    let mut errors = Vec::new();
+
+    for path in &sources {
+        match shader_compiler::compile(&shader_root, path) {
+            Ok(compiled) => shaders.extend(compiled.shaders),
+            Err(source_errors) => errors.extend(source_errors),
+        }
+    }
+
+    if !errors.is_empty() {
+        for error in &errors {
+            eprintln!("error: {error}");
+        }
+        eprintln!("{} shader compilation error(s)", errors.len());
+        std::process::exit(1);
+    }
+
+    shaders.sort_by(|lhs, rhs| lhs.name.cmp(&rhs.name));
+
+    let mut table = String::new();
+    table.push_str("/// Embedded shaders sorted by name\n");
+    table.push_str("pub static SHADERS: &[(&str, &[u8])] = &[\n");
+
+    for shader in &shaders {
+        let file_name = format!("{}.spv", shader.name.replace(['/', ':'], "."));
+        let path = shader_out_dir.join(file_name);
+
+        std::fs::write(&path, spirv_bytes(&shader.words)).expect("failed to write compiled shader");
+
+        let path = path.to_str().expect("output path is not valid UTF-8");
+        writeln!(table, "    ({:?}, include_bytes!({:?})),", shader.name, path).unwrap();
+    }
+
+    table.push_str("];\n");
+
+    std::fs::write(out_dir.join("shaders.rs"), table).expect("failed to write shader table");
+}
+
+/// SPIR-V words to little-endian bytes converting function
+fn spirv_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
@@ -0,0 +1,6 @@
+// Shared declarations of mesh shaders
+
+layout(push_constant) uniform PushConstants {
+    mat4 transform;
+    vec4 color;
+} push_constants;
@@ -0,0 +1,20 @@
+#version 450
+
+// permutation: unlit UNLIT
+
+#include <include/common.glsl>
+
+layout(location = 0) in vec3 in_normal;
+layout(location = 1) in vec2 in_uv;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+#ifdef UNLIT
+    out_color = push_constants.color;
+#else
+    const vec3 light_direction = normalize(vec3(0.3, 1.0, 0.5));
+    float diffuse = max(dot(normalize(in_normal), light_direction), 0.0);
+    out_color = vec4(push_constants.color.rgb * (0.2 + 0.8 * diffuse), push_constants.color.a);
+#endif
+}
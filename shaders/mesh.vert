@@ -0,0 +1,16 @@
+#version 450
+
+#include <include/common.glsl>
+
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec2 in_uv;
+
+layout(location = 0) out vec3 out_normal;
+layout(location = 1) out vec2 out_uv;
+
+void main() {
+    gl_Position = push_constants.transform * vec4(in_position, 1.0);
+    out_normal = in_normal;
+    out_uv = in_uv;
+}
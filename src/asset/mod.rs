@@ -0,0 +1,2 @@
+pub mod mtl;
+pub mod obj;
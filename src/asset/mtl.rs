@@ -0,0 +1,560 @@
+use std::{
+    io::BufRead,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::utility::math::Vec3;
+
+use super::obj::ObjMesh;
+
+/// Texture map reference
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureMap {
+    /// Texture path, resolved against material library directory
+    pub path: PathBuf,
+    /// `-o` texture coordinate offset
+    pub offset: Vec3<f32>,
+    /// `-s` texture coordinate scale
+    pub scale: Vec3<f32>,
+    /// `-bm` bump multiplier
+    pub bump_multiplier: f32,
+}
+
+impl TextureMap {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            offset: Vec3::new(0.0, 0.0, 0.0),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+            bump_multiplier: 1.0,
+        }
+    }
+}
+
+/// MTL material
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: String,
+    /// `Ka` ambient color
+    pub ambient: Vec3<f32>,
+    /// `Kd` diffuse color
+    pub diffuse: Vec3<f32>,
+    /// `Ks` specular color
+    pub specular: Vec3<f32>,
+    /// `Ke` emissive color
+    pub emissive: Vec3<f32>,
+    /// `Ns` specular exponent
+    pub shininess: f32,
+    /// `d` opacity, `Tr` is its complement
+    pub dissolve: f32,
+    /// `illum` illumination model
+    pub illumination: u32,
+    /// `Pr` PBR roughness
+    pub roughness: Option<f32>,
+    /// `Pm` PBR metallic
+    pub metallic: Option<f32>,
+    /// `map_Kd` texture
+    pub diffuse_map: Option<TextureMap>,
+    /// `map_Ks` texture
+    pub specular_map: Option<TextureMap>,
+    /// `map_Bump` or `bump` texture
+    pub bump_map: Option<TextureMap>,
+    /// `map_d` texture
+    pub dissolve_map: Option<TextureMap>,
+    /// `map_Pr` texture
+    pub roughness_map: Option<TextureMap>,
+    /// `map_Pm` texture
+    pub metallic_map: Option<TextureMap>,
+}
+
+impl Material {
+    /// Default material create function
+    /// Default values are ones used by most OBJ exporters for missing statements.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ambient: Vec3::new(0.0, 0.0, 0.0),
+            diffuse: Vec3::new(0.8, 0.8, 0.8),
+            specular: Vec3::new(0.0, 0.0, 0.0),
+            emissive: Vec3::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            dissolve: 1.0,
+            illumination: 1,
+            roughness: None,
+            metallic: None,
+            diffuse_map: None,
+            specular_map: None,
+            bump_map: None,
+            dissolve_map: None,
+            roughness_map: None,
+            metallic_map: None,
+        }
+    }
+}
+
+/// MTL parsing error kind
+#[derive(Clone, Debug, PartialEq)]
+pub enum MtlErrorKind {
+    /// Statement lacks required argument
+    MissingArgument(&'static str),
+    InvalidNumber(String),
+    /// Material property is declared before the first `newmtl`
+    NoMaterial,
+}
+
+impl std::fmt::Display for MtlErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingArgument(argument) => f.write_fmt(format_args!("missing {argument}")),
+            Self::InvalidNumber(value) => f.write_fmt(format_args!("invalid number \"{value}\"")),
+            Self::NoMaterial => f.write_str("material property is declared before newmtl"),
+        }
+    }
+}
+
+/// MTL loading error
+#[derive(Clone, Debug)]
+pub enum MtlError {
+    IoError {
+        path: Option<PathBuf>,
+        error: Arc<std::io::Error>,
+    },
+    ParseError {
+        path: Option<PathBuf>,
+        /// 1-based line number
+        line: usize,
+        kind: MtlErrorKind,
+    },
+}
+
+impl std::fmt::Display for MtlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError { path: Some(path), error } => {
+                f.write_fmt(format_args!("failed to read {}: {error}", path.display()))
+            }
+            Self::IoError { path: None, error } => f.write_fmt(format_args!("read error: {error}")),
+            Self::ParseError { path: Some(path), line, kind } => {
+                f.write_fmt(format_args!("{}:{line}: {kind}", path.display()))
+            }
+            Self::ParseError { path: None, line, kind } => f.write_fmt(format_args!("line {line}: {kind}")),
+        }
+    }
+}
+
+impl std::error::Error for MtlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Mesh face range with material
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaterialRange {
+    /// Range of faces in `ObjMesh::faces`
+    pub faces: Range<usize>,
+    /// Index of material in `MaterialLibrary::materials`, `None` if face range has no material
+    /// or material isn't found
+    pub material: Option<usize>,
+}
+
+/// Number parsing function
+fn parse_number<T: std::str::FromStr>(value: Option<&str>, argument: &'static str) -> Result<T, MtlErrorKind> {
+    let value = value.ok_or(MtlErrorKind::MissingArgument(argument))?;
+    value.parse().map_err(|_| MtlErrorKind::InvalidNumber(value.to_string()))
+}
+
+/// Color (`r [g b]`, `xyz x [y z]` or `spectral file [factor]`) parsing function
+/// CIE XYZ colors are converted to linear sRGB, `None` is returned for spectral curves, as they aren't supported.
+fn parse_color<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Option<Vec3<f32>>, MtlErrorKind> {
+    let first = words.next();
+
+    let (xyz, first) = match first {
+        Some("spectral") => return Ok(None),
+        Some("xyz") => (true, words.next()),
+        first => (false, first),
+    };
+
+    let r = parse_number(first, "red component")?;
+    let color = match words.next() {
+        // Single component means gray color
+        None => Vec3::new(r, r, r),
+        g => Vec3::new(r, parse_number(g, "green component")?, parse_number(words.next(), "blue component")?),
+    };
+
+    if !xyz {
+        return Ok(Some(color));
+    }
+
+    // D65 white point
+    Ok(Some(Vec3::new(
+        3.2406 * color.x - 1.5372 * color.y - 0.4986 * color.z,
+        -0.9689 * color.x + 1.8758 * color.y + 0.0415 * color.z,
+        0.0557 * color.x - 0.2040 * color.y + 1.0570 * color.z,
+    )))
+}
+
+/// Texture map statement arguments (`[options] path`) parsing function
+fn parse_texture_map(arguments: &str, base_dir: &Path) -> Result<TextureMap, MtlErrorKind> {
+    let mut map = TextureMap::new(PathBuf::new());
+    let mut rest = arguments.trim();
+
+    // Options are parsed up to the first non-option word, the rest is path that may contain spaces
+    while rest.starts_with('-') {
+        let (option, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let mut tail = tail.trim_start();
+
+        let mut numbers = Vec::new();
+        let max_numbers = match option {
+            "-o" | "-s" | "-t" => 3,
+            "-mm" => 2,
+            "-bm" | "-boost" | "-texres" => 1,
+            // Options with non-numeric value (e.g. "-clamp on", "-imfchan r")
+            _ => {
+                let (_, after_value) = tail.split_once(char::is_whitespace).unwrap_or((tail, ""));
+                rest = after_value.trim_start();
+                continue;
+            }
+        };
+
+        while numbers.len() < max_numbers {
+            let (word, after_word) = tail.split_once(char::is_whitespace).unwrap_or((tail, ""));
+            match word.parse::<f32>() {
+                Ok(number) => {
+                    numbers.push(number);
+                    tail = after_word.trim_start();
+                }
+                Err(_) => break,
+            }
+        }
+
+        if numbers.is_empty() {
+            return Err(MtlErrorKind::MissingArgument("texture option value"));
+        }
+
+        let vector = || Vec3::new(numbers[0], *numbers.get(1).unwrap_or(&0.0), *numbers.get(2).unwrap_or(&0.0));
+        match option {
+            "-o" => map.offset = vector(),
+            "-s" => {
+                map.scale = Vec3::new(numbers[0], *numbers.get(1).unwrap_or(&1.0), *numbers.get(2).unwrap_or(&1.0))
+            }
+            "-bm" => map.bump_multiplier = numbers[0],
+            _ => {}
+        }
+
+        rest = tail;
+    }
+
+    if rest.is_empty() {
+        return Err(MtlErrorKind::MissingArgument("texture path"));
+    }
+
+    // Exporters on Windows write backslash separators
+    let relative = PathBuf::from(rest.replace('\\', "/"));
+    map.path = base_dir.join(relative);
+
+    Ok(map)
+}
+
+/// Material library
+#[derive(Clone, Debug, Default)]
+pub struct MaterialLibrary {
+    pub materials: Vec<Material>,
+}
+
+impl MaterialLibrary {
+    /// Library from file loading function
+    /// Texture paths are resolved against library directory.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MtlError> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path).map_err(|error| MtlError::IoError {
+            path: Some(path.to_path_buf()),
+            error: Arc::new(error),
+        })?;
+
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        Self::parse(std::io::BufReader::new(file), base_dir).map_err(|error| match error {
+            MtlError::IoError { path: None, error } => MtlError::IoError {
+                path: Some(path.to_path_buf()),
+                error,
+            },
+            MtlError::ParseError { path: None, line, kind } => MtlError::ParseError {
+                path: Some(path.to_path_buf()),
+                line,
+                kind,
+            },
+            error => error,
+        })
+    }
+
+    /// Library from reader parsing function, texture paths are resolved against `base_dir`
+    pub fn parse(reader: impl BufRead, base_dir: impl AsRef<Path>) -> Result<Self, MtlError> {
+        let mut library = Self::default();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.map_err(|error| MtlError::IoError {
+                path: None,
+                error: Arc::new(error),
+            })?;
+
+            library
+                .parse_statement(&line, base_dir.as_ref())
+                .map_err(|kind| MtlError::ParseError {
+                    path: None,
+                    line: index + 1,
+                    kind,
+                })?;
+        }
+
+        Ok(library)
+    }
+
+    /// Libraries referenced by mesh loading function
+    /// Libraries are searched relative to mesh file directory. Missing libraries are skipped
+    /// with a warning, so faces referencing their materials have no material.
+    pub fn load_for_mesh(mesh: &ObjMesh, mesh_path: impl AsRef<Path>) -> Result<Self, MtlError> {
+        let mesh_dir = mesh_path.as_ref().parent().unwrap_or(Path::new(""));
+        let mut library = Self::default();
+
+        for name in &mesh.material_libraries {
+            // Library name may contain spaces, so the whole argument is tried first
+            let whole = mesh_dir.join(name);
+            let paths = if whole.is_file() {
+                vec![whole]
+            } else {
+                name.split_whitespace().map(|name| mesh_dir.join(name)).collect()
+            };
+
+            for path in paths {
+                match Self::load(&path) {
+                    Ok(loaded) => library.materials.extend(loaded.materials),
+                    Err(MtlError::IoError { error, .. }) if error.kind() == std::io::ErrorKind::NotFound => {
+                        log::warn!("material library {} is not found", path.display());
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+
+        Ok(library)
+    }
+
+    /// Single statement parsing function, unsupported statements are ignored
+    fn parse_statement(&mut self, line: &str, base_dir: &Path) -> Result<(), MtlErrorKind> {
+        let line = line.split_once('#').map_or(line, |(content, _)| content).trim();
+        let mut words = line.split_whitespace();
+
+        let Some(keyword) = words.next() else {
+            return Ok(());
+        };
+        let rest = line[keyword.len()..].trim();
+
+        if keyword == "newmtl" {
+            if rest.is_empty() {
+                return Err(MtlErrorKind::MissingArgument("material name"));
+            }
+            self.materials.push(Material::new(rest));
+            return Ok(());
+        }
+
+        let material = self.materials.last_mut().ok_or(MtlErrorKind::NoMaterial)?;
+
+        match keyword {
+            "Ka" => {
+                if let Some(color) = parse_color(words)? {
+                    material.ambient = color;
+                }
+            }
+            "Kd" => {
+                if let Some(color) = parse_color(words)? {
+                    material.diffuse = color;
+                }
+            }
+            "Ks" => {
+                if let Some(color) = parse_color(words)? {
+                    material.specular = color;
+                }
+            }
+            "Ke" => {
+                if let Some(color) = parse_color(words)? {
+                    material.emissive = color;
+                }
+            }
+            "Ns" => material.shininess = parse_number(words.next(), "specular exponent")?,
+            "d" => {
+                // "-halo" factor is ignored
+                let value = words.find(|word| *word != "-halo");
+                material.dissolve = parse_number(value, "dissolve")?;
+            }
+            "Tr" => material.dissolve = 1.0 - parse_number::<f32>(words.next(), "transparency")?,
+            "illum" => material.illumination = parse_number(words.next(), "illumination model")?,
+            "Pr" => material.roughness = Some(parse_number(words.next(), "roughness")?),
+            "Pm" => material.metallic = Some(parse_number(words.next(), "metallic")?),
+            "map_Kd" => material.diffuse_map = Some(parse_texture_map(rest, base_dir)?),
+            "map_Ks" => material.specular_map = Some(parse_texture_map(rest, base_dir)?),
+            "map_Bump" | "map_bump" | "bump" => material.bump_map = Some(parse_texture_map(rest, base_dir)?),
+            "map_d" => material.dissolve_map = Some(parse_texture_map(rest, base_dir)?),
+            "map_Pr" => material.roughness_map = Some(parse_texture_map(rest, base_dir)?),
+            "map_Pm" => material.metallic_map = Some(parse_texture_map(rest, base_dir)?),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Material by name getting function
+    /// The last material with such name is returned, as later libraries override earlier ones.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.materials.iter().rposition(|material| material.name == name)
+    }
+
+    /// Mesh face ranges with materials getting function
+    /// Ranges cover all mesh faces in order, missing materials are reported with a warning.
+    pub fn material_ranges(&self, mesh: &ObjMesh) -> Vec<MaterialRange> {
+        let mut ranges = Vec::new();
+        let mut next_face = 0;
+
+        for range in &mesh.material_ranges {
+            if next_face < range.faces.start {
+                ranges.push(MaterialRange {
+                    faces: next_face..range.faces.start,
+                    material: None,
+                });
+            }
+
+            let material = self.find(&range.name);
+            if material.is_none() {
+                log::warn!("material \"{}\" is not found", range.name);
+            }
+
+            ranges.push(MaterialRange {
+                faces: range.faces.clone(),
+                material,
+            });
+            next_face = range.faces.end;
+        }
+
+        if next_face < mesh.faces.len() {
+            ranges.push(MaterialRange {
+                faces: next_face..mesh.faces.len(),
+                material: None,
+            });
+        }
+
+        ranges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> MaterialLibrary {
+        MaterialLibrary::parse(source.as_bytes(), "textures").unwrap()
+    }
+
+    #[test]
+    fn color_forms() {
+        let library = parse(
+            "newmtl a\nKa 0.5\nKd 0.1 0.2 0.3\nKs spectral metal.rfl 2.0\nKe xyz 0.9505 1.0 1.089",
+        );
+        let material = &library.materials[0];
+
+        assert_eq!(material.ambient, Vec3::new(0.5, 0.5, 0.5));
+        assert_eq!(material.diffuse, Vec3::new(0.1, 0.2, 0.3));
+        // Spectral colors are skipped, so default value stays
+        assert_eq!(material.specular, Material::new("a").specular);
+        // D65 white is converted to sRGB white
+        for component in [material.emissive.x, material.emissive.y, material.emissive.z] {
+            assert!((component - 1.0).abs() < 1e-3, "{component}");
+        }
+
+        let error = MaterialLibrary::parse("newmtl a\nKd 0.1 0.2".as_bytes(), "").unwrap_err();
+        assert!(matches!(
+            error,
+            MtlError::ParseError {
+                line: 2,
+                kind: MtlErrorKind::MissingArgument("blue component"),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn texture_options() {
+        let library = parse(concat!(
+            "newmtl a\n",
+            "map_Kd -o 0.5 0.25 -s 2 3 4 -clamp on albedo.png\n",
+            "map_Bump -bm 0.3 -imfchan r normal map.png\n",
+            "map_Ks -s 2 dir\\specular.png\n",
+        ));
+        let material = &library.materials[0];
+
+        let diffuse_map = material.diffuse_map.as_ref().unwrap();
+        assert_eq!(diffuse_map.path, Path::new("textures/albedo.png"));
+        assert_eq!(diffuse_map.offset, Vec3::new(0.5, 0.25, 0.0));
+        assert_eq!(diffuse_map.scale, Vec3::new(2.0, 3.0, 4.0));
+
+        let bump_map = material.bump_map.as_ref().unwrap();
+        assert_eq!(bump_map.path, Path::new("textures/normal map.png"));
+        assert_eq!(bump_map.bump_multiplier, 0.3);
+
+        let specular_map = material.specular_map.as_ref().unwrap();
+        assert_eq!(specular_map.path, Path::new("textures/dir/specular.png"));
+        assert_eq!(specular_map.scale, Vec3::new(2.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn texture_option_without_value() {
+        let error = MaterialLibrary::parse("newmtl a\nmap_Kd -bm albedo.png".as_bytes(), "").unwrap_err();
+        assert!(matches!(error, MtlError::ParseError { kind: MtlErrorKind::MissingArgument(_), .. }));
+    }
+
+    #[test]
+    fn dissolve_and_transparency() {
+        let library = parse("newmtl d\nd 0.25\nnewmtl tr\nTr 0.25\nnewmtl halo\nd -halo 0.5\nnewmtl default");
+        let dissolves = library.materials.iter().map(|material| material.dissolve).collect::<Vec<_>>();
+
+        assert_eq!(dissolves, [0.25, 0.75, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn property_before_newmtl() {
+        let error = MaterialLibrary::parse("# header\nKd 1 1 1".as_bytes(), "").unwrap_err();
+        assert!(matches!(error, MtlError::ParseError { line: 2, kind: MtlErrorKind::NoMaterial, .. }));
+    }
+
+    #[test]
+    fn material_ranges_fill_gaps() {
+        let mesh = ObjMesh::parse(
+            concat!(
+                "v 0 0 0\nv 1 0 0\nv 0 1 0\n",
+                "f 1 2 3\n",
+                "usemtl red\nf 1 2 3\nf 1 2 3\n",
+                "usemtl missing\nf 1 2 3\n",
+                "usemtl red\nf 1 2 3\n",
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let library = parse("newmtl blue\nnewmtl red\nKd 1 0 0");
+
+        assert_eq!(
+            library.material_ranges(&mesh),
+            [
+                MaterialRange { faces: 0..1, material: None },
+                MaterialRange { faces: 1..3, material: Some(1) },
+                MaterialRange { faces: 3..4, material: None },
+                MaterialRange { faces: 4..5, material: Some(1) },
+            ]
+        );
+
+        let mesh = ObjMesh::parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3".as_bytes()).unwrap();
+        assert_eq!(library.material_ranges(&mesh), [MaterialRange { faces: 0..1, material: None }]);
+    }
+}
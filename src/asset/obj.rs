@@ -0,0 +1,486 @@
+use std::{
+    io::BufRead,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::utility::math::{Vec2, Vec3};
+
+/// Face vertex, indices are zero-based and refer to `ObjMesh` attribute arrays
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ObjVertex {
+    pub position: u32,
+    pub uv: Option<u32>,
+    pub normal: Option<u32>,
+}
+
+/// Polygon face
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjFace {
+    /// Range of face vertices in `ObjMesh::face_vertices`
+    pub vertices: Range<usize>,
+    /// Smoothing group, 0 if smoothing is off
+    pub smoothing_group: u32,
+}
+
+/// Named range of faces (object, group or material range)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjFaceRange {
+    pub name: String,
+    /// Range of faces in `ObjMesh::faces`
+    pub faces: Range<usize>,
+}
+
+/// Wavefront OBJ polygon mesh
+/// Faces keep separate position/uv/normal indices, as they're stored in file.
+#[derive(Clone, Debug, Default)]
+pub struct ObjMesh {
+    pub positions: Vec<Vec3<f32>>,
+    pub uvs: Vec<Vec2<f32>>,
+    pub normals: Vec<Vec3<f32>>,
+    pub face_vertices: Vec<ObjVertex>,
+    pub faces: Vec<ObjFace>,
+    /// `o` statement ranges
+    pub objects: Vec<ObjFaceRange>,
+    /// `g` statement ranges
+    pub groups: Vec<ObjFaceRange>,
+    /// `usemtl` statement ranges, faces declared before the first `usemtl` have no material
+    pub material_ranges: Vec<ObjFaceRange>,
+    /// `mtllib` statement arguments
+    pub material_libraries: Vec<String>,
+    /// Any `s` statement is declared, so smoothing groups are meaningful
+    pub has_smoothing_groups: bool,
+}
+
+/// OBJ parsing error kind
+#[derive(Clone, Debug, PartialEq)]
+pub enum ObjErrorKind {
+    /// Statement lacks required argument
+    MissingArgument(&'static str),
+    InvalidNumber(String),
+    /// Face vertex isn't in `v`, `v/vt`, `v//vn` or `v/vt/vn` format
+    InvalidFaceVertex(String),
+    /// Index is zero or refers to attribute that isn't declared yet
+    IndexOutOfRange { index: i64, count: usize },
+    /// Face has less than 3 vertices
+    DegenerateFace(usize),
+}
+
+impl std::fmt::Display for ObjErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingArgument(argument) => f.write_fmt(format_args!("missing {argument}")),
+            Self::InvalidNumber(value) => f.write_fmt(format_args!("invalid number \"{value}\"")),
+            Self::InvalidFaceVertex(value) => f.write_fmt(format_args!("invalid face vertex \"{value}\"")),
+            Self::IndexOutOfRange { index, count } => {
+                f.write_fmt(format_args!("index {index} is out of range, {count} elements declared"))
+            }
+            Self::DegenerateFace(count) => f.write_fmt(format_args!("face has only {count} vertices")),
+        }
+    }
+}
+
+/// OBJ loading error
+#[derive(Clone, Debug)]
+pub enum ObjError {
+    IoError {
+        path: Option<PathBuf>,
+        error: Arc<std::io::Error>,
+    },
+    ParseError {
+        /// 1-based line number
+        line: usize,
+        kind: ObjErrorKind,
+    },
+}
+
+impl std::fmt::Display for ObjError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IoError { path: Some(path), error } => {
+                f.write_fmt(format_args!("failed to read {}: {error}", path.display()))
+            }
+            Self::IoError { path: None, error } => f.write_fmt(format_args!("read error: {error}")),
+            Self::ParseError { line, kind } => f.write_fmt(format_args!("line {line}: {kind}")),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Number parsing function
+fn parse_number<T: std::str::FromStr>(value: Option<&str>, argument: &'static str) -> Result<T, ObjErrorKind> {
+    let value = value.ok_or(ObjErrorKind::MissingArgument(argument))?;
+    value.parse().map_err(|_| ObjErrorKind::InvalidNumber(value.to_string()))
+}
+
+/// One-based or negative relative index to zero-based index resolving function
+fn resolve_index(value: &str, count: usize) -> Result<u32, ObjErrorKind> {
+    let index = value
+        .parse::<i64>()
+        .map_err(|_| ObjErrorKind::InvalidNumber(value.to_string()))?;
+
+    let resolved = match index {
+        1.. => index - 1,
+        ..0 => count as i64 + index,
+        0 => -1,
+    };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(ObjErrorKind::IndexOutOfRange { index, count });
+    }
+
+    Ok(resolved as u32)
+}
+
+/// Named face range list extending function, empty previous range is replaced
+fn begin_range(ranges: &mut Vec<ObjFaceRange>, name: &str, face_count: usize) {
+    if let Some(last) = ranges.last_mut() {
+        last.faces.end = face_count;
+        if last.faces.is_empty() {
+            ranges.pop();
+        }
+    }
+
+    ranges.push(ObjFaceRange {
+        name: name.to_string(),
+        faces: face_count..face_count,
+    });
+}
+
+/// Named face range list finishing function
+fn end_ranges(ranges: &mut Vec<ObjFaceRange>, face_count: usize) {
+    if let Some(last) = ranges.last_mut() {
+        last.faces.end = face_count;
+        if last.faces.is_empty() {
+            ranges.pop();
+        }
+    }
+}
+
+impl ObjMesh {
+    /// Mesh from file loading function
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ObjError> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path).map_err(|error| ObjError::IoError {
+            path: Some(path.to_path_buf()),
+            error: Arc::new(error),
+        })?;
+
+        Self::parse(std::io::BufReader::new(file)).map_err(|error| match error {
+            ObjError::IoError { path: None, error } => ObjError::IoError {
+                path: Some(path.to_path_buf()),
+                error,
+            },
+            error => error,
+        })
+    }
+
+    /// Mesh from reader parsing function
+    pub fn parse(reader: impl BufRead) -> Result<Self, ObjError> {
+        let mut mesh = Self::default();
+        let mut smoothing_group = 0;
+        let mut statement = String::new();
+        let mut statement_line = 0;
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.map_err(|error| ObjError::IoError {
+                path: None,
+                error: Arc::new(error),
+            })?;
+
+            if statement.is_empty() {
+                statement_line = index + 1;
+            }
+
+            // Lines ending with backslash are continued on the next line
+            if let Some(line) = line.strip_suffix('\\') {
+                statement.push_str(line);
+                statement.push(' ');
+                continue;
+            }
+            statement.push_str(&line);
+
+            mesh.parse_statement(&statement, &mut smoothing_group)
+                .map_err(|kind| ObjError::ParseError {
+                    line: statement_line,
+                    kind,
+                })?;
+
+            statement.clear();
+        }
+
+        if !statement.is_empty() {
+            mesh.parse_statement(&statement, &mut smoothing_group)
+                .map_err(|kind| ObjError::ParseError {
+                    line: statement_line,
+                    kind,
+                })?;
+        }
+
+        let face_count = mesh.faces.len();
+        end_ranges(&mut mesh.objects, face_count);
+        end_ranges(&mut mesh.groups, face_count);
+        end_ranges(&mut mesh.material_ranges, face_count);
+
+        Ok(mesh)
+    }
+
+    /// Single statement parsing function
+    /// Unsupported statements (e.g. lines, curves) are ignored.
+    fn parse_statement(&mut self, statement: &str, smoothing_group: &mut u32) -> Result<(), ObjErrorKind> {
+        let statement = statement.split_once('#').map_or(statement, |(content, _)| content);
+        let mut words = statement.split_whitespace();
+
+        let Some(keyword) = words.next() else {
+            return Ok(());
+        };
+
+        // Name arguments may contain spaces (e.g. "mtllib bobo neca.mtl")
+        let rest = || statement.trim_start()[keyword.len()..].trim();
+
+        match keyword {
+            "v" => {
+                let x = parse_number(words.next(), "x coordinate")?;
+                let y = parse_number(words.next(), "y coordinate")?;
+                let z = parse_number(words.next(), "z coordinate")?;
+                self.positions.push(Vec3::new(x, y, z));
+            }
+            "vt" => {
+                let u = parse_number(words.next(), "u coordinate")?;
+                let v = match words.next() {
+                    Some(v) => parse_number(Some(v), "v coordinate")?,
+                    None => 0.0,
+                };
+                self.uvs.push(Vec2::new(u, v));
+            }
+            "vn" => {
+                let x = parse_number(words.next(), "x coordinate")?;
+                let y = parse_number(words.next(), "y coordinate")?;
+                let z = parse_number(words.next(), "z coordinate")?;
+                self.normals.push(Vec3::new(x, y, z));
+            }
+            "f" => {
+                let start = self.face_vertices.len();
+
+                for word in words {
+                    let vertex = self.parse_face_vertex(word);
+                    match vertex {
+                        Ok(vertex) => self.face_vertices.push(vertex),
+                        Err(kind) => {
+                            self.face_vertices.truncate(start);
+                            return Err(kind);
+                        }
+                    }
+                }
+
+                let count = self.face_vertices.len() - start;
+                if count < 3 {
+                    self.face_vertices.truncate(start);
+                    return Err(ObjErrorKind::DegenerateFace(count));
+                }
+
+                self.faces.push(ObjFace {
+                    vertices: start..self.face_vertices.len(),
+                    smoothing_group: *smoothing_group,
+                });
+            }
+            "o" => begin_range(&mut self.objects, rest(), self.faces.len()),
+            "g" => begin_range(&mut self.groups, rest(), self.faces.len()),
+            "usemtl" => {
+                let name = rest();
+                if name.is_empty() {
+                    return Err(ObjErrorKind::MissingArgument("material name"));
+                }
+                begin_range(&mut self.material_ranges, name, self.faces.len());
+            }
+            "mtllib" => {
+                let name = rest();
+                if name.is_empty() {
+                    return Err(ObjErrorKind::MissingArgument("material library name"));
+                }
+                self.material_libraries.push(name.to_string());
+            }
+            "s" => {
+                self.has_smoothing_groups = true;
+                *smoothing_group = match words.next() {
+                    Some("off") => 0,
+                    value => parse_number(value, "smoothing group")?,
+                };
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Face vertex (`v`, `v/vt`, `v//vn` or `v/vt/vn`) parsing function
+    fn parse_face_vertex(&self, word: &str) -> Result<ObjVertex, ObjErrorKind> {
+        let mut parts = word.split('/');
+        let invalid = || ObjErrorKind::InvalidFaceVertex(word.to_string());
+
+        let position = resolve_index(parts.next().ok_or_else(invalid)?, self.positions.len())?;
+        let uv = match parts.next() {
+            None | Some("") => None,
+            Some(uv) => Some(resolve_index(uv, self.uvs.len())?),
+        };
+        // Trailing empty normal slot ("1/2/") is written by some exporters
+        let normal = match parts.next() {
+            None | Some("") => None,
+            Some(normal) => Some(resolve_index(normal, self.normals.len())?),
+        };
+
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(ObjVertex { position, uv, normal })
+    }
+
+    /// Face vertices getting function
+    pub fn face_vertices(&self, face: &ObjFace) -> &[ObjVertex] {
+        &self.face_vertices[face.vertices.clone()]
+    }
+
+    /// Triangle count after fan or ear clipping triangulation getting function
+    pub fn triangle_count(&self) -> usize {
+        self.faces.iter().map(|face| face.vertices.len() - 2).sum()
+    }
+
+    /// Face material name getting function
+    pub fn face_material(&self, face_index: usize) -> Option<&str> {
+        self.material_ranges
+            .iter()
+            .find(|range| range.faces.contains(&face_index))
+            .map(|range| range.name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<ObjMesh, ObjError> {
+        ObjMesh::parse(source.as_bytes())
+    }
+
+    const POSITIONS: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvt 0 1\nvn 0 0 1\n";
+
+    fn first_face(mesh: &ObjMesh) -> &[ObjVertex] {
+        mesh.face_vertices(&mesh.faces[0])
+    }
+
+    #[test]
+    fn face_vertex_forms() {
+        let vertex = |position, uv, normal| ObjVertex { position, uv, normal };
+
+        let mesh = parse(&format!("{POSITIONS}f 1 2 3")).unwrap();
+        assert_eq!(first_face(&mesh), [vertex(0, None, None), vertex(1, None, None), vertex(2, None, None)]);
+
+        let mesh = parse(&format!("{POSITIONS}f 1/1 2/2 3/3")).unwrap();
+        assert_eq!(first_face(&mesh), [vertex(0, Some(0), None), vertex(1, Some(1), None), vertex(2, Some(2), None)]);
+
+        let mesh = parse(&format!("{POSITIONS}f 1//1 2//1 3//1")).unwrap();
+        assert_eq!(first_face(&mesh), [vertex(0, None, Some(0)), vertex(1, None, Some(0)), vertex(2, None, Some(0))]);
+
+        let mesh = parse(&format!("{POSITIONS}f 1/1/1 2/2/1 3/3/1")).unwrap();
+        assert_eq!(
+            first_face(&mesh),
+            [vertex(0, Some(0), Some(0)), vertex(1, Some(1), Some(0)), vertex(2, Some(2), Some(0))]
+        );
+    }
+
+    #[test]
+    fn trailing_empty_normal_slot() {
+        let mesh = parse(&format!("{POSITIONS}f 1/1/ 2/2/ 3/3/")).unwrap();
+        assert!(first_face(&mesh).iter().all(|vertex| vertex.uv.is_some() && vertex.normal.is_none()));
+    }
+
+    #[test]
+    fn invalid_face_vertex() {
+        let error = parse(&format!("{POSITIONS}f 1/1/1/1 2 3")).unwrap_err();
+        assert!(matches!(error, ObjError::ParseError { kind: ObjErrorKind::InvalidFaceVertex(_), .. }));
+    }
+
+    #[test]
+    fn negative_indices() {
+        let mesh = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 1 1 0\nf -4 -1 -2").unwrap();
+        let positions = |face: &ObjFace| mesh.face_vertices(face).iter().map(|vertex| vertex.position).collect::<Vec<_>>();
+
+        assert_eq!(positions(&mesh.faces[0]), [0, 1, 2]);
+        assert_eq!(positions(&mesh.faces[1]), [0, 3, 2]);
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        let error = parse("v 0 0 0\nv 1 0 0\nf 1 2 3").unwrap_err();
+        assert!(matches!(
+            error,
+            ObjError::ParseError {
+                line: 3,
+                kind: ObjErrorKind::IndexOutOfRange { index: 3, count: 2 },
+            }
+        ));
+
+        let error = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2").unwrap_err();
+        assert!(matches!(error, ObjError::ParseError { kind: ObjErrorKind::IndexOutOfRange { index: 0, .. }, .. }));
+    }
+
+    #[test]
+    fn line_continuation() {
+        let mesh = parse("v 0 0 \\\n0\nv 1 0 0\nv 0 1 0\nf 1 \\\n2 \\\n3").unwrap();
+
+        assert_eq!(mesh.positions.len(), 3);
+        assert_eq!(first_face(&mesh).len(), 3);
+    }
+
+    #[test]
+    fn error_line_numbers() {
+        // Continued statement is reported at its first line
+        let error = parse("v 0 0 0\n\n# comment\nv 1 \\\nx 0").unwrap_err();
+        assert!(matches!(error, ObjError::ParseError { line: 4, kind: ObjErrorKind::InvalidNumber(_) }));
+        assert_eq!(error.to_string(), "line 4: invalid number \"x\"");
+
+        let error = parse(&format!("{POSITIONS}f 1 2")).unwrap_err();
+        assert!(matches!(error, ObjError::ParseError { line: 8, kind: ObjErrorKind::DegenerateFace(2) }));
+    }
+
+    #[test]
+    fn empty_ranges_are_dropped() {
+        let mesh = parse(&format!(
+            "{POSITIONS}o empty\no first\ng a\ng b\nusemtl unused\nusemtl red\nf 1 2 3\no second\nusemtl blue\nf 1 2 3\ng c\nusemtl last"
+        ))
+        .unwrap();
+
+        let ranges = |ranges: &[ObjFaceRange]| {
+            ranges
+                .iter()
+                .map(|range| (range.name.clone(), range.faces.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ranges(&mesh.objects), [("first".to_string(), 0..1), ("second".to_string(), 1..2)]);
+        assert_eq!(ranges(&mesh.groups), [("b".to_string(), 0..2)]);
+        assert_eq!(ranges(&mesh.material_ranges), [("red".to_string(), 0..1), ("blue".to_string(), 1..2)]);
+    }
+
+    #[test]
+    fn names_with_spaces_and_smoothing_groups() {
+        let mesh = parse(&format!("mtllib bobo neca.mtl\n{POSITIONS}s 1\nf 1 2 3\ns off\nf 1 2 3 # comment")).unwrap();
+
+        assert_eq!(mesh.material_libraries, ["bobo neca.mtl"]);
+        assert_eq!(mesh.faces[0].smoothing_group, 1);
+        assert_eq!(mesh.faces[1].smoothing_group, 0);
+        assert!(mesh.has_smoothing_groups);
+        assert!(!parse(&format!("{POSITIONS}f 1 2 3")).unwrap().has_smoothing_groups);
+        assert_eq!(mesh.face_material(0), None);
+    }
+}
@@ -1,2 +1,4 @@
+pub mod asset;
+pub mod mesh;
 pub mod render;
 pub mod utility;
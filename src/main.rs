@@ -1,6 +1,14 @@
 use std::sync::Arc;
 
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use wat3rs::{
+    render::{KernelConfig, Render, SwapchainConfig},
+    utility::math::Ext2,
+};
+
 struct Context {
+    // Render is declared before window to be dropped before it
+    render: Render,
     window: Arc<winit::window::Window>,
 }
 
@@ -16,17 +24,39 @@ impl ApplicationHandler {
 
 impl winit::application::ApplicationHandler for ApplicationHandler {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if self.context.is_some() {
+            return;
+        }
+
         let window = Arc::new(
             event_loop
                 .create_window(
-                    winit::window::WindowAttributes::default()
-                        .with_title("WAT3RS")
-                        .with_resizable(false),
+                    winit::window::WindowAttributes::default().with_title("WAT3RS"),
                 )
                 .expect("Error creating window"),
         );
 
-        self.context = Some(Context { window });
+        let size = window.inner_size();
+        let render_result = Render::new(
+            window.window_handle().expect("Error getting window handle").as_raw(),
+            window.display_handle().expect("Error getting display handle").as_raw(),
+            Ext2::new(size.width, size.height),
+            KernelConfig::default().layer(c"VK_LAYER_RENDERDOC_Capture"),
+            SwapchainConfig::default(),
+        );
+
+        let render = match render_result {
+            Ok(render) => render,
+            Err(err) => {
+                eprintln!("Error creating render: {err}");
+                event_loop.exit();
+                return;
+            }
+        };
+
+        window.request_redraw();
+
+        self.context = Some(Context { render, window });
     }
 
     fn window_event(
@@ -51,6 +81,27 @@ impl winit::application::ApplicationHandler for ApplicationHandler {
         }
 
         match event {
+            Event::Resized(size) => {
+                context.render.resize(Ext2::new(size.width, size.height));
+
+                // Redraws are stopped while window is minimized, so they're resumed here
+                if size.width != 0 && size.height != 0 {
+                    context.window.request_redraw();
+                }
+            }
+            Event::RedrawRequested => {
+                if let Err(err) = context.render.render_frame() {
+                    eprintln!("Error rendering frame: {err}");
+                    event_loop.exit();
+                    return;
+                }
+
+                // There is nothing to render to while window is minimized
+                let size = context.window.inner_size();
+                if size.width != 0 && size.height != 0 {
+                    context.window.request_redraw();
+                }
+            }
             _ => {}
         }
     }
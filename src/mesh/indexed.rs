@@ -0,0 +1,468 @@
+use std::{collections::HashMap, ops::Range};
+
+use ash::vk;
+
+use crate::{
+    asset::{mtl::MaterialRange, obj::ObjMesh},
+    utility::math::{self, Vec2, Vec3, Vec4},
+};
+
+use super::triangulate::triangulate_polygon;
+
+/// Maximal vertex count addressable by 16-bit indices
+/// 0xFFFF is primitive restart index, so it's never used as vertex index.
+const MAX_U16_VERTICES: usize = u16::MAX as usize;
+
+/// Index buffer element format
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum IndexFormat {
+    /// 16-bit indices if all vertices may be addressed by them, 32-bit otherwise
+    #[default]
+    Auto,
+    U16,
+    U32,
+}
+
+/// Index buffer data
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    /// Index count getting function
+    pub fn len(&self) -> usize {
+        match self {
+            Self::U16(indices) => indices.len(),
+            Self::U32(indices) => indices.len(),
+        }
+    }
+
+    /// Index buffer is empty check function
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Index type for `vkCmdBindIndexBuffer` getting function
+    pub fn index_type(&self) -> vk::IndexType {
+        match self {
+            Self::U16(_) => vk::IndexType::UINT16,
+            Self::U32(_) => vk::IndexType::UINT32,
+        }
+    }
+
+    /// Index data bytes getting function
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::U16(indices) => bytemuck::cast_slice(indices),
+            Self::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+}
+
+/// Mesh processing error
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MeshError {
+    /// 16-bit indices are requested for mesh with more than 65535 vertices
+    TooManyVertices(usize),
+    /// Operation requires vertex normals
+    MissingNormals,
+    /// Operation requires vertex texture coordinates
+    MissingUvs,
+}
+
+impl std::fmt::Display for MeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooManyVertices(count) => f.write_fmt(format_args!(
+                "{count} vertices can't be addressed by 16-bit indices"
+            )),
+            Self::MissingNormals => f.write_str("mesh has no normals"),
+            Self::MissingUvs => f.write_str("mesh has no texture coordinates"),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+/// Interleaved mesh vertex
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    /// Tangent with bitangent sign in `w`
+    pub tangent: [f32; 4],
+}
+
+unsafe impl bytemuck::Zeroable for MeshVertex {}
+unsafe impl bytemuck::Pod for MeshVertex {}
+
+crate::impl_vertex!(MeshVertex { position, normal, uv, tangent });
+
+/// Range of mesh triangles drawn with single material
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Submesh {
+    /// Range in `Mesh::indices`
+    pub indices: Range<usize>,
+    /// Index of material in `MaterialLibrary::materials`
+    pub material: Option<usize>,
+}
+
+/// Indexed triangle mesh
+/// Each vertex is a unique (position, uv, normal) combination, so attribute arrays have the same length.
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub positions: Vec<Vec3<f32>>,
+    /// Vertex normals, `None` if some of source vertices have no normal
+    pub normals: Option<Vec<Vec3<f32>>>,
+    /// Vertex texture coordinates, `None` if some of source vertices have no texture coordinates
+    pub uvs: Option<Vec<Vec2<f32>>>,
+    /// Vertex tangents with bitangent sign (`bitangent = sign * normal % tangent`) in `w`
+    pub tangents: Option<Vec<Vec4<f32>>>,
+    /// Triangle list indices
+    pub indices: Vec<u32>,
+    /// Per-triangle smoothing group, 0 if smoothing is off
+    pub smoothing_groups: Vec<u32>,
+    /// Smoothing groups are declared by source mesh, otherwise all of them are 0
+    pub has_smoothing_groups: bool,
+    /// Submeshes in `indices` order, they cover all triangles
+    pub submeshes: Vec<Submesh>,
+}
+
+/// Float comparison bits getting function
+/// Adding zero turns negative zeros into positive ones, so they compare equal.
+pub(super) fn value_bits(value: f32) -> u32 {
+    (value + 0.0).to_bits()
+}
+
+/// Vertex welding key, attributes are compared by value
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub(super) struct VertexKey([u32; 8]);
+
+impl VertexKey {
+    pub(super) fn new(position: Vec3<f32>, uv: Vec2<f32>, normal: Vec3<f32>) -> Self {
+        let bits = value_bits;
+
+        Self([
+            bits(position.x),
+            bits(position.y),
+            bits(position.z),
+            bits(uv.x),
+            bits(uv.y),
+            bits(normal.x),
+            bits(normal.y),
+            bits(normal.z),
+        ])
+    }
+}
+
+impl Mesh {
+    /// Mesh from OBJ polygon mesh building function
+    /// Faces are triangulated, equal vertices are welded and degenerate triangles are removed.
+    /// Submeshes are built from `material_ranges` (see `MaterialLibrary::material_ranges`),
+    /// whole mesh is a single submesh without material if they're empty.
+    pub fn from_obj(obj: &ObjMesh, material_ranges: &[MaterialRange]) -> Self {
+        let has_uvs = obj.face_vertices.iter().all(|vertex| vertex.uv.is_some());
+        let has_normals = obj.face_vertices.iter().all(|vertex| vertex.normal.is_some());
+
+        let mut mesh = Self {
+            normals: has_normals.then(Vec::new),
+            uvs: has_uvs.then(Vec::new),
+            has_smoothing_groups: obj.has_smoothing_groups,
+            ..Default::default()
+        };
+
+        let mut welded = HashMap::<VertexKey, u32>::new();
+        let mut polygon = Vec::new();
+        let mut triangles = Vec::new();
+
+        let whole_mesh = [MaterialRange {
+            faces: 0..obj.faces.len(),
+            material: None,
+        }];
+        let ranges = if material_ranges.is_empty() { &whole_mesh[..] } else { material_ranges };
+
+        for range in ranges {
+            let start = mesh.indices.len();
+
+            for face in &obj.faces[range.faces.clone()] {
+                let vertices = obj.face_vertices(face);
+
+                polygon.clear();
+                polygon.extend(vertices.iter().map(|vertex| obj.positions[vertex.position as usize]));
+                triangles.clear();
+                triangulate_polygon(&polygon, &mut triangles);
+
+                for triangle in &triangles {
+                    for &corner in triangle {
+                        let vertex = vertices[corner];
+                        let position = obj.positions[vertex.position as usize];
+                        let uv = match (has_uvs, vertex.uv) {
+                            (true, Some(uv)) => obj.uvs[uv as usize],
+                            _ => Vec2::new(0.0, 0.0),
+                        };
+                        let normal = match (has_normals, vertex.normal) {
+                            (true, Some(normal)) => obj.normals[normal as usize],
+                            _ => Vec3::new(0.0, 0.0, 0.0),
+                        };
+
+                        let index = *welded.entry(VertexKey::new(position, uv, normal)).or_insert_with(|| {
+                            mesh.positions.push(position);
+                            if let Some(uvs) = &mut mesh.uvs {
+                                uvs.push(uv);
+                            }
+                            if let Some(normals) = &mut mesh.normals {
+                                normals.push(normal);
+                            }
+                            mesh.positions.len() as u32 - 1
+                        });
+
+                        mesh.indices.push(index);
+                    }
+
+                    mesh.smoothing_groups.push(face.smoothing_group);
+                }
+            }
+
+            mesh.submeshes.push(Submesh {
+                indices: start..mesh.indices.len(),
+                material: range.material,
+            });
+        }
+
+        mesh.remove_degenerate_triangles();
+        mesh
+    }
+
+    /// Vertex splitting function
+    /// Each triangle corner gets its own attribute value, then corners with equal welding keys
+    /// (built from mesh, corner vertex index and value) are welded. Returns attribute values for new vertices.
+    pub(super) fn split_vertices<T: Copy, K: Eq + std::hash::Hash>(
+        &mut self,
+        corner_values: &[T],
+        key: impl Fn(&Self, u32, T) -> K,
+    ) -> Vec<T> {
+        let keys = self
+            .indices
+            .iter()
+            .zip(corner_values)
+            .map(|(&index, &value)| key(self, index, value))
+            .collect::<Vec<_>>();
+
+        let mut welded = HashMap::<K, u32>::new();
+        let mut remap = Vec::new();
+        let mut values = Vec::new();
+
+        for ((index, &value), key) in self.indices.iter_mut().zip(corner_values).zip(keys) {
+            *index = *welded.entry(key).or_insert_with(|| {
+                remap.push(*index as usize);
+                values.push(value);
+                remap.len() as u32 - 1
+            });
+        }
+
+        fn gather<A: Copy>(attributes: &[A], remap: &[usize]) -> Vec<A> {
+            remap.iter().map(|&index| attributes[index]).collect()
+        }
+
+        self.positions = gather(&self.positions, &remap);
+        self.normals = self.normals.as_deref().map(|normals| gather(normals, &remap));
+        self.uvs = self.uvs.as_deref().map(|uvs| gather(uvs, &remap));
+        self.tangents = self.tangents.as_deref().map(|tangents| gather(tangents, &remap));
+
+        values
+    }
+
+    /// Vertex count getting function
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Triangle count getting function
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Degenerate triangle removing function
+    /// Triangle is degenerate if its corners are collinear up to float precision.
+    /// Returns removed triangle count, empty submeshes are removed too.
+    pub fn remove_degenerate_triangles(&mut self) -> usize {
+        let is_degenerate = |triangle: &[u32]| {
+            let [a, b, c] = [0, 1, 2].map(|corner| self.positions[triangle[corner] as usize]);
+            let (ab, ac) = (b - a, c - a);
+
+            // |ab % ac| = |ab| * |ac| * sin(angle)
+            let cross = (ab % ac).length2();
+            cross <= f32::EPSILON * f32::EPSILON * ab.length2() * ac.length2()
+        };
+
+        let mut indices = Vec::with_capacity(self.indices.len());
+        let mut smoothing_groups = Vec::with_capacity(self.smoothing_groups.len());
+        let mut submeshes = Vec::with_capacity(self.submeshes.len());
+
+        for submesh in &self.submeshes {
+            let start = indices.len();
+
+            for triangle_index in submesh.indices.start / 3..submesh.indices.end / 3 {
+                let triangle = &self.indices[triangle_index * 3..triangle_index * 3 + 3];
+                if !is_degenerate(triangle) {
+                    indices.extend_from_slice(triangle);
+                    smoothing_groups.push(self.smoothing_groups[triangle_index]);
+                }
+            }
+
+            if indices.len() != start {
+                submeshes.push(Submesh {
+                    indices: start..indices.len(),
+                    material: submesh.material,
+                });
+            }
+        }
+
+        let removed = self.triangle_count() - indices.len() / 3;
+        self.indices = indices;
+        self.smoothing_groups = smoothing_groups;
+        self.submeshes = submeshes;
+
+        removed
+    }
+
+    /// Axis-aligned bounding box calculation function, empty mesh has zero-sized box at origin
+    pub fn bounding_box(&self) -> math::Box<f32> {
+        let Some(first) = self.positions.first() else {
+            return math::Box::default();
+        };
+
+        let mut min = *first;
+        let mut max = *first;
+        for position in &self.positions[1..] {
+            min = Vec3::new(min.x.min(position.x), min.y.min(position.y), min.z.min(position.z));
+            max = Vec3::new(max.x.max(position.x), max.y.max(position.y), max.z.max(position.z));
+        }
+
+        math::Box::new(min.x..max.x, min.y..max.y, min.z..max.z)
+    }
+
+    /// Index buffer building function
+    /// Fails if 16-bit indices are requested, but mesh has too many vertices.
+    pub fn index_buffer(&self, format: IndexFormat) -> Result<Indices, MeshError> {
+        let fits_u16 = self.vertex_count() <= MAX_U16_VERTICES;
+
+        match format {
+            IndexFormat::U16 if !fits_u16 => Err(MeshError::TooManyVertices(self.vertex_count())),
+            IndexFormat::U16 | IndexFormat::Auto if fits_u16 => {
+                Ok(Indices::U16(self.indices.iter().map(|&index| index as u16).collect()))
+            }
+            _ => Ok(Indices::U32(self.indices.clone())),
+        }
+    }
+
+    /// Interleaved vertex buffer building function, missing attributes are zeroed
+    pub fn vertices(&self) -> Vec<MeshVertex> {
+        (0..self.vertex_count())
+            .map(|index| {
+                let position = self.positions[index];
+                let normal = self.normals.as_ref().map_or(Vec3::new(0.0, 0.0, 0.0), |normals| normals[index]);
+                let uv = self.uvs.as_ref().map_or(Vec2::new(0.0, 0.0), |uvs| uvs[index]);
+                let tangent = self
+                    .tangents
+                    .as_ref()
+                    .map_or(Vec4::new(0.0, 0.0, 0.0, 0.0), |tangents| tangents[index]);
+
+                MeshVertex {
+                    position: [position.x, position.y, position.z],
+                    normal: [normal.x, normal.y, normal.z],
+                    uv: [uv.x, uv.y],
+                    tangent: [tangent.x, tangent.y, tangent.z, tangent.w],
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn obj(source: &str) -> ObjMesh {
+        ObjMesh::parse(source.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn equal_vertices_are_welded() {
+        // Quad of two triangles sharing an edge, positions are duplicated in file
+        let mesh = Mesh::from_obj(
+            &obj("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf 4 5 6"),
+            &[],
+        );
+
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_eq!(mesh.indices[0], mesh.indices[3]);
+        assert_eq!(mesh.indices[2], mesh.indices[4]);
+        assert_eq!(mesh.submeshes, [Submesh { indices: 0..6, material: None }]);
+    }
+
+    #[test]
+    fn vertices_with_different_attributes_are_split() {
+        let mesh = Mesh::from_obj(
+            &obj("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nvn 0 0 -1\nf 1//1 2//1 3//1\nf 1//2 3//2 4//2"),
+            &[],
+        );
+
+        assert_eq!(mesh.vertex_count(), 6);
+        assert!(mesh.uvs.is_none());
+        assert_eq!(mesh.normals.as_ref().map(Vec::len), Some(6));
+    }
+
+    #[test]
+    fn degenerate_triangles_are_removed() {
+        let mut mesh = Mesh::from_obj(
+            &obj(concat!(
+                "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 2 0 0\n",
+                "usemtl a\nf 1 2 3\nf 1 2 4\n",
+                "usemtl b\nf 1 1 3\n",
+                "usemtl c\nf 2 3 1\n",
+            )),
+            &[
+                MaterialRange { faces: 0..2, material: Some(0) },
+                MaterialRange { faces: 2..3, material: Some(1) },
+                MaterialRange { faces: 3..4, material: Some(2) },
+            ],
+        );
+
+        // Collinear and repeated corner triangles are dropped with their empty submesh
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_eq!(mesh.smoothing_groups.len(), 2);
+        assert_eq!(
+            mesh.submeshes,
+            [Submesh { indices: 0..3, material: Some(0) }, Submesh { indices: 3..6, material: Some(2) }]
+        );
+        assert_eq!(mesh.remove_degenerate_triangles(), 0);
+    }
+
+    #[test]
+    fn index_format_limits() {
+        let mesh = |vertex_count: usize| Mesh {
+            positions: vec![Vec3::new(0.0, 0.0, 0.0); vertex_count],
+            indices: vec![0, 1, vertex_count as u32 - 1],
+            ..Default::default()
+        };
+
+        let fitting = mesh(MAX_U16_VERTICES);
+        assert_eq!(fitting.index_buffer(IndexFormat::Auto).unwrap().index_type(), vk::IndexType::UINT16);
+        assert_eq!(fitting.index_buffer(IndexFormat::U16).unwrap(), Indices::U16(vec![0, 1, 0xFFFE]));
+
+        // The largest index would be primitive restart one
+        let exceeding = mesh(MAX_U16_VERTICES + 1);
+        assert_eq!(exceeding.index_buffer(IndexFormat::Auto).unwrap().index_type(), vk::IndexType::UINT32);
+        assert_eq!(
+            exceeding.index_buffer(IndexFormat::U16),
+            Err(MeshError::TooManyVertices(MAX_U16_VERTICES + 1))
+        );
+        assert_eq!(fitting.index_buffer(IndexFormat::U32).unwrap().index_type(), vk::IndexType::UINT32);
+    }
+}
@@ -0,0 +1,10 @@
+mod indexed;
+mod normals;
+mod optimize;
+mod tangents;
+mod triangulate;
+
+pub use indexed::{IndexFormat, Indices, Mesh, MeshError, MeshVertex, Submesh};
+pub use normals::{NormalConfig, NormalWeighting};
+pub use optimize::{CacheStatistics, OptimizationReport, DEFAULT_CACHE_SIZE, DEFAULT_OVERDRAW_THRESHOLD};
+pub use triangulate::triangulate_polygon;
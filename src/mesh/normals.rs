@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use crate::utility::math::{Vec2, Vec3};
+
+use super::indexed::{value_bits, Mesh, VertexKey};
+
+/// Face normal contribution weighting
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum NormalWeighting {
+    /// Faces contribute proportionally to their area
+    Area,
+    /// Faces contribute proportionally to their angle at vertex, so result doesn't depend on tessellation
+    #[default]
+    Angle,
+    /// Faces contribute proportionally to product of their area and angle at vertex
+    AreaAngle,
+}
+
+/// Smooth normal generation configuration
+#[derive(Copy, Clone, Debug)]
+pub struct NormalConfig {
+    weighting: NormalWeighting,
+    crease_angle: f32,
+    smoothing_groups: bool,
+}
+
+impl Default for NormalConfig {
+    fn default() -> Self {
+        Self {
+            weighting: NormalWeighting::default(),
+            crease_angle: std::f32::consts::PI,
+            smoothing_groups: true,
+        }
+    }
+}
+
+impl NormalConfig {
+    /// Face normal weighting setting function
+    pub fn weighting(mut self, weighting: NormalWeighting) -> Self {
+        self.weighting = weighting;
+        self
+    }
+
+    /// Crease angle (in radians) setting function
+    /// Faces which normals differ by more than crease angle don't smooth each other. Default is PI,
+    /// so only smoothing groups split normals.
+    pub fn crease_angle(mut self, crease_angle: f32) -> Self {
+        self.crease_angle = crease_angle;
+        self
+    }
+
+    /// Smoothing group usage setting function
+    /// If enabled, only faces in the same smoothing group are smoothed together and faces with
+    /// smoothing off (group 0) are flat. Groups of meshes without `s` statements are always ignored.
+    pub fn smoothing_groups(mut self, smoothing_groups: bool) -> Self {
+        self.smoothing_groups = smoothing_groups;
+        self
+    }
+}
+
+/// Triangle geometry used by normal generation
+struct TriangleFrame {
+    /// Cross product of edges, its length is doubled triangle area
+    cross: Vec3<f32>,
+    /// Unit normal, zero for degenerate triangle
+    normal: Vec3<f32>,
+    /// Corner angles
+    angles: [f32; 3],
+}
+
+/// Angle between vectors calculation function
+fn angle_between(a: Vec3<f32>, b: Vec3<f32>) -> f32 {
+    let lengths = (a.length2() * b.length2()).sqrt();
+    if lengths == 0.0 {
+        return 0.0;
+    }
+    ((a ^ b) / lengths).clamp(-1.0, 1.0).acos()
+}
+
+impl Mesh {
+    /// Triangle frames calculation function
+    fn triangle_frames(&self) -> Vec<TriangleFrame> {
+        self.indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|corner| self.positions[triangle[corner] as usize]);
+                let cross = (b - a) % (c - a);
+                let length = cross.length();
+
+                TriangleFrame {
+                    cross,
+                    normal: if length > 0.0 { cross / length } else { cross },
+                    angles: [
+                        angle_between(b - a, c - a),
+                        angle_between(c - b, a - b),
+                        angle_between(a - c, b - c),
+                    ],
+                }
+            })
+            .collect()
+    }
+
+    /// Per-corner normals applying function, vertices are split where corner normals differ
+    /// Previous normals are replaced, so vertices differing only by them are welded.
+    fn apply_corner_normals(&mut self, corner_normals: &[Vec3<f32>]) {
+        let normals = self.split_vertices(corner_normals, |mesh, index, normal| {
+            let uv = mesh.uvs.as_ref().map_or(Vec2::new(0.0, 0.0), |uvs| uvs[index as usize]);
+            VertexKey::new(mesh.positions[index as usize], uv, normal)
+        });
+
+        self.normals = Some(normals);
+        // Tangents depend on normals, so they must be regenerated
+        self.tangents = None;
+    }
+
+    /// Flat normal generating function, existing normals are replaced
+    /// Vertices shared by faces with different normals are split.
+    pub fn generate_flat_normals(&mut self) {
+        let frames = self.triangle_frames();
+        let corner_normals = (0..self.indices.len())
+            .map(|corner| frames[corner / 3].normal)
+            .collect::<Vec<_>>();
+
+        self.apply_corner_normals(&corner_normals);
+    }
+
+    /// Smooth normal generating function, existing normals are replaced
+    /// Faces sharing vertex position are smoothed together (even across texture seams) unless
+    /// smoothing groups or crease angle separate them, vertices are split where needed.
+    pub fn generate_smooth_normals(&mut self, config: NormalConfig) {
+        let frames = self.triangle_frames();
+        let min_cos = config.crease_angle.cos();
+        // Meshes without `s` statements have all faces in group 0, which would make them flat
+        let use_groups = config.smoothing_groups && self.has_smoothing_groups;
+
+        let mut position_corners = HashMap::<[u32; 3], Vec<usize>>::new();
+        for (corner, &index) in self.indices.iter().enumerate() {
+            let position = self.positions[index as usize];
+            position_corners
+                .entry([value_bits(position.x), value_bits(position.y), value_bits(position.z)])
+                .or_default()
+                .push(corner);
+        }
+
+        let contribution = |corner: usize| {
+            let frame = &frames[corner / 3];
+            match config.weighting {
+                NormalWeighting::Area => frame.cross,
+                NormalWeighting::Angle => frame.normal * frame.angles[corner % 3],
+                NormalWeighting::AreaAngle => frame.cross * frame.angles[corner % 3],
+            }
+        };
+
+        let mut corner_normals = vec![Vec3::new(0.0, 0.0, 0.0); self.indices.len()];
+
+        for corners in position_corners.values() {
+            for &corner in corners {
+                let triangle = corner / 3;
+                let group = self.smoothing_groups[triangle];
+                let face_normal = frames[triangle].normal;
+
+                if use_groups && group == 0 {
+                    corner_normals[corner] = face_normal;
+                    continue;
+                }
+
+                let mut normal = Vec3::new(0.0, 0.0, 0.0);
+                for &other in corners {
+                    let other_triangle = other / 3;
+                    if other_triangle != triangle {
+                        if use_groups && self.smoothing_groups[other_triangle] != group {
+                            continue;
+                        }
+                        if (face_normal ^ frames[other_triangle].normal) < min_cos {
+                            continue;
+                        }
+                    }
+                    normal += contribution(other);
+                }
+
+                let length = normal.length();
+                corner_normals[corner] = if length > 0.0 { normal / length } else { face_normal };
+            }
+        }
+
+        self.apply_corner_normals(&corner_normals);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asset::obj::ObjMesh;
+
+    use super::*;
+
+    const CUBE: &str = concat!(
+        "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\nv 1 0 1\nv 1 1 1\nv 0 1 1\n",
+        "f 1 4 3 2\nf 5 6 7 8\nf 1 2 6 5\nf 3 4 8 7\nf 1 5 8 4\nf 2 3 7 6\n",
+    );
+
+    fn cube(smoothing: &str) -> Mesh {
+        let obj = ObjMesh::parse(format!("{smoothing}\n{CUBE}").as_bytes()).unwrap();
+        Mesh::from_obj(&obj, &[])
+    }
+
+    fn assert_near(a: Vec3<f32>, b: Vec3<f32>) {
+        assert!((a - b).length() < 1e-5, "{a:?} != {b:?}");
+    }
+
+    /// Corner normals (pointing away from cube center for smooth ones) checking function
+    fn assert_smooth(mesh: &Mesh) {
+        let normals = mesh.normals.as_ref().unwrap();
+        let center = Vec3::new(0.5, 0.5, 0.5);
+
+        for (&position, &normal) in mesh.positions.iter().zip(normals) {
+            let direction = position - center;
+            assert_near(normal, direction / direction.length());
+        }
+    }
+
+    fn assert_flat(mesh: &Mesh) {
+        let normals = mesh.normals.as_ref().unwrap();
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| mesh.positions[triangle[corner] as usize]);
+            let face_normal = (b - a) % (c - a);
+            for &index in triangle {
+                assert_near(normals[index as usize], face_normal / face_normal.length());
+            }
+        }
+    }
+
+    #[test]
+    fn mesh_without_smoothing_statements_is_smooth() {
+        let mut mesh = cube("");
+        mesh.generate_smooth_normals(NormalConfig::default());
+
+        assert_eq!(mesh.vertex_count(), 8);
+        assert_smooth(&mesh);
+    }
+
+    #[test]
+    fn flat_shaded_mesh_is_welded() {
+        let flat = concat!(
+            "vn 0 0 -1\nvn 0 0 1\nvn 0 -1 0\nvn 0 1 0\nvn -1 0 0\nvn 1 0 0\n",
+            "f 1//1 4//1 3//1 2//1\nf 5//2 6//2 7//2 8//2\nf 1//3 2//3 6//3 5//3\n",
+            "f 3//4 4//4 8//4 7//4\nf 1//5 5//5 8//5 4//5\nf 2//6 3//6 7//6 6//6\n",
+        );
+        let obj = ObjMesh::parse(format!("{}{flat}", &CUBE[..CUBE.find('f').unwrap()]).as_bytes()).unwrap();
+        let mut mesh = Mesh::from_obj(&obj, &[]);
+        assert_eq!(mesh.vertex_count(), 24);
+        assert_flat(&mesh);
+
+        mesh.generate_smooth_normals(NormalConfig::default());
+
+        assert_eq!(mesh.vertex_count(), 8);
+        assert_smooth(&mesh);
+    }
+
+    #[test]
+    fn smoothing_groups() {
+        let mut mesh = cube("s 1");
+        mesh.generate_smooth_normals(NormalConfig::default());
+        assert_eq!(mesh.vertex_count(), 8);
+        assert_smooth(&mesh);
+
+        let mut mesh = cube("s off");
+        mesh.generate_smooth_normals(NormalConfig::default());
+        assert_eq!(mesh.vertex_count(), 24);
+        assert_flat(&mesh);
+
+        let mut mesh = cube("s off");
+        mesh.generate_smooth_normals(NormalConfig::default().smoothing_groups(false));
+        assert_eq!(mesh.vertex_count(), 8);
+        assert_smooth(&mesh);
+    }
+
+    #[test]
+    fn crease_angle() {
+        let mut mesh = cube("");
+        mesh.generate_smooth_normals(NormalConfig::default().crease_angle(std::f32::consts::FRAC_PI_4));
+
+        assert_eq!(mesh.vertex_count(), 24);
+        assert_flat(&mesh);
+    }
+
+    #[test]
+    fn flat_normals() {
+        let mut mesh = cube("s 1");
+        mesh.tangents = Some(vec![Default::default(); mesh.vertex_count()]);
+        mesh.generate_flat_normals();
+
+        assert_eq!(mesh.vertex_count(), 24);
+        assert_flat(&mesh);
+        assert!(mesh.tangents.is_none());
+    }
+
+    #[test]
+    fn weighting_modes() {
+        // Cube faces are split into two triangles, so only angle weighting gives symmetric normals
+        for weighting in [NormalWeighting::Area, NormalWeighting::AreaAngle] {
+            let mut mesh = cube("");
+            mesh.generate_smooth_normals(NormalConfig::default().weighting(weighting));
+
+            let normals = mesh.normals.as_ref().unwrap();
+            for (&position, &normal) in mesh.positions.iter().zip(normals) {
+                let direction = position - Vec3::new(0.5, 0.5, 0.5);
+                assert!((normal.length() - 1.0).abs() < 1e-5);
+                assert!((normal ^ direction) > 0.0);
+            }
+        }
+    }
+}
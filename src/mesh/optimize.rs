@@ -0,0 +1,581 @@
+use std::{collections::HashMap, ops::Range};
+
+use crate::utility::math::Vec3;
+
+use super::indexed::Mesh;
+
+/// Simulated LRU cache size used by vertex cache optimization
+const OPTIMIZER_CACHE_SIZE: usize = 32;
+
+/// Forsyth's algorithm score parameters
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+/// FIFO cache size used for overdraw optimization clusters and in `Mesh::optimize` report
+pub const DEFAULT_CACHE_SIZE: usize = 16;
+
+/// Default overdraw optimization threshold
+pub const DEFAULT_OVERDRAW_THRESHOLD: f32 = 1.05;
+
+/// Post-transform vertex cache statistics
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CacheStatistics {
+    /// Simulated FIFO cache size
+    pub cache_size: usize,
+    /// Vertex shader invocation count
+    pub vertices_transformed: usize,
+    pub triangle_count: usize,
+    pub vertex_count: usize,
+    /// Average cache miss ratio, transformed vertices per triangle (0.5 is optimal for large meshes)
+    pub acmr: f32,
+    /// Average transform to vertex ratio, transformed vertices per vertex (1.0 is optimal)
+    pub atvr: f32,
+}
+
+impl std::fmt::Display for CacheStatistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "ACMR {:.3}, ATVR {:.3} ({} vertices transformed for {} triangles and {} vertices, FIFO cache of {})",
+            self.acmr, self.atvr, self.vertices_transformed, self.triangle_count, self.vertex_count, self.cache_size
+        ))
+    }
+}
+
+/// Mesh optimization report
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OptimizationReport {
+    pub before: CacheStatistics,
+    pub after: CacheStatistics,
+}
+
+impl std::fmt::Display for OptimizationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("before: {}\nafter: {}", self.before, self.after))
+    }
+}
+
+/// FIFO cache simulator
+struct FifoCache {
+    size: u32,
+    time: u32,
+    /// Per-vertex time of the last insertion
+    timestamps: Vec<u32>,
+}
+
+impl FifoCache {
+    fn new(size: usize, vertex_count: usize) -> Self {
+        Self {
+            size: size as u32,
+            time: size as u32 + 1,
+            timestamps: vec![0; vertex_count],
+        }
+    }
+
+    /// Vertex access function, returns true on cache miss
+    fn access(&mut self, vertex: u32) -> bool {
+        let timestamp = &mut self.timestamps[vertex as usize];
+
+        if self.time - *timestamp > self.size {
+            *timestamp = self.time;
+            self.time += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Triangle access function, returns cache miss count
+    fn access_triangle(&mut self, triangle: &[u32]) -> usize {
+        triangle.iter().filter(|&&vertex| self.access(vertex)).count()
+    }
+
+    /// Triangle list access function, returns cache miss count
+    fn access_triangles(&mut self, indices: &[u32]) -> usize {
+        indices.iter().filter(|&&vertex| self.access(vertex)).count()
+    }
+
+    /// Cache emptying function
+    fn reset(&mut self) {
+        self.time += self.size + 1;
+    }
+}
+
+/// Forsyth's vertex score calculation function
+fn vertex_score(cache_position: Option<usize>, remaining_triangles: u32) -> f32 {
+    if remaining_triangles == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        None => 0.0,
+        // Vertices of the last triangle get fixed score, so it isn't favored over its neighbors
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scale = 1.0 / (OPTIMIZER_CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+        }
+    };
+
+    cache_score + VALENCE_BOOST_SCALE * (remaining_triangles as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+/// Submesh indices to dense local vertex indices remapping function
+/// Returns local indices and local vertex count, so per-submesh work doesn't depend on mesh vertex count.
+fn local_indices(indices: &[u32]) -> (Vec<u32>, usize) {
+    let mut remap = HashMap::<u32, u32>::new();
+    let local = indices
+        .iter()
+        .map(|&index| {
+            let next = remap.len() as u32;
+            *remap.entry(index).or_insert(next)
+        })
+        .collect();
+
+    (local, remap.len())
+}
+
+/// Triangle order optimized for vertex cache by Forsyth's algorithm calculation function
+fn forsyth_order(indices: &[u32], vertex_count: usize) -> Vec<usize> {
+    let triangle_count = indices.len() / 3;
+
+    // Vertex to triangle adjacency, remaining triangles are kept at the beginning of vertex list
+    let mut remaining = vec![0u32; vertex_count];
+    for &vertex in indices {
+        remaining[vertex as usize] += 1;
+    }
+
+    let mut offsets = Vec::with_capacity(vertex_count + 1);
+    offsets.push(0);
+    for &count in &remaining {
+        offsets.push(offsets.last().unwrap() + count as usize);
+    }
+
+    let mut adjacency = vec![0usize; indices.len()];
+    let mut filled = vec![0usize; vertex_count];
+    for (corner, &vertex) in indices.iter().enumerate() {
+        let vertex = vertex as usize;
+        adjacency[offsets[vertex] + filled[vertex]] = corner / 3;
+        filled[vertex] += 1;
+    }
+
+    let mut scores = remaining
+        .iter()
+        .map(|&count| vertex_score(None, count))
+        .collect::<Vec<_>>();
+    let triangle_score = |scores: &[f32], triangle: usize| {
+        indices[triangle * 3..triangle * 3 + 3]
+            .iter()
+            .map(|&vertex| scores[vertex as usize])
+            .sum::<f32>()
+    };
+
+    let mut emitted = vec![false; triangle_count];
+    let mut order = Vec::with_capacity(triangle_count);
+    let mut cache = Vec::<u32>::with_capacity(OPTIMIZER_CACHE_SIZE + 3);
+    let mut next_cache = Vec::<u32>::with_capacity(OPTIMIZER_CACHE_SIZE + 3);
+    let mut cursor = 0;
+
+    let mut best = (0..triangle_count).max_by(|&a, &b| {
+        triangle_score(&scores, a).total_cmp(&triangle_score(&scores, b))
+    });
+
+    while let Some(triangle) = best {
+        emitted[triangle] = true;
+        order.push(triangle);
+
+        let corners = &indices[triangle * 3..triangle * 3 + 3];
+        for &vertex in corners {
+            let vertex = vertex as usize;
+            let list = &mut adjacency[offsets[vertex]..offsets[vertex] + remaining[vertex] as usize];
+            let position = list.iter().position(|&other| other == triangle).unwrap();
+            list.swap(position, list.len() - 1);
+            remaining[vertex] -= 1;
+        }
+
+        // Triangle vertices are moved to the front of LRU cache
+        next_cache.clear();
+        next_cache.extend_from_slice(corners);
+        next_cache.extend(cache.iter().filter(|vertex| !corners.contains(vertex)));
+
+        for (position, &vertex) in next_cache.iter().enumerate() {
+            let position = (position < OPTIMIZER_CACHE_SIZE).then_some(position);
+            scores[vertex as usize] = vertex_score(position, remaining[vertex as usize]);
+        }
+
+        next_cache.truncate(OPTIMIZER_CACHE_SIZE);
+        std::mem::swap(&mut cache, &mut next_cache);
+
+        // The next triangle is the best one using cached vertices
+        best = None;
+        let mut best_score = f32::MIN;
+        for &vertex in &cache {
+            let vertex = vertex as usize;
+            for &candidate in &adjacency[offsets[vertex]..offsets[vertex] + remaining[vertex] as usize] {
+                let score = triangle_score(&scores, candidate);
+                if score > best_score {
+                    best = Some(candidate);
+                    best_score = score;
+                }
+            }
+        }
+
+        // Dead end, the next not emitted triangle is used
+        if best.is_none() {
+            while cursor < triangle_count && emitted[cursor] {
+                cursor += 1;
+            }
+            best = (cursor < triangle_count).then_some(cursor);
+        }
+    }
+
+    order
+}
+
+impl Mesh {
+    /// Submesh triangle ranges getting function
+    fn submesh_triangles(&self) -> Vec<Range<usize>> {
+        self.submeshes
+            .iter()
+            .map(|submesh| submesh.indices.start / 3..submesh.indices.end / 3)
+            .collect()
+    }
+
+    /// Triangle reordering function, `order` contains old triangle indices relative to range start
+    fn reorder_triangles(&mut self, triangles: Range<usize>, order: &[usize]) {
+        let indices = order
+            .iter()
+            .flat_map(|&triangle| {
+                let start = (triangles.start + triangle) * 3;
+                [self.indices[start], self.indices[start + 1], self.indices[start + 2]]
+            })
+            .collect::<Vec<_>>();
+        let smoothing_groups = order
+            .iter()
+            .map(|&triangle| self.smoothing_groups[triangles.start + triangle])
+            .collect::<Vec<_>>();
+
+        self.indices[triangles.start * 3..triangles.end * 3].copy_from_slice(&indices);
+        self.smoothing_groups[triangles.clone()].copy_from_slice(&smoothing_groups);
+    }
+
+    /// Post-transform vertex cache statistics calculation function
+    pub fn cache_statistics(&self, cache_size: usize) -> CacheStatistics {
+        let vertices_transformed = FifoCache::new(cache_size, self.vertex_count()).access_triangles(&self.indices);
+
+        let ratio = |count: usize| match count {
+            0 => 0.0,
+            count => vertices_transformed as f32 / count as f32,
+        };
+
+        CacheStatistics {
+            cache_size,
+            vertices_transformed,
+            triangle_count: self.triangle_count(),
+            vertex_count: self.vertex_count(),
+            acmr: ratio(self.triangle_count()),
+            atvr: ratio(self.vertex_count()),
+        }
+    }
+
+    /// Vertex cache optimization function
+    /// Triangles are reordered by Forsyth's algorithm inside each submesh, so submeshes stay valid.
+    pub fn optimize_vertex_cache(&mut self) {
+        for triangles in self.submesh_triangles() {
+            let (indices, vertex_count) = local_indices(&self.indices[triangles.start * 3..triangles.end * 3]);
+            let order = forsyth_order(&indices, vertex_count);
+            self.reorder_triangles(triangles, &order);
+        }
+    }
+
+    /// Overdraw optimization function
+    /// Vertex cache optimized triangle order is split into clusters, which are sorted so outer
+    /// facing clusters are drawn first. Clusters are split further while their ACMR stays below
+    /// `threshold` times ACMR of the whole cache run, so greater threshold trades vertex cache
+    /// efficiency for less overdraw. Should be called after `optimize_vertex_cache`.
+    pub fn optimize_overdraw(&mut self, threshold: f32) {
+        for triangles in self.submesh_triangles() {
+            let indices = &self.indices[triangles.start * 3..triangles.end * 3];
+            let triangle_count = triangles.len();
+            let (local_indices, local_vertex_count) = local_indices(indices);
+
+            let mut cache = FifoCache::new(DEFAULT_CACHE_SIZE, local_vertex_count);
+            let misses = local_indices
+                .chunks_exact(3)
+                .map(|triangle| cache.access_triangle(triangle))
+                .collect::<Vec<_>>();
+
+            // Hard boundaries are triangles that miss all vertices, i.e. new cache runs
+            let mut hard_boundaries = (0..triangle_count)
+                .filter(|&triangle| triangle == 0 || misses[triangle] == 3)
+                .collect::<Vec<_>>();
+            hard_boundaries.push(triangle_count);
+
+            // Runs are split into clusters while cluster ACMR with initially empty cache stays close
+            // to run ACMR, so drawing clusters in another order costs little cache efficiency
+            let mut clusters = Vec::new();
+            for run in hard_boundaries.windows(2) {
+                let run_triangles = &local_indices[run[0] * 3..run[1] * 3];
+
+                cache.reset();
+                let run_misses = cache.access_triangles(run_triangles);
+                let max_acmr = run_misses as f32 / (run[1] - run[0]) as f32 * threshold;
+
+                cache.reset();
+                let mut start = run[0];
+                let mut cluster_misses = 0;
+                for (offset, triangle) in run_triangles.chunks_exact(3).enumerate() {
+                    let end = run[0] + offset + 1;
+                    cluster_misses += cache.access_triangle(triangle);
+
+                    if cluster_misses as f32 / (end - start) as f32 <= max_acmr && end < run[1] {
+                        clusters.push(start..end);
+                        start = end;
+                        cluster_misses = 0;
+                        cache.reset();
+                    }
+                }
+                if start < run[1] {
+                    clusters.push(start..run[1]);
+                }
+            }
+
+            // Cluster sort key is distance of its centroid from mesh centroid along cluster normal
+            let triangle_geometry = |triangle: usize| {
+                let [a, b, c] = [0, 1, 2].map(|corner| self.positions[indices[triangle * 3 + corner] as usize]);
+                let cross = (b - a) % (c - a);
+                let area = cross.length() * 0.5;
+                (cross, (a + b + c) / 3.0, area)
+            };
+
+            let mut mesh_centroid = Vec3::new(0.0, 0.0, 0.0);
+            let mut mesh_area = 0.0;
+            for triangle in 0..triangle_count {
+                let (_, centroid, area) = triangle_geometry(triangle);
+                mesh_centroid += centroid * area;
+                mesh_area += area;
+            }
+            if mesh_area > 0.0 {
+                mesh_centroid /= mesh_area;
+            }
+
+            let mut keyed_clusters = clusters
+                .into_iter()
+                .map(|cluster| {
+                    let mut normal = Vec3::new(0.0, 0.0, 0.0);
+                    let mut centroid = Vec3::new(0.0, 0.0, 0.0);
+                    let mut area = 0.0;
+
+                    for triangle in cluster.clone() {
+                        let (triangle_cross, triangle_centroid, triangle_area) = triangle_geometry(triangle);
+                        normal += triangle_cross;
+                        centroid += triangle_centroid * triangle_area;
+                        area += triangle_area;
+                    }
+
+                    let length = normal.length();
+                    let key = if length > 0.0 && area > 0.0 {
+                        (centroid / area - mesh_centroid) ^ (normal / length)
+                    } else {
+                        0.0
+                    };
+
+                    (key, cluster)
+                })
+                .collect::<Vec<_>>();
+
+            keyed_clusters.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+            let order = keyed_clusters
+                .into_iter()
+                .flat_map(|(_, cluster)| cluster)
+                .collect::<Vec<_>>();
+            self.reorder_triangles(triangles, &order);
+        }
+    }
+
+    /// Vertex fetch optimization function
+    /// Vertices are reordered by first use in index buffer, unused vertices are removed.
+    /// Should be called after triangle order optimizations.
+    pub fn optimize_vertex_fetch(&mut self) {
+        let mut remap = vec![u32::MAX; self.vertex_count()];
+        let mut order = Vec::with_capacity(self.vertex_count());
+
+        for index in &mut self.indices {
+            let new_index = &mut remap[*index as usize];
+            if *new_index == u32::MAX {
+                *new_index = order.len() as u32;
+                order.push(*index as usize);
+            }
+            *index = *new_index;
+        }
+
+        fn gather<A: Copy>(attributes: &[A], order: &[usize]) -> Vec<A> {
+            order.iter().map(|&index| attributes[index]).collect()
+        }
+
+        self.positions = gather(&self.positions, &order);
+        self.normals = self.normals.as_deref().map(|normals| gather(normals, &order));
+        self.uvs = self.uvs.as_deref().map(|uvs| gather(uvs, &order));
+        self.tangents = self.tangents.as_deref().map(|tangents| gather(tangents, &order));
+    }
+
+    /// Full index buffer optimization function
+    /// Vertex cache, overdraw (with default threshold) and vertex fetch optimizations are applied.
+    /// Returns cache statistics before and after optimization.
+    pub fn optimize(&mut self) -> OptimizationReport {
+        let before = self.cache_statistics(DEFAULT_CACHE_SIZE);
+
+        self.optimize_vertex_cache();
+        self.optimize_overdraw(DEFAULT_OVERDRAW_THRESHOLD);
+        self.optimize_vertex_fetch();
+
+        OptimizationReport {
+            before,
+            after: self.cache_statistics(DEFAULT_CACHE_SIZE),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::Submesh;
+
+    /// Grid of `size` x `size` quads split into two submeshes, triangles are shuffled inside submeshes
+    /// Smoothing group of triangle is its smallest vertex index, so it may be checked after reordering.
+    fn shuffled_grid(size: u32) -> Mesh {
+        let mut mesh = Mesh::default();
+
+        for y in 0..=size {
+            for x in 0..=size {
+                mesh.positions.push(Vec3::new(x as f32, y as f32, ((x * y) % 3) as f32 * 0.1));
+            }
+        }
+
+        let mut triangles = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                let corner = y * (size + 1) + x;
+                triangles.push([corner, corner + 1, corner + size + 2]);
+                triangles.push([corner, corner + size + 2, corner + size + 1]);
+            }
+        }
+
+        let half = triangles.len() / 2;
+        for range in [0..half, half..triangles.len()] {
+            let start = mesh.indices.len();
+            let count = range.len();
+
+            // 7919 is prime, so it's coprime with triangle count
+            for index in 0..count {
+                let triangle = triangles[range.start + index * 7919 % count];
+                mesh.indices.extend_from_slice(&triangle);
+                mesh.smoothing_groups.push(*triangle.iter().min().unwrap());
+            }
+
+            mesh.submeshes.push(Submesh {
+                indices: start..mesh.indices.len(),
+                material: Some(mesh.submeshes.len()),
+            });
+        }
+
+        mesh
+    }
+
+    /// Sorted submesh triangles getting function, triangles are compared by positions
+    fn submesh_triangles(mesh: &Mesh) -> Vec<Vec<[[u32; 3]; 3]>> {
+        mesh.submeshes
+            .iter()
+            .map(|submesh| {
+                let mut triangles = mesh.indices[submesh.indices.clone()]
+                    .chunks_exact(3)
+                    .map(|triangle| {
+                        [0, 1, 2].map(|corner| {
+                            let position = mesh.positions[triangle[corner] as usize];
+                            [position.x, position.y, position.z].map(f32::to_bits)
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                triangles.sort();
+                triangles
+            })
+            .collect()
+    }
+
+    fn assert_smoothing_groups_follow_triangles(mesh: &Mesh) {
+        for (triangle, &group) in mesh.indices.chunks_exact(3).zip(&mesh.smoothing_groups) {
+            assert_eq!(*triangle.iter().min().unwrap(), group);
+        }
+    }
+
+    #[test]
+    fn vertex_cache_optimization() {
+        let mut mesh = shuffled_grid(16);
+        let triangles = submesh_triangles(&mesh);
+        let before = mesh.cache_statistics(DEFAULT_CACHE_SIZE);
+
+        mesh.optimize_vertex_cache();
+        let after = mesh.cache_statistics(DEFAULT_CACHE_SIZE);
+
+        assert_eq!(submesh_triangles(&mesh), triangles);
+        assert_smoothing_groups_follow_triangles(&mesh);
+        assert!(after.acmr < before.acmr * 0.75, "{before} -> {after}");
+        assert_eq!(after.triangle_count, before.triangle_count);
+    }
+
+    #[test]
+    fn overdraw_optimization() {
+        let mut mesh = shuffled_grid(16);
+        let triangles = submesh_triangles(&mesh);
+
+        mesh.optimize_vertex_cache();
+        let optimized = mesh.cache_statistics(DEFAULT_CACHE_SIZE);
+
+        mesh.optimize_overdraw(DEFAULT_OVERDRAW_THRESHOLD);
+        let after = mesh.cache_statistics(DEFAULT_CACHE_SIZE);
+
+        assert_eq!(submesh_triangles(&mesh), triangles);
+        assert_smoothing_groups_follow_triangles(&mesh);
+        assert!(after.acmr <= optimized.acmr * DEFAULT_OVERDRAW_THRESHOLD, "{optimized} -> {after}");
+    }
+
+    #[test]
+    fn full_optimization() {
+        let mut mesh = shuffled_grid(16);
+        let triangles = submesh_triangles(&mesh);
+        let submeshes = mesh.submeshes.clone();
+
+        let report = mesh.optimize();
+
+        assert_eq!(submesh_triangles(&mesh), triangles);
+        assert_eq!(mesh.submeshes, submeshes);
+        assert!(report.after.acmr < report.before.acmr, "{report}");
+        assert_eq!(report.after.vertex_count, report.before.vertex_count);
+
+        // Vertex fetch order follows the first use in index buffer
+        let mut next = 0;
+        for &index in &mesh.indices {
+            assert!(index <= next);
+            next = next.max(index + 1);
+        }
+    }
+
+    #[test]
+    fn unused_vertices_are_removed() {
+        let mut mesh = shuffled_grid(2);
+        mesh.positions.push(Vec3::new(-1.0, -1.0, -1.0));
+        let triangles = submesh_triangles(&mesh);
+
+        mesh.optimize_vertex_fetch();
+
+        assert_eq!(mesh.vertex_count(), 9);
+        assert_eq!(submesh_triangles(&mesh), triangles);
+    }
+
+    #[test]
+    fn local_indices_are_dense() {
+        assert_eq!(local_indices(&[100, 7, 100, 42, 7, 5]), (vec![0, 1, 0, 2, 1, 3], 4));
+    }
+}
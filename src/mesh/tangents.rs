@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use crate::utility::math::{Vec3, Vec4};
+
+use super::indexed::{value_bits, Mesh, MeshError};
+
+/// Vector projection onto plane with unit normal `normal` function
+fn project_onto_plane(vector: Vec3<f32>, normal: Vec3<f32>) -> Vec3<f32> {
+    vector - normal * (normal ^ vector)
+}
+
+/// Vector normalization function, zero vector stays zero
+fn normalized_or_zero(vector: Vec3<f32>) -> Vec3<f32> {
+    let length = vector.length();
+    if length > 0.0 { vector / length } else { vector }
+}
+
+/// Arbitrary unit vector orthogonal to unit `normal` getting function
+fn any_orthogonal(normal: Vec3<f32>) -> Vec3<f32> {
+    let axis = if normal.x.abs() < 0.9 { Vec3::new(1.0, 0.0, 0.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+    normalized_or_zero(axis % normal)
+}
+
+/// Disjoint set root finding function with path halving
+fn find_root(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+impl Mesh {
+    /// Tangent generating function, existing tangents are replaced
+    /// Generation follows MikkTSpace conventions: triangle tangents are angle-weighted, projected
+    /// onto vertex normal plane and accumulated per vertex group, bitangent sign is stored in `w`.
+    /// Group is a set of triangles sharing vertex that are connected by edges at it and have the same
+    /// texture space orientation, so vertices are split between disconnected fans and mirrored halves.
+    pub fn generate_tangents(&mut self) -> Result<(), MeshError> {
+        let normals = self.normals.as_ref().ok_or(MeshError::MissingNormals)?;
+        let uvs = self.uvs.as_ref().ok_or(MeshError::MissingUvs)?;
+
+        // Triangle texture space tangent and orientation, `None` for zero texture space area
+        let triangle_tangents = self
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [p0, p1, p2] = [0, 1, 2].map(|corner| self.positions[triangle[corner] as usize]);
+                let [t0, t1, t2] = [0, 1, 2].map(|corner| uvs[triangle[corner] as usize]);
+
+                let (v1, v2) = (p1 - p0, p2 - p0);
+                let (d1, d2) = (t1 - t0, t2 - t0);
+
+                let signed_area = d1 % d2;
+                if signed_area == 0.0 {
+                    return None;
+                }
+
+                let orientation_preserving = signed_area > 0.0;
+                let sign = if orientation_preserving { 1.0 } else { -1.0 };
+                let tangent = normalized_or_zero(v1 * d2.y - v2 * d1.y) * sign;
+
+                Some((tangent, orientation_preserving))
+            })
+            .collect::<Vec<_>>();
+
+        // Projected tangent at corner weighted by corner angle in normal plane
+        let corner_contribution = |corner: usize, tangent: Vec3<f32>| {
+            let triangle = &self.indices[corner / 3 * 3..corner / 3 * 3 + 3];
+            let local = corner % 3;
+            let vertex = triangle[local] as usize;
+            let normal = normals[vertex];
+
+            let position = self.positions[vertex];
+            let next = self.positions[triangle[(local + 1) % 3] as usize];
+            let prev = self.positions[triangle[(local + 2) % 3] as usize];
+
+            let edge_next = normalized_or_zero(project_onto_plane(next - position, normal));
+            let edge_prev = normalized_or_zero(project_onto_plane(prev - position, normal));
+            let angle = (edge_next ^ edge_prev).clamp(-1.0, 1.0).acos();
+
+            normalized_or_zero(project_onto_plane(tangent, normal)) * angle
+        };
+
+        // Other vertices of corner triangle, corners sharing any of them are connected by edge
+        let corner_neighbors = |corner: usize| {
+            let base = corner / 3 * 3;
+            [self.indices[base + (corner + 1) % 3], self.indices[base + (corner + 2) % 3]]
+        };
+
+        let mut vertex_corners = HashMap::<u32, Vec<usize>>::new();
+        for (corner, &vertex) in self.indices.iter().enumerate() {
+            vertex_corners.entry(vertex).or_default().push(corner);
+        }
+
+        let mut corner_tangents = vec![Vec4::new(0.0, 0.0, 0.0, 0.0); self.indices.len()];
+        let mut parents = Vec::new();
+
+        for (&vertex, corners) in &vertex_corners {
+            // Corners of triangles with texture space are grouped by connectivity and orientation
+            parents.clear();
+            parents.extend(0..corners.len());
+
+            for (first_index, &first) in corners.iter().enumerate() {
+                let Some((_, first_orientation)) = triangle_tangents[first / 3] else {
+                    continue;
+                };
+                let first_neighbors = corner_neighbors(first);
+
+                for (second_index, &second) in corners.iter().enumerate().skip(first_index + 1) {
+                    let Some((_, second_orientation)) = triangle_tangents[second / 3] else {
+                        continue;
+                    };
+
+                    let connected = corner_neighbors(second)
+                        .iter()
+                        .any(|neighbor| first_neighbors.contains(neighbor));
+                    if connected && first_orientation == second_orientation {
+                        let (first_root, second_root) =
+                            (find_root(&mut parents, first_index), find_root(&mut parents, second_index));
+                        parents[second_root] = first_root;
+                    }
+                }
+            }
+
+            let mut group_tangents = HashMap::<usize, Vec3<f32>>::new();
+            for (index, &corner) in corners.iter().enumerate() {
+                if let Some((tangent, _)) = triangle_tangents[corner / 3] {
+                    *group_tangents
+                        .entry(find_root(&mut parents, index))
+                        .or_insert(Vec3::new(0.0, 0.0, 0.0)) += corner_contribution(corner, tangent);
+                }
+            }
+
+            let group_tangent = |root: usize, orientation_preserving: bool| {
+                let tangent = group_tangents
+                    .get(&root)
+                    .map_or(Vec3::new(0.0, 0.0, 0.0), |&tangent| normalized_or_zero(tangent));
+                let tangent = if tangent.length2() > 0.0 { tangent } else { any_orthogonal(normals[vertex as usize]) };
+                let sign = if orientation_preserving { 1.0 } else { -1.0 };
+
+                Vec4::new(tangent.x, tangent.y, tangent.z, sign)
+            };
+
+            // Triangles without texture space take tangent of any group sharing vertex,
+            // orientation preserving groups are preferred
+            let fallback = (0..corners.len())
+                .filter_map(|index| triangle_tangents[corners[index] / 3].map(|(_, orientation)| (index, orientation)))
+                .max_by_key(|&(_, orientation)| orientation)
+                .map(|(index, orientation)| (find_root(&mut parents, index), orientation));
+
+            for (index, &corner) in corners.iter().enumerate() {
+                corner_tangents[corner] = match triangle_tangents[corner / 3] {
+                    Some((_, orientation_preserving)) => {
+                        group_tangent(find_root(&mut parents, index), orientation_preserving)
+                    }
+                    None => match fallback {
+                        Some((root, orientation_preserving)) => group_tangent(root, orientation_preserving),
+                        None => group_tangent(usize::MAX, true),
+                    },
+                };
+            }
+        }
+
+        let tangents = self.split_vertices(&corner_tangents, |_, index, tangent| {
+            (
+                index,
+                [
+                    value_bits(tangent.x),
+                    value_bits(tangent.y),
+                    value_bits(tangent.z),
+                    value_bits(tangent.w),
+                ],
+            )
+        });
+        self.tangents = Some(tangents);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utility::math::Vec2;
+
+    use super::*;
+
+    /// Vertex position in z = 0 plane and texture coordinates
+    type PlanarVertex = ((f32, f32), (f32, f32));
+
+    /// Mesh in z = 0 plane with +Z normals create function
+    fn planar_mesh(vertices: &[PlanarVertex], indices: &[u32]) -> Mesh {
+        Mesh {
+            positions: vertices.iter().map(|&((x, y), _)| Vec3::new(x, y, 0.0)).collect(),
+            normals: Some(vec![Vec3::new(0.0, 0.0, 1.0); vertices.len()]),
+            uvs: Some(vertices.iter().map(|&(_, (u, v))| Vec2::new(u, v)).collect()),
+            indices: indices.to_vec(),
+            smoothing_groups: vec![0; indices.len() / 3],
+            ..Default::default()
+        }
+    }
+
+    fn corner_tangent(mesh: &Mesh, corner: usize) -> Vec4<f32> {
+        mesh.tangents.as_ref().unwrap()[mesh.indices[corner] as usize]
+    }
+
+    fn assert_tangent(mesh: &Mesh, corner: usize, expected: Vec4<f32>) {
+        let tangent = corner_tangent(mesh, corner);
+        let difference = Vec3::new(tangent.x - expected.x, tangent.y - expected.y, tangent.z - expected.z);
+        assert!(difference.length() < 1e-5 && tangent.w == expected.w, "corner {corner}: {tangent:?} != {expected:?}");
+    }
+
+    #[test]
+    fn quad() {
+        let mut mesh = planar_mesh(
+            &[((0.0, 0.0), (0.0, 0.0)), ((1.0, 0.0), (1.0, 0.0)), ((1.0, 1.0), (1.0, 1.0)), ((0.0, 1.0), (0.0, 1.0))],
+            &[0, 1, 2, 0, 2, 3],
+        );
+        mesh.generate_tangents().unwrap();
+
+        assert_eq!(mesh.vertex_count(), 4);
+        for corner in 0..6 {
+            assert_tangent(&mesh, corner, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn mirrored_uvs_split_vertices() {
+        // Right quad mirrors u, shared edge vertices have equal uvs
+        let mut mesh = planar_mesh(
+            &[
+                ((0.0, 0.0), (0.0, 0.0)),
+                ((1.0, 0.0), (1.0, 0.0)),
+                ((1.0, 1.0), (1.0, 1.0)),
+                ((0.0, 1.0), (0.0, 1.0)),
+                ((2.0, 0.0), (0.0, 0.0)),
+                ((2.0, 1.0), (0.0, 1.0)),
+            ],
+            &[0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2],
+        );
+        mesh.generate_tangents().unwrap();
+
+        assert_eq!(mesh.vertex_count(), 8);
+        for corner in 0..6 {
+            assert_tangent(&mesh, corner, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        }
+        for corner in 6..12 {
+            assert_tangent(&mesh, corner, Vec4::new(-1.0, 0.0, 0.0, -1.0));
+        }
+    }
+
+    #[test]
+    fn disconnected_fans_split_vertices() {
+        // Triangles share only the origin vertex and have texture spaces rotated by 90 degrees
+        let mut mesh = planar_mesh(
+            &[
+                ((0.0, 0.0), (0.0, 0.0)),
+                ((1.0, 0.0), (1.0, 0.0)),
+                ((0.0, 1.0), (0.0, 1.0)),
+                ((-1.0, 0.0), (0.0, 1.0)),
+                ((0.0, -1.0), (-1.0, 0.0)),
+            ],
+            &[0, 1, 2, 0, 3, 4],
+        );
+        mesh.generate_tangents().unwrap();
+
+        assert_eq!(mesh.vertex_count(), 6);
+        for corner in 0..3 {
+            assert_tangent(&mesh, corner, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        }
+        for corner in 3..6 {
+            assert_tangent(&mesh, corner, Vec4::new(0.0, 1.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn connected_fan_is_averaged() {
+        // Triangles share an edge, their tangents are +X and diagonal
+        let mut mesh = planar_mesh(
+            &[((0.0, 0.0), (0.0, 0.0)), ((1.0, 0.0), (1.0, 0.0)), ((0.0, 1.0), (0.0, 1.0)), ((-1.0, 0.0), (-1.0, 1.0))],
+            &[0, 1, 2, 0, 2, 3],
+        );
+        mesh.generate_tangents().unwrap();
+
+        assert_eq!(mesh.vertex_count(), 4);
+        let tangent = corner_tangent(&mesh, 0);
+        assert_eq!(tangent, corner_tangent(&mesh, 3));
+        assert!(tangent.x > tangent.y && tangent.y > 0.0 && tangent.w == 1.0, "{tangent:?}");
+    }
+
+    #[test]
+    fn degenerate_texture_space_takes_neighbor_tangent() {
+        let mut mesh = planar_mesh(
+            &[((0.0, 0.0), (0.0, 0.0)), ((1.0, 0.0), (1.0, 0.0)), ((0.0, 1.0), (0.0, 1.0)), ((-1.0, 0.0), (0.0, 0.0))],
+            &[0, 1, 2, 0, 2, 3],
+        );
+        mesh.uvs.as_mut().unwrap()[3] = Vec2::new(0.0, 0.5);
+        mesh.generate_tangents().unwrap();
+
+        assert_tangent(&mesh, 3, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        assert_tangent(&mesh, 4, Vec4::new(1.0, 0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn missing_attributes() {
+        let mut mesh = planar_mesh(&[((0.0, 0.0), (0.0, 0.0)); 3], &[0, 1, 2]);
+        mesh.uvs = None;
+        assert_eq!(mesh.generate_tangents(), Err(MeshError::MissingUvs));
+
+        mesh.normals = None;
+        assert_eq!(mesh.generate_tangents(), Err(MeshError::MissingNormals));
+    }
+}
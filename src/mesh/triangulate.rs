@@ -0,0 +1,198 @@
+use crate::utility::math::{Vec2, Vec3};
+
+/// Polygon normal by Newell's method calculation function, result isn't normalized
+fn newell_normal(positions: &[Vec3<f32>]) -> Vec3<f32> {
+    let mut normal = Vec3::new(0.0, 0.0, 0.0);
+
+    for (index, current) in positions.iter().enumerate() {
+        let next = positions[(index + 1) % positions.len()];
+
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+
+    normal
+}
+
+/// Point in triangle (edges included) check function, triangle must be counter-clockwise
+fn triangle_contains(a: Vec2<f32>, b: Vec2<f32>, c: Vec2<f32>, point: Vec2<f32>) -> bool {
+    (b - a) % (point - a) >= 0.0 && (c - b) % (point - b) >= 0.0 && (a - c) % (point - c) >= 0.0
+}
+
+/// Polygon triangulation function
+/// Convex and concave polygons are triangulated by ear clipping in the plane of polygon,
+/// triangles keep polygon winding. Resulting triangles consist of polygon vertex indices and
+/// are appended to `triangles`. Self-intersecting polygons don't fail, but may produce
+/// overlapping triangles.
+pub fn triangulate_polygon(positions: &[Vec3<f32>], triangles: &mut Vec<[usize; 3]>) {
+    let count = positions.len();
+
+    if count < 3 {
+        return;
+    }
+    if count == 3 {
+        triangles.push([0, 1, 2]);
+        return;
+    }
+
+    // Polygon is projected onto the plane of two coordinates with the smallest normal components
+    let normal = newell_normal(positions);
+    let (abs_x, abs_y, abs_z) = (normal.x.abs(), normal.y.abs(), normal.z.abs());
+
+    let (dropped_axis, facing) = if abs_z >= abs_x && abs_z >= abs_y {
+        (2, normal.z)
+    } else if abs_x >= abs_y {
+        (0, normal.x)
+    } else {
+        (1, normal.y)
+    };
+
+    // Zero-area polygon has no meaningful plane, so it's just fanned
+    if facing == 0.0 {
+        triangles.extend((1..count - 1).map(|index| [0, index, index + 1]));
+        return;
+    }
+
+    // Projected polygon is made counter-clockwise
+    let points = positions
+        .iter()
+        .map(|p| {
+            let point = match dropped_axis {
+                0 => Vec2::new(p.y, p.z),
+                1 => Vec2::new(p.z, p.x),
+                _ => Vec2::new(p.x, p.y),
+            };
+            if facing > 0.0 { point } else { Vec2::new(point.y, point.x) }
+        })
+        .collect::<Vec<_>>();
+
+    let mut remaining = (0..count).collect::<Vec<_>>();
+
+    while remaining.len() > 3 {
+        let length = remaining.len();
+        let corner = |index: usize| {
+            (
+                remaining[(index + length - 1) % length],
+                remaining[index],
+                remaining[(index + 1) % length],
+            )
+        };
+        let is_convex = |(prev, current, next): (usize, usize, usize)| {
+            (points[current] - points[prev]) % (points[next] - points[current]) > 0.0
+        };
+
+        let ear = (0..length).find(|&index| {
+            let (prev, current, next) = corner(index);
+            if !is_convex((prev, current, next)) {
+                return false;
+            }
+
+            // Vertices coincident with ear corners (e.g. from bridged holes) don't block it
+            let (a, b, c) = (points[prev], points[current], points[next]);
+            !remaining.iter().any(|&other| {
+                let point = points[other];
+                point != a && point != b && point != c && triangle_contains(a, b, c, point)
+            })
+        });
+
+        // Invalid polygons may have no ears, so any convex or at least the first corner is clipped
+        let ear = ear
+            .or_else(|| (0..length).find(|&index| is_convex(corner(index))))
+            .unwrap_or(0);
+
+        let (prev, current, next) = corner(ear);
+        triangles.push([prev, current, next]);
+        remaining.remove(ear);
+    }
+
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polygon(points: &[(f32, f32)]) -> Vec<Vec3<f32>> {
+        points.iter().map(|&(x, y)| Vec3::new(x, y, 0.0)).collect()
+    }
+
+    /// Triangulation with check of triangle count and winding, returns triangles
+    fn triangulate(positions: &[Vec3<f32>]) -> Vec<[usize; 3]> {
+        let mut triangles = Vec::new();
+        triangulate_polygon(positions, &mut triangles);
+
+        assert_eq!(triangles.len(), positions.len() - 2);
+
+        let normal = newell_normal(positions);
+        for &[a, b, c] in &triangles {
+            let triangle_normal = (positions[b] - positions[a]) % (positions[c] - positions[a]);
+            assert!((triangle_normal ^ normal) >= 0.0, "triangle {:?} has flipped winding", [a, b, c]);
+        }
+
+        triangles
+    }
+
+    /// Total triangle area calculation function
+    fn area(positions: &[Vec3<f32>], triangles: &[[usize; 3]]) -> f32 {
+        triangles
+            .iter()
+            .map(|&[a, b, c]| ((positions[b] - positions[a]) % (positions[c] - positions[a])).length() / 2.0)
+            .sum()
+    }
+
+    #[test]
+    fn convex() {
+        let positions = polygon(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]);
+        let triangles = triangulate(&positions);
+
+        assert!((area(&positions, &triangles) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn concave() {
+        // Arrow with reflex vertex at (1, 1)
+        let positions = polygon(&[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (1.0, 1.0), (0.0, 2.0)]);
+        let triangles = triangulate(&positions);
+
+        assert!((area(&positions, &triangles) - 3.0).abs() < 1e-6);
+        // Triangle spanning both arrow tips lies outside of polygon
+        assert!(!triangles.iter().any(|triangle| triangle.contains(&2) && triangle.contains(&4)));
+    }
+
+    #[test]
+    fn collinear_vertices() {
+        let positions = polygon(&[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (0.0, 1.0)]);
+        let triangles = triangulate(&positions);
+
+        assert!((area(&positions, &triangles) - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn reversed_winding() {
+        let mut positions = polygon(&[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (1.0, 1.0), (0.0, 2.0)]);
+        positions.reverse();
+        let triangles = triangulate(&positions);
+
+        assert!((area(&positions, &triangles) - 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn non_axis_aligned_plane() {
+        let positions = [(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (1.0, 1.0), (0.0, 2.0)]
+            .map(|(x, y)| Vec3::new(x, y, x + y));
+        let triangles = triangulate(&positions);
+
+        assert!((area(&positions, &triangles) - 3.0 * 3.0f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn small_polygons() {
+        let mut triangles = Vec::new();
+        triangulate_polygon(&polygon(&[(0.0, 0.0), (1.0, 0.0)]), &mut triangles);
+        assert!(triangles.is_empty());
+
+        triangulate_polygon(&polygon(&[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]), &mut triangles);
+        assert_eq!(triangles, [[0, 1, 2]]);
+    }
+}
@@ -0,0 +1,827 @@
+use std::{collections::HashMap, ptr::NonNull, sync::{Arc, Mutex}};
+
+use ash::vk;
+
+use super::{
+    error::{VulkanCallError, VulkanResultExt},
+    kernel::Kernel,
+};
+
+/// Intended memory usage
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryLocation {
+    /// Device local memory, not accessible by host
+    GpuOnly,
+    /// Host visible coherent memory for host to device transfers (e.g. staging and uniform buffers)
+    CpuToGpu,
+    /// Host visible memory for device to host transfers (e.g. readback buffers)
+    GpuToCpu,
+}
+
+impl MemoryLocation {
+    /// Required and preferred memory property flags getting function
+    fn property_flags(self) -> (vk::MemoryPropertyFlags, vk::MemoryPropertyFlags) {
+        match self {
+            Self::GpuOnly => (
+                vk::MemoryPropertyFlags::empty(),
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            ),
+            Self::CpuToGpu => (
+                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                vk::MemoryPropertyFlags::empty(),
+            ),
+            Self::GpuToCpu => (
+                vk::MemoryPropertyFlags::HOST_VISIBLE,
+                vk::MemoryPropertyFlags::HOST_CACHED | vk::MemoryPropertyFlags::HOST_COHERENT,
+            ),
+        }
+    }
+}
+
+/// Sub-allocation strategy of the memory block
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AllocationStrategy {
+    /// General purpose first-fit strategy, freed ranges are reused immediately
+    #[default]
+    FreeList,
+    /// Bump strategy for short-living allocations, block space is reused only after all its allocations are freed
+    Linear,
+}
+
+/// Allocation description
+#[derive(Copy, Clone, Debug)]
+pub struct AllocationDesc {
+    pub name: &'static str,
+    pub requirements: vk::MemoryRequirements,
+    pub location: MemoryLocation,
+    /// True for buffers and linear tiling images, false for optimal tiling images
+    pub linear: bool,
+    pub strategy: AllocationStrategy,
+    /// Forces separate `vk::DeviceMemory` for the allocation
+    pub dedicated: bool,
+}
+
+impl AllocationDesc {
+    /// Free list allocation description create function
+    pub fn new(
+        name: &'static str,
+        requirements: vk::MemoryRequirements,
+        location: MemoryLocation,
+        linear: bool,
+    ) -> Self {
+        Self {
+            name,
+            requirements,
+            location,
+            linear,
+            strategy: AllocationStrategy::FreeList,
+            dedicated: false,
+        }
+    }
+
+    /// Allocation strategy setting function
+    pub fn strategy(mut self, strategy: AllocationStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Dedicated allocation flag setting function
+    pub fn dedicated(mut self, dedicated: bool) -> Self {
+        self.dedicated = dedicated;
+        self
+    }
+}
+
+/// Memory allocation error
+#[derive(Clone, Debug)]
+pub enum AllocationError {
+    VulkanError(VulkanCallError),
+    /// There is no memory type that satisfies allocation requirements
+    NoCompatibleMemoryType,
+    /// `maxMemoryAllocationCount` device limit is reached
+    TooManyAllocations,
+}
+
+impl std::fmt::Display for AllocationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
+            Self::NoCompatibleMemoryType => f.write_str("no compatible memory type found"),
+            Self::TooManyAllocations => f.write_str("device memory allocation count limit is reached"),
+        }
+    }
+}
+
+impl std::error::Error for AllocationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::VulkanError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<VulkanCallError> for AllocationError {
+    fn from(value: VulkanCallError) -> Self {
+        Self::VulkanError(value)
+    }
+}
+
+/// Allocator configuration
+#[derive(Copy, Clone, Debug)]
+pub struct AllocatorConfig {
+    block_size: u64,
+    dedicated_threshold: u64,
+}
+
+impl Default for AllocatorConfig {
+    fn default() -> Self {
+        Self {
+            block_size: 64 * 1024 * 1024,
+            dedicated_threshold: 32 * 1024 * 1024,
+        }
+    }
+}
+
+impl AllocatorConfig {
+    /// Memory block size setting function
+    /// Blocks are made smaller for small heaps (at most 1/8 of the heap).
+    pub fn block_size(mut self, block_size: u64) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Dedicated allocation threshold setting function
+    /// Allocations of at least this size get separate `vk::DeviceMemory`.
+    pub fn dedicated_threshold(mut self, dedicated_threshold: u64) -> Self {
+        self.dedicated_threshold = dedicated_threshold;
+        self
+    }
+}
+
+/// Allocator usage statistics
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AllocatorStats {
+    /// Count of `vk::DeviceMemory` objects, including dedicated ones
+    pub device_memory_count: usize,
+    pub block_count: usize,
+    pub dedicated_allocation_count: usize,
+    pub allocation_count: usize,
+    /// Total size of `vk::DeviceMemory` objects
+    pub reserved_bytes: u64,
+    /// Total size of live allocations
+    pub allocated_bytes: u64,
+    /// Size of the largest free range in block-based pools
+    pub largest_free_range: u64,
+}
+
+impl AllocatorStats {
+    /// Block memory fragmentation getting function
+    /// Returns value in [0, 1] range, 0 means that all free block memory is contiguous.
+    pub fn fragmentation(&self) -> f32 {
+        let free_bytes = self.reserved_bytes.saturating_sub(self.allocated_bytes);
+
+        if free_bytes == 0 {
+            0.0
+        } else {
+            1.0 - (self.largest_free_range.min(free_bytes) as f64 / free_bytes as f64) as f32
+        }
+    }
+}
+
+/// Memory allocation
+/// Allocation must be returned to the allocator by `Allocator::free`,
+/// allocations that are not freed are reported as leaks on allocator drop.
+#[derive(Debug)]
+pub struct Allocation {
+    id: u64,
+    memory: vk::DeviceMemory,
+    offset: u64,
+    size: u64,
+    /// Size of whole device memory object
+    memory_size: u64,
+    memory_type_index: u32,
+    mapped_ptr: Option<NonNull<u8>>,
+    source: AllocationSource,
+}
+
+// Mapped pointer is owned by allocation, so it may be sent between threads
+unsafe impl Send for Allocation {}
+unsafe impl Sync for Allocation {}
+
+#[derive(Copy, Clone, Debug)]
+enum AllocationSource {
+    Block { pool: usize, block: usize },
+    Dedicated,
+}
+
+impl Allocation {
+    /// Device memory getting function
+    pub fn memory(&self) -> vk::DeviceMemory {
+        self.memory
+    }
+
+    /// Offset in device memory getting function
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Allocation size getting function
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Memory type index getting function
+    pub fn memory_type_index(&self) -> u32 {
+        self.memory_type_index
+    }
+
+    /// Mapped memory pointer getting function
+    /// Returns `None` for allocations in host invisible memory.
+    pub fn mapped_ptr(&self) -> Option<NonNull<u8>> {
+        self.mapped_ptr
+    }
+
+    /// Mapped memory getting function
+    pub fn mapped_slice(&self) -> Option<&[u8]> {
+        self.mapped_ptr
+            .map(|ptr| unsafe { std::slice::from_raw_parts(ptr.as_ptr(), self.size as usize) })
+    }
+
+    /// Mutable mapped memory getting function
+    pub fn mapped_slice_mut(&mut self) -> Option<&mut [u8]> {
+        self.mapped_ptr
+            .map(|ptr| unsafe { std::slice::from_raw_parts_mut(ptr.as_ptr(), self.size as usize) })
+    }
+}
+
+/// First-fit free list sub-allocator
+struct FreeList {
+    /// Free ranges (offset, size), sorted by offset
+    free: Vec<(u64, u64)>,
+}
+
+impl FreeList {
+    fn new(size: u64) -> Self {
+        Self { free: vec![(0, size)] }
+    }
+
+    fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        let (index, offset) = self.free.iter().enumerate().find_map(|(index, (offset, range_size))| {
+            let aligned_offset = offset.next_multiple_of(alignment);
+            (aligned_offset + size <= offset + range_size).then_some((index, aligned_offset))
+        })?;
+
+        let (range_offset, range_size) = self.free[index];
+        let mut split = Vec::with_capacity(2);
+
+        // Alignment padding stays free and is merged back on the neighbour free
+        if offset > range_offset {
+            split.push((range_offset, offset - range_offset));
+        }
+        if offset + size < range_offset + range_size {
+            split.push((offset + size, range_offset + range_size - offset - size));
+        }
+
+        self.free.splice(index..index + 1, split);
+
+        Some(offset)
+    }
+
+    fn free(&mut self, offset: u64, size: u64) {
+        let index = self.free.partition_point(|(free_offset, _)| *free_offset < offset);
+        self.free.insert(index, (offset, size));
+
+        // Merge with the next range
+        if index + 1 < self.free.len() && self.free[index].0 + self.free[index].1 == self.free[index + 1].0 {
+            self.free[index].1 += self.free[index + 1].1;
+            self.free.remove(index + 1);
+        }
+
+        // Merge with the previous range
+        if index > 0 && self.free[index - 1].0 + self.free[index - 1].1 == self.free[index].0 {
+            self.free[index - 1].1 += self.free[index].1;
+            self.free.remove(index);
+        }
+    }
+
+    fn largest_free_range(&self) -> u64 {
+        self.free.iter().map(|(_, size)| *size).max().unwrap_or(0)
+    }
+}
+
+/// Bump sub-allocator
+struct Linear {
+    size: u64,
+    offset: u64,
+}
+
+impl Linear {
+    fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        let offset = self.offset.next_multiple_of(alignment);
+
+        if offset + size > self.size {
+            return None;
+        }
+
+        self.offset = offset + size;
+        Some(offset)
+    }
+}
+
+enum SubAllocator {
+    FreeList(FreeList),
+    Linear(Linear),
+}
+
+/// Memory block
+struct Block {
+    memory: vk::DeviceMemory,
+    size: u64,
+    mapped_ptr: Option<NonNull<u8>>,
+    sub_allocator: SubAllocator,
+    allocation_count: usize,
+    allocated_bytes: u64,
+}
+
+// Block is accessed only under allocator state lock
+unsafe impl Send for Block {}
+
+impl Block {
+    fn allocate(&mut self, size: u64, alignment: u64) -> Option<u64> {
+        let offset = match &mut self.sub_allocator {
+            SubAllocator::FreeList(free_list) => free_list.allocate(size, alignment),
+            SubAllocator::Linear(linear) => linear.allocate(size, alignment),
+        }?;
+
+        self.allocation_count += 1;
+        self.allocated_bytes += size;
+
+        Some(offset)
+    }
+
+    fn free(&mut self, offset: u64, size: u64) {
+        self.allocation_count -= 1;
+        self.allocated_bytes -= size;
+
+        match &mut self.sub_allocator {
+            SubAllocator::FreeList(free_list) => free_list.free(offset, size),
+            SubAllocator::Linear(linear) => {
+                if self.allocation_count == 0 {
+                    linear.offset = 0;
+                }
+            }
+        }
+    }
+
+    fn largest_free_range(&self) -> u64 {
+        match &self.sub_allocator {
+            SubAllocator::FreeList(free_list) => free_list.largest_free_range(),
+            SubAllocator::Linear(linear) => linear.size - linear.offset,
+        }
+    }
+}
+
+/// Pool of blocks with the same memory type, resource kind and strategy
+struct Pool {
+    memory_type_index: u32,
+    linear: bool,
+    strategy: AllocationStrategy,
+    blocks: Vec<Option<Block>>,
+}
+
+/// Live allocation description used for leak reporting
+struct LiveAllocation {
+    name: &'static str,
+    size: u64,
+    memory_type_index: u32,
+}
+
+struct AllocatorState {
+    pools: Vec<Pool>,
+    dedicated: HashMap<u64, vk::DeviceMemory>,
+    live: HashMap<u64, LiveAllocation>,
+    next_id: u64,
+    device_memory_count: u32,
+}
+
+/// Sub-allocating device memory allocator
+pub struct Allocator {
+    kernel: Arc<Kernel>,
+    config: AllocatorConfig,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    state: Mutex<AllocatorState>,
+}
+
+impl Allocator {
+    pub fn new(kernel: Arc<Kernel>, config: AllocatorConfig) -> Self {
+        let memory_properties = unsafe {
+            kernel
+                .instance
+                .get_physical_device_memory_properties(kernel.physical_device)
+        };
+
+        Self {
+            kernel,
+            config,
+            memory_properties,
+            state: Mutex::new(AllocatorState {
+                pools: Vec::new(),
+                dedicated: HashMap::new(),
+                live: HashMap::new(),
+                next_id: 0,
+                device_memory_count: 0,
+            }),
+        }
+    }
+
+    /// Kernel getting function
+    pub fn kernel(&self) -> &Arc<Kernel> {
+        &self.kernel
+    }
+
+    /// Memory properties getting function
+    pub fn memory_properties(&self) -> &vk::PhysicalDeviceMemoryProperties {
+        &self.memory_properties
+    }
+
+    /// Memory type finding function
+    /// Returns memory types allowed by `type_bits` that have `required` flags,
+    /// ones that also have `preferred` flags go first.
+    pub fn find_memory_types(
+        &self,
+        type_bits: u32,
+        required: vk::MemoryPropertyFlags,
+        preferred: vk::MemoryPropertyFlags,
+    ) -> Vec<u32> {
+        let types = self.memory_properties.memory_types_as_slice();
+        let allowed = (0..types.len() as u32)
+            .filter(|index| type_bits & (1 << index) != 0)
+            .filter(|index| types[*index as usize].property_flags.contains(required))
+            .collect::<Vec<_>>();
+
+        let (mut result, rest): (Vec<u32>, Vec<u32>) = allowed
+            .into_iter()
+            .partition(|index| types[*index as usize].property_flags.contains(preferred));
+
+        result.extend(rest);
+        result
+    }
+
+    /// Block size for memory type getting function
+    fn block_size(&self, memory_type_index: u32) -> u64 {
+        let heap_index = self.memory_properties.memory_types[memory_type_index as usize].heap_index;
+        let heap_size = self.memory_properties.memory_heaps[heap_index as usize].size;
+
+        self.config.block_size.min(heap_size / 8).max(1)
+    }
+
+    /// Device memory allocation function, host visible memory is mapped persistently
+    fn allocate_device_memory(
+        &self,
+        state: &mut AllocatorState,
+        size: u64,
+        memory_type_index: u32,
+    ) -> Result<(vk::DeviceMemory, Option<NonNull<u8>>), AllocationError> {
+        if state.device_memory_count >= self.kernel.physical_device_properties.limits.max_memory_allocation_count {
+            return Err(AllocationError::TooManyAllocations);
+        }
+
+        let device = &self.kernel.device;
+        let memory = unsafe {
+            device.allocate_memory(
+                &vk::MemoryAllocateInfo::default()
+                    .allocation_size(size)
+                    .memory_type_index(memory_type_index),
+                None,
+            )
+        }
+        .context("vkAllocateMemory")?;
+
+        let host_visible = self.memory_properties.memory_types[memory_type_index as usize]
+            .property_flags
+            .contains(vk::MemoryPropertyFlags::HOST_VISIBLE);
+
+        let mapped_ptr = if host_visible {
+            match unsafe { device.map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) } {
+                Ok(ptr) => NonNull::new(ptr as *mut u8),
+                Err(err) => {
+                    unsafe { device.free_memory(memory, None) };
+                    return Err(VulkanCallError { call: "vkMapMemory", result: err }.into());
+                }
+            }
+        } else {
+            None
+        };
+
+        state.device_memory_count += 1;
+
+        Ok((memory, mapped_ptr))
+    }
+
+    /// Memory allocation function
+    pub fn allocate(&self, desc: &AllocationDesc) -> Result<Allocation, AllocationError> {
+        let (required, preferred) = desc.location.property_flags();
+        let memory_types = self.find_memory_types(desc.requirements.memory_type_bits, required, preferred);
+
+        if memory_types.is_empty() {
+            return Err(AllocationError::NoCompatibleMemoryType);
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let mut last_error = AllocationError::NoCompatibleMemoryType;
+
+        // Next memory type is tried if the heap of the current one is exhausted
+        for memory_type_index in memory_types {
+            let result = if desc.dedicated || desc.requirements.size >= self.config.dedicated_threshold {
+                self.allocate_dedicated(&mut state, desc, memory_type_index)
+            } else {
+                self.allocate_from_pool(&mut state, desc, memory_type_index)
+            };
+
+            match result {
+                Ok(allocation) => {
+                    state.live.insert(allocation.id, LiveAllocation {
+                        name: desc.name,
+                        size: allocation.size,
+                        memory_type_index,
+                    });
+                    return Ok(allocation);
+                }
+                Err(err @ AllocationError::VulkanError(VulkanCallError {
+                    result: vk::Result::ERROR_OUT_OF_DEVICE_MEMORY | vk::Result::ERROR_OUT_OF_HOST_MEMORY,
+                    ..
+                })) => last_error = err,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Err(last_error)
+    }
+
+    fn allocate_dedicated(
+        &self,
+        state: &mut AllocatorState,
+        desc: &AllocationDesc,
+        memory_type_index: u32,
+    ) -> Result<Allocation, AllocationError> {
+        let (memory, mapped_ptr) = self.allocate_device_memory(state, desc.requirements.size, memory_type_index)?;
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.dedicated.insert(id, memory);
+
+        Ok(Allocation {
+            id,
+            memory,
+            offset: 0,
+            size: desc.requirements.size,
+            memory_size: desc.requirements.size,
+            memory_type_index,
+            mapped_ptr,
+            source: AllocationSource::Dedicated,
+        })
+    }
+
+    fn allocate_from_pool(
+        &self,
+        state: &mut AllocatorState,
+        desc: &AllocationDesc,
+        memory_type_index: u32,
+    ) -> Result<Allocation, AllocationError> {
+        let size = desc.requirements.size;
+        let alignment = desc.requirements.alignment.max(1);
+
+        let pool_index = match state.pools.iter().position(|pool| {
+            pool.memory_type_index == memory_type_index
+                && pool.linear == desc.linear
+                && pool.strategy == desc.strategy
+        }) {
+            Some(index) => index,
+            None => {
+                state.pools.push(Pool {
+                    memory_type_index,
+                    linear: desc.linear,
+                    strategy: desc.strategy,
+                    blocks: Vec::new(),
+                });
+                state.pools.len() - 1
+            }
+        };
+
+        let existing = state.pools[pool_index]
+            .blocks
+            .iter_mut()
+            .enumerate()
+            .find_map(|(block_index, block)| {
+                let block = block.as_mut()?;
+                let offset = block.allocate(size, alignment)?;
+                Some((block_index, block.memory, block.size, block.mapped_ptr, offset))
+            });
+
+        let (block_index, memory, memory_size, block_mapped_ptr, offset) = match existing {
+            Some(existing) => existing,
+            None => {
+                let block_size = self.block_size(memory_type_index).max(size);
+                let (memory, mapped_ptr) = self.allocate_device_memory(state, block_size, memory_type_index)?;
+
+                let mut block = Block {
+                    memory,
+                    size: block_size,
+                    mapped_ptr,
+                    sub_allocator: match desc.strategy {
+                        AllocationStrategy::FreeList => SubAllocator::FreeList(FreeList::new(block_size)),
+                        AllocationStrategy::Linear => SubAllocator::Linear(Linear {
+                            size: block_size,
+                            offset: 0,
+                        }),
+                    },
+                    allocation_count: 0,
+                    allocated_bytes: 0,
+                };
+                let offset = block
+                    .allocate(size, alignment)
+                    .expect("empty block must fit allocation");
+
+                let blocks = &mut state.pools[pool_index].blocks;
+                let block_index = match blocks.iter().position(Option::is_none) {
+                    Some(index) => {
+                        blocks[index] = Some(block);
+                        index
+                    }
+                    None => {
+                        blocks.push(Some(block));
+                        blocks.len() - 1
+                    }
+                };
+
+                (block_index, memory, block_size, mapped_ptr, offset)
+            }
+        };
+
+        let id = state.next_id;
+        state.next_id += 1;
+
+        Ok(Allocation {
+            id,
+            memory,
+            offset,
+            size,
+            memory_size,
+            memory_type_index,
+            mapped_ptr: block_mapped_ptr.map(|ptr| unsafe { ptr.add(offset as usize) }),
+            source: AllocationSource::Block {
+                pool: pool_index,
+                block: block_index,
+            },
+        })
+    }
+
+    /// Allocation freeing function
+    /// # Note
+    /// Allocation must not be used by GPU during this call.
+    pub fn free(&self, allocation: Allocation) {
+        let mut state = self.state.lock().unwrap();
+        let device = &self.kernel.device;
+
+        state.live.remove(&allocation.id);
+
+        match allocation.source {
+            AllocationSource::Dedicated => {
+                if let Some(memory) = state.dedicated.remove(&allocation.id) {
+                    unsafe { device.free_memory(memory, None) };
+                    state.device_memory_count -= 1;
+                }
+            }
+            AllocationSource::Block { pool, block } => {
+                let pool = &mut state.pools[pool];
+                let Some(block_ref) = pool.blocks[block].as_mut() else {
+                    return;
+                };
+
+                block_ref.free(allocation.offset, allocation.size);
+
+                // One empty block per pool is kept to avoid allocation thrashing
+                if block_ref.allocation_count == 0 {
+                    let empty_count = pool
+                        .blocks
+                        .iter()
+                        .flatten()
+                        .filter(|block| block.allocation_count == 0)
+                        .count();
+
+                    if empty_count > 1 {
+                        if let Some(block) = pool.blocks[block].take() {
+                            unsafe { device.free_memory(block.memory, None) };
+                            state.device_memory_count -= 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Host writes to non-coherent memory flushing function
+    pub fn flush(&self, allocation: &Allocation) -> Result<(), AllocationError> {
+        if let Some(range) = self.non_coherent_range(allocation) {
+            unsafe { self.kernel.device.flush_mapped_memory_ranges(&[range]) }
+                .context("vkFlushMappedMemoryRanges")?;
+        }
+
+        Ok(())
+    }
+
+    /// Device writes to non-coherent memory invalidation function
+    pub fn invalidate(&self, allocation: &Allocation) -> Result<(), AllocationError> {
+        if let Some(range) = self.non_coherent_range(allocation) {
+            unsafe { self.kernel.device.invalidate_mapped_memory_ranges(&[range]) }
+                .context("vkInvalidateMappedMemoryRanges")?;
+        }
+
+        Ok(())
+    }
+
+    /// Mapped memory range of the non-coherent allocation getting function
+    fn non_coherent_range(&self, allocation: &Allocation) -> Option<vk::MappedMemoryRange<'static>> {
+        allocation.mapped_ptr?;
+
+        let flags = self.memory_properties.memory_types[allocation.memory_type_index as usize].property_flags;
+        if flags.contains(vk::MemoryPropertyFlags::HOST_COHERENT) {
+            return None;
+        }
+
+        let atom_size = self.kernel.physical_device_properties.limits.non_coherent_atom_size.max(1);
+        let offset = allocation.offset / atom_size * atom_size;
+
+        // Range rounded up to atom size must not exceed memory object, so its tail is covered by WHOLE_SIZE
+        let end = (allocation.offset + allocation.size).next_multiple_of(atom_size);
+        let size = if end >= allocation.memory_size { vk::WHOLE_SIZE } else { end - offset };
+
+        Some(
+            vk::MappedMemoryRange::default()
+                .memory(allocation.memory)
+                .offset(offset)
+                .size(size),
+        )
+    }
+
+    /// Statistics getting function
+    pub fn stats(&self) -> AllocatorStats {
+        let state = self.state.lock().unwrap();
+        let mut stats = AllocatorStats {
+            device_memory_count: state.device_memory_count as usize,
+            dedicated_allocation_count: state.dedicated.len(),
+            allocation_count: state.live.len(),
+            allocated_bytes: state.live.values().map(|allocation| allocation.size).sum(),
+            ..Default::default()
+        };
+
+        for block in state.pools.iter().flat_map(|pool| pool.blocks.iter().flatten()) {
+            stats.block_count += 1;
+            stats.reserved_bytes += block.size;
+            stats.largest_free_range = stats.largest_free_range.max(block.largest_free_range());
+        }
+
+        stats.reserved_bytes += state
+            .live
+            .iter()
+            .filter(|(id, _)| state.dedicated.contains_key(id))
+            .map(|(_, allocation)| allocation.size)
+            .sum::<u64>();
+
+        stats
+    }
+}
+
+impl Drop for Allocator {
+    fn drop(&mut self) {
+        let state = self.state.get_mut().unwrap();
+
+        if !state.live.is_empty() {
+            log::warn!(
+                "{} device memory allocations ({} bytes) are leaked",
+                state.live.len(),
+                state.live.values().map(|allocation| allocation.size).sum::<u64>()
+            );
+
+            for allocation in state.live.values() {
+                log::warn!(
+                    "    leaked \"{}\": {} bytes of memory type {}",
+                    allocation.name,
+                    allocation.size,
+                    allocation.memory_type_index
+                );
+            }
+        }
+
+        let device = &self.kernel.device;
+        unsafe {
+            for block in state.pools.iter_mut().flat_map(|pool| pool.blocks.drain(..).flatten()) {
+                device.free_memory(block.memory, None);
+            }
+            for (_, memory) in state.dedicated.drain() {
+                device.free_memory(memory, None);
+            }
+        }
+    }
+}
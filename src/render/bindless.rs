@@ -0,0 +1,369 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use super::{
+    error::{VulkanCallError, VulkanResultExt},
+    kernel::Kernel,
+};
+
+/// Binding of combined image sampler array in bindless set
+pub const BINDLESS_IMAGE_BINDING: u32 = 0;
+
+/// Binding of storage buffer array in bindless set
+pub const BINDLESS_BUFFER_BINDING: u32 = 1;
+
+/// Bindless image index, should be passed to shaders (e.g. by push constants)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BindlessImage(u32);
+
+impl BindlessImage {
+    /// Index in bindless image array getting function
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+/// Bindless buffer index, should be passed to shaders (e.g. by push constants)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BindlessBuffer(u32);
+
+impl BindlessBuffer {
+    /// Index in bindless buffer array getting function
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+/// Bindless table error
+#[derive(Clone, Debug)]
+pub enum BindlessError {
+    VulkanError(VulkanCallError),
+    /// Kernel is created without bindless support
+    Unsupported,
+    /// All table slots of such kind are used
+    TableFull,
+}
+
+impl std::fmt::Display for BindlessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
+            Self::Unsupported => f.write_str("bindless descriptors are not supported by kernel"),
+            Self::TableFull => f.write_str("bindless table is full"),
+        }
+    }
+}
+
+impl std::error::Error for BindlessError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::VulkanError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<VulkanCallError> for BindlessError {
+    fn from(value: VulkanCallError) -> Self {
+        Self::VulkanError(value)
+    }
+}
+
+/// Bindless table configuration
+#[derive(Copy, Clone, Debug)]
+pub struct BindlessConfig {
+    max_images: u32,
+    max_buffers: u32,
+}
+
+impl Default for BindlessConfig {
+    fn default() -> Self {
+        Self {
+            max_images: 16384,
+            max_buffers: 16384,
+        }
+    }
+}
+
+impl BindlessConfig {
+    /// Image array size setting function, it's limited by device
+    pub fn max_images(mut self, max_images: u32) -> Self {
+        self.max_images = max_images;
+        self
+    }
+
+    /// Buffer array size setting function, it's limited by device
+    pub fn max_buffers(mut self, max_buffers: u32) -> Self {
+        self.max_buffers = max_buffers;
+        self
+    }
+}
+
+/// Table slot allocator
+struct Slots {
+    capacity: u32,
+    next: u32,
+    free: Vec<u32>,
+}
+
+impl Slots {
+    fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            next: 0,
+            free: Vec::new(),
+        }
+    }
+
+    fn allocate(&mut self) -> Option<u32> {
+        if let Some(index) = self.free.pop() {
+            return Some(index);
+        }
+        if self.next == self.capacity {
+            return None;
+        }
+        self.next += 1;
+        Some(self.next - 1)
+    }
+
+    fn free(&mut self, index: u32) {
+        self.free.push(index);
+    }
+}
+
+/// Global bindless descriptor table
+/// Table consists of single set with partially bound, update-after-bind arrays of combined image
+/// samplers (binding 0) and storage buffers (binding 1). Indices stay stable until removal.
+/// # Note
+/// Removed index may be reused right away, so descriptors must not be removed while frames
+/// using them are in flight (e.g. removal should be deferred by `Render::defer_destroy`).
+pub struct BindlessTable {
+    kernel: Arc<Kernel>,
+    layout: vk::DescriptorSetLayout,
+    pool: vk::DescriptorPool,
+    set: vk::DescriptorSet,
+    images: Slots,
+    buffers: Slots,
+}
+
+impl BindlessTable {
+    /// Table create function
+    /// Fails with `BindlessError::Unsupported` if kernel has no bindless support,
+    /// classic descriptor sets should be used then.
+    pub fn new(kernel: Arc<Kernel>, config: BindlessConfig) -> Result<Self, BindlessError> {
+        let support = kernel.bindless.ok_or(BindlessError::Unsupported)?;
+
+        let max_images = config.max_images.min(support.max_sampled_images).max(1);
+        let max_buffers = config.max_buffers.min(support.max_storage_buffers).max(1);
+
+        let bindings = [
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(BINDLESS_IMAGE_BINDING)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(max_images)
+                .stage_flags(vk::ShaderStageFlags::ALL),
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(BINDLESS_BUFFER_BINDING)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(max_buffers)
+                .stage_flags(vk::ShaderStageFlags::ALL),
+        ];
+        let binding_flags = [vk::DescriptorBindingFlags::PARTIALLY_BOUND
+            | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+            | vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING; 2];
+
+        let device = &kernel.device;
+
+        let layout = unsafe {
+            device.create_descriptor_set_layout(
+                &vk::DescriptorSetLayoutCreateInfo::default()
+                    .flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+                    .bindings(&bindings)
+                    .push_next(&mut vk::DescriptorSetLayoutBindingFlagsCreateInfo::default().binding_flags(&binding_flags)),
+                None,
+            )
+        }
+        .context("vkCreateDescriptorSetLayout")?;
+
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                descriptor_count: max_images,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_BUFFER,
+                descriptor_count: max_buffers,
+            },
+        ];
+
+        let pool = match unsafe {
+            device.create_descriptor_pool(
+                &vk::DescriptorPoolCreateInfo::default()
+                    .flags(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND)
+                    .max_sets(1)
+                    .pool_sizes(&pool_sizes),
+                None,
+            )
+        } {
+            Ok(pool) => pool,
+            Err(err) => {
+                unsafe { device.destroy_descriptor_set_layout(layout, None) };
+                return Err(VulkanCallError { call: "vkCreateDescriptorPool", result: err }.into());
+            }
+        };
+
+        let set = match unsafe {
+            device.allocate_descriptor_sets(
+                &vk::DescriptorSetAllocateInfo::default()
+                    .descriptor_pool(pool)
+                    .set_layouts(&[layout]),
+            )
+        } {
+            Ok(sets) => sets[0],
+            Err(err) => {
+                unsafe {
+                    device.destroy_descriptor_pool(pool, None);
+                    device.destroy_descriptor_set_layout(layout, None);
+                }
+                return Err(VulkanCallError { call: "vkAllocateDescriptorSets", result: err }.into());
+            }
+        };
+
+        Ok(Self {
+            kernel,
+            layout,
+            pool,
+            set,
+            images: Slots::new(max_images),
+            buffers: Slots::new(max_buffers),
+        })
+    }
+
+    /// Set layout getting function, should be used in pipeline layouts
+    pub fn layout(&self) -> vk::DescriptorSetLayout {
+        self.layout
+    }
+
+    /// Table descriptor set getting function
+    pub fn set(&self) -> vk::DescriptorSet {
+        self.set
+    }
+
+    /// Image array size getting function
+    pub fn max_images(&self) -> u32 {
+        self.images.capacity
+    }
+
+    /// Buffer array size getting function
+    pub fn max_buffers(&self) -> u32 {
+        self.buffers.capacity
+    }
+
+    /// Table set binding function
+    /// # Safety
+    /// Command buffer must be in recording state and pipeline layout must contain table layout at `set_index`
+    pub unsafe fn bind(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        bind_point: vk::PipelineBindPoint,
+        pipeline_layout: vk::PipelineLayout,
+        set_index: u32,
+    ) {
+        self.kernel.device.cmd_bind_descriptor_sets(
+            command_buffer,
+            bind_point,
+            pipeline_layout,
+            set_index,
+            &[self.set],
+            &[],
+        );
+    }
+
+    /// Image descriptor writing function
+    fn write_image(&self, index: u32, view: vk::ImageView, layout: vk::ImageLayout, sampler: vk::Sampler) {
+        let image_info = vk::DescriptorImageInfo {
+            sampler,
+            image_view: view,
+            image_layout: layout,
+        };
+
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(self.set)
+            .dst_binding(BINDLESS_IMAGE_BINDING)
+            .dst_array_element(index)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(std::slice::from_ref(&image_info));
+
+        unsafe { self.kernel.device.update_descriptor_sets(&[write], &[]) };
+    }
+
+    /// Buffer descriptor writing function
+    fn write_buffer(&self, index: u32, buffer: vk::Buffer, offset: vk::DeviceSize, range: vk::DeviceSize) {
+        let buffer_info = vk::DescriptorBufferInfo { buffer, offset, range };
+
+        let write = vk::WriteDescriptorSet::default()
+            .dst_set(self.set)
+            .dst_binding(BINDLESS_BUFFER_BINDING)
+            .dst_array_element(index)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .buffer_info(std::slice::from_ref(&buffer_info));
+
+        unsafe { self.kernel.device.update_descriptor_sets(&[write], &[]) };
+    }
+
+    /// Image adding function
+    /// Image must stay in `layout` while it may be accessed by shaders.
+    pub fn add_image(
+        &mut self,
+        view: vk::ImageView,
+        layout: vk::ImageLayout,
+        sampler: vk::Sampler,
+    ) -> Result<BindlessImage, BindlessError> {
+        let index = self.images.allocate().ok_or(BindlessError::TableFull)?;
+        self.write_image(index, view, layout, sampler);
+        Ok(BindlessImage(index))
+    }
+
+    /// Image descriptor replacing function, index stays the same
+    pub fn update_image(&self, image: BindlessImage, view: vk::ImageView, layout: vk::ImageLayout, sampler: vk::Sampler) {
+        self.write_image(image.0, view, layout, sampler);
+    }
+
+    /// Image removing function, index may be reused by next added image
+    pub fn remove_image(&mut self, image: BindlessImage) {
+        self.images.free(image.0);
+    }
+
+    /// Storage buffer adding function
+    /// `vk::WHOLE_SIZE` range may be used to bind the rest of buffer.
+    pub fn add_buffer(
+        &mut self,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        range: vk::DeviceSize,
+    ) -> Result<BindlessBuffer, BindlessError> {
+        let index = self.buffers.allocate().ok_or(BindlessError::TableFull)?;
+        self.write_buffer(index, buffer, offset, range);
+        Ok(BindlessBuffer(index))
+    }
+
+    /// Buffer descriptor replacing function, index stays the same
+    pub fn update_buffer(&self, buffer: BindlessBuffer, handle: vk::Buffer, offset: vk::DeviceSize, range: vk::DeviceSize) {
+        self.write_buffer(buffer.0, handle, offset, range);
+    }
+
+    /// Buffer removing function, index may be reused by next added buffer
+    pub fn remove_buffer(&mut self, buffer: BindlessBuffer) {
+        self.buffers.free(buffer.0);
+    }
+}
+
+impl Drop for BindlessTable {
+    fn drop(&mut self) {
+        unsafe {
+            self.kernel.device.destroy_descriptor_pool(self.pool, None);
+            self.kernel.device.destroy_descriptor_set_layout(self.layout, None);
+        }
+    }
+}
@@ -0,0 +1,337 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use ash::vk;
+
+use super::{
+    allocator::{Allocation, AllocationDesc, AllocationError, Allocator, MemoryLocation},
+    error::{VulkanCallError, VulkanResultExt},
+    kernel::Kernel,
+};
+
+/// Resource creation error
+#[derive(Clone, Debug)]
+pub enum ResourceCreateError {
+    VulkanError(VulkanCallError),
+    AllocationError(AllocationError),
+    /// Resource with zero size is requested
+    EmptyResource,
+    /// Format doesn't support requested image usage with optimal tiling
+    UnsupportedFormat {
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+    },
+    /// Image description is inconsistent (e.g. cube image with layer count not divisible by 6)
+    InvalidDescription(&'static str),
+}
+
+impl std::fmt::Display for ResourceCreateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
+            Self::AllocationError(err) => f.write_fmt(format_args!("memory allocation error: {err}")),
+            Self::EmptyResource => f.write_str("resource size is zero"),
+            Self::UnsupportedFormat { format, usage } => f.write_fmt(format_args!(
+                "format {format:?} doesn't support {usage:?} usage"
+            )),
+            Self::InvalidDescription(reason) => f.write_fmt(format_args!("invalid description: {reason}")),
+        }
+    }
+}
+
+impl std::error::Error for ResourceCreateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::VulkanError(err) => Some(err),
+            Self::AllocationError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<VulkanCallError> for ResourceCreateError {
+    fn from(value: VulkanCallError) -> Self {
+        Self::VulkanError(value)
+    }
+}
+
+impl From<AllocationError> for ResourceCreateError {
+    fn from(value: AllocationError) -> Self {
+        Self::AllocationError(value)
+    }
+}
+
+/// Mapped buffer write error
+#[derive(Clone, Debug)]
+pub enum BufferWriteError {
+    /// Buffer memory is not host visible
+    NotMapped,
+    /// Written range exceeds buffer length
+    OutOfBounds {
+        offset: usize,
+        count: usize,
+        len: usize,
+    },
+    AllocationError(AllocationError),
+}
+
+impl std::fmt::Display for BufferWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotMapped => f.write_str("buffer memory is not host visible"),
+            Self::OutOfBounds { offset, count, len } => f.write_fmt(format_args!(
+                "write of {count} elements at {offset} is out of buffer bounds ({len} elements)"
+            )),
+            Self::AllocationError(err) => f.write_fmt(format_args!("memory flush error: {err}")),
+        }
+    }
+}
+
+impl std::error::Error for BufferWriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::AllocationError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<AllocationError> for BufferWriteError {
+    fn from(value: AllocationError) -> Self {
+        Self::AllocationError(value)
+    }
+}
+
+/// Typed GPU buffer
+/// Buffer is destroyed and its memory is returned to the allocator on drop.
+pub struct Buffer<T: bytemuck::Pod> {
+    kernel: Arc<Kernel>,
+    allocator: Arc<Allocator>,
+    buffer: vk::Buffer,
+    allocation: Option<Allocation>,
+    len: usize,
+    usage: vk::BufferUsageFlags,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> Buffer<T> {
+    /// Buffer of `len` elements create function
+    pub fn new(
+        allocator: &Arc<Allocator>,
+        name: &'static str,
+        len: usize,
+        usage: vk::BufferUsageFlags,
+        location: MemoryLocation,
+    ) -> Result<Self, ResourceCreateError> {
+        let size = len
+            .checked_mul(std::mem::size_of::<T>())
+            .ok_or(ResourceCreateError::InvalidDescription("buffer size overflows usize"))? as u64;
+
+        if size == 0 {
+            return Err(ResourceCreateError::EmptyResource);
+        }
+
+        let kernel = allocator.kernel().clone();
+        let device = &kernel.device;
+
+        let buffer = unsafe {
+            device.create_buffer(
+                &vk::BufferCreateInfo::default()
+                    .size(size)
+                    .usage(usage)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE),
+                None,
+            )
+        }
+        .context("vkCreateBuffer")?;
+
+        let mut requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+        // Mapped memory is accessed as `[T]`
+        requirements.alignment = requirements.alignment.max(std::mem::align_of::<T>() as u64);
+
+        let allocation = match allocator.allocate(&AllocationDesc::new(name, requirements, location, true)) {
+            Ok(allocation) => allocation,
+            Err(err) => {
+                unsafe { device.destroy_buffer(buffer, None) };
+                return Err(err.into());
+            }
+        };
+
+        if let Err(err) = unsafe {
+            device.bind_buffer_memory(buffer, allocation.memory(), allocation.offset())
+        } {
+            unsafe { device.destroy_buffer(buffer, None) };
+            allocator.free(allocation);
+            return Err(VulkanCallError { call: "vkBindBufferMemory", result: err }.into());
+        }
+
+        Ok(Self {
+            kernel: kernel.clone(),
+            allocator: allocator.clone(),
+            buffer,
+            allocation: Some(allocation),
+            len,
+            usage,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Host visible buffer with initial data create function
+    pub fn from_slice(
+        allocator: &Arc<Allocator>,
+        name: &'static str,
+        data: &[T],
+        usage: vk::BufferUsageFlags,
+    ) -> Result<Self, ResourceCreateError> {
+        let mut buffer = Self::new(allocator, name, data.len(), usage, MemoryLocation::CpuToGpu)?;
+
+        match buffer.write(0, data) {
+            Ok(()) => Ok(buffer),
+            Err(BufferWriteError::AllocationError(err)) => Err(err.into()),
+            Err(_) => unreachable!("host visible buffer of data length must be writable"),
+        }
+    }
+
+    /// Vertex buffer create function
+    pub fn vertex(
+        allocator: &Arc<Allocator>,
+        name: &'static str,
+        len: usize,
+        location: MemoryLocation,
+    ) -> Result<Self, ResourceCreateError> {
+        Self::new(allocator, name, len, vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST, location)
+    }
+
+    /// Index buffer create function
+    pub fn index(
+        allocator: &Arc<Allocator>,
+        name: &'static str,
+        len: usize,
+        location: MemoryLocation,
+    ) -> Result<Self, ResourceCreateError> {
+        Self::new(allocator, name, len, vk::BufferUsageFlags::INDEX_BUFFER | vk::BufferUsageFlags::TRANSFER_DST, location)
+    }
+
+    /// Uniform buffer create function
+    pub fn uniform(
+        allocator: &Arc<Allocator>,
+        name: &'static str,
+        len: usize,
+        location: MemoryLocation,
+    ) -> Result<Self, ResourceCreateError> {
+        Self::new(allocator, name, len, vk::BufferUsageFlags::UNIFORM_BUFFER | vk::BufferUsageFlags::TRANSFER_DST, location)
+    }
+
+    /// Storage buffer create function
+    pub fn storage(
+        allocator: &Arc<Allocator>,
+        name: &'static str,
+        len: usize,
+        location: MemoryLocation,
+    ) -> Result<Self, ResourceCreateError> {
+        Self::new(
+            allocator,
+            name,
+            len,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST,
+            location,
+        )
+    }
+
+    /// Indirect command buffer create function
+    pub fn indirect(
+        allocator: &Arc<Allocator>,
+        name: &'static str,
+        len: usize,
+        location: MemoryLocation,
+    ) -> Result<Self, ResourceCreateError> {
+        Self::new(
+            allocator,
+            name,
+            len,
+            vk::BufferUsageFlags::INDIRECT_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+            location,
+        )
+    }
+
+    /// Buffer handle getting function
+    pub fn handle(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    /// Buffer length (in elements) getting function
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Buffer emptiness checking function, always false as empty buffers can't be created
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Buffer size (in bytes) getting function
+    pub fn size(&self) -> u64 {
+        (self.len * std::mem::size_of::<T>()) as u64
+    }
+
+    /// Buffer usage getting function
+    pub fn usage(&self) -> vk::BufferUsageFlags {
+        self.usage
+    }
+
+    /// Buffer memory allocation getting function
+    pub fn allocation(&self) -> &Allocation {
+        self.allocation.as_ref().unwrap()
+    }
+
+    /// Mapped buffer contents getting function
+    /// Returns `None` if buffer memory isn't host visible.
+    pub fn mapped(&self) -> Option<&[T]> {
+        let bytes = self.allocation.as_ref()?.mapped_slice()?;
+        Some(bytemuck::cast_slice(&bytes[..self.size() as usize]))
+    }
+
+    /// Mutable mapped buffer contents getting function
+    /// # Note
+    /// Changes must be flushed by `flush` call if memory isn't host coherent.
+    pub fn mapped_mut(&mut self) -> Option<&mut [T]> {
+        let size = self.size() as usize;
+        let bytes = self.allocation.as_mut()?.mapped_slice_mut()?;
+        Some(bytemuck::cast_slice_mut(&mut bytes[..size]))
+    }
+
+    /// Mapped memory flushing function
+    pub fn flush(&self) -> Result<(), AllocationError> {
+        self.allocator.flush(self.allocation())
+    }
+
+    /// Data to mapped buffer writing function
+    /// Written data is flushed if memory isn't host coherent.
+    pub fn write(&mut self, offset: usize, data: &[T]) -> Result<(), BufferWriteError> {
+        let len = self.len;
+
+        if offset.checked_add(data.len()).is_none_or(|end| end > len) {
+            return Err(BufferWriteError::OutOfBounds {
+                offset,
+                count: data.len(),
+                len,
+            });
+        }
+
+        let mapped = self.mapped_mut().ok_or(BufferWriteError::NotMapped)?;
+        mapped[offset..offset + data.len()].copy_from_slice(data);
+
+        self.flush()?;
+
+        Ok(())
+    }
+}
+
+impl<T: bytemuck::Pod> Drop for Buffer<T> {
+    fn drop(&mut self) {
+        unsafe { self.kernel.device.destroy_buffer(self.buffer, None) };
+
+        if let Some(allocation) = self.allocation.take() {
+            self.allocator.free(allocation);
+        }
+    }
+}
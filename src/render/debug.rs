@@ -0,0 +1,376 @@
+use std::{
+    ffi::CStr,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use ash::vk;
+
+/// Debug message severity
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DebugMessageSeverity {
+    Verbose,
+    Info,
+    Warning,
+    Error,
+}
+
+impl DebugMessageSeverity {
+    /// Severity from vulkan flags getting function
+    /// The highest severity is selected if several flags are set.
+    pub fn from_flags(flags: vk::DebugUtilsMessageSeverityFlagsEXT) -> Self {
+        if flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::ERROR) {
+            Self::Error
+        } else if flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::WARNING) {
+            Self::Warning
+        } else if flags.contains(vk::DebugUtilsMessageSeverityFlagsEXT::INFO) {
+            Self::Info
+        } else {
+            Self::Verbose
+        }
+    }
+}
+
+impl std::fmt::Display for DebugMessageSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Verbose => "VERBOSE",
+            Self::Info => "INFO",
+            Self::Warning => "WARNING",
+            Self::Error => "ERROR",
+        })
+    }
+}
+
+/// Object referenced by debug message
+#[derive(Clone, Debug)]
+pub struct DebugObject {
+    pub object_type: vk::ObjectType,
+    pub handle: u64,
+    /// Debug name set by `vkSetDebugUtilsObjectNameEXT`
+    pub name: Option<String>,
+}
+
+/// Parsed debug messenger message
+#[derive(Clone, Debug)]
+pub struct DebugMessage {
+    pub severity: DebugMessageSeverity,
+    pub types: vk::DebugUtilsMessageTypeFlagsEXT,
+    pub message_id_name: Option<String>,
+    pub message_id_number: i32,
+    pub message: String,
+    pub objects: Vec<DebugObject>,
+    pub queue_labels: Vec<String>,
+    pub command_buffer_labels: Vec<String>,
+}
+
+impl DebugMessage {
+    /// Validation error checking function
+    pub fn is_validation_error(&self) -> bool {
+        self.severity == DebugMessageSeverity::Error
+            && self.types.contains(vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION)
+    }
+
+    /// Message from vulkan callback data parsing function
+    /// # Safety
+    /// Callback data must be valid, as it's guaranteed during debug callback execution
+    unsafe fn from_callback_data(
+        severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+        types: vk::DebugUtilsMessageTypeFlagsEXT,
+        data: &vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+    ) -> Self {
+        unsafe fn slice<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+            if ptr.is_null() || count == 0 {
+                &[]
+            } else {
+                std::slice::from_raw_parts(ptr, count as usize)
+            }
+        }
+
+        fn to_string(str: Option<&CStr>) -> Option<String> {
+            str.map(|str| str.to_string_lossy().into_owned())
+        }
+
+        let labels = |ptr, count| {
+            slice::<vk::DebugUtilsLabelEXT>(ptr, count)
+                .iter()
+                .filter_map(|label| to_string(label.label_name_as_c_str()))
+                .collect::<Vec<_>>()
+        };
+
+        Self {
+            severity: DebugMessageSeverity::from_flags(severity),
+            types,
+            message_id_name: to_string(data.message_id_name_as_c_str()),
+            message_id_number: data.message_id_number,
+            message: to_string(data.message_as_c_str()).unwrap_or_else(|| "<unknown>".to_string()),
+            objects: slice(data.p_objects, data.object_count)
+                .iter()
+                .map(|object| DebugObject {
+                    object_type: object.object_type,
+                    handle: object.object_handle,
+                    name: to_string(object.object_name_as_c_str()),
+                })
+                .collect(),
+            queue_labels: labels(data.p_queue_labels, data.queue_label_count),
+            command_buffer_labels: labels(data.p_cmd_buf_labels, data.cmd_buf_label_count),
+        }
+    }
+}
+
+impl std::fmt::Display for DebugMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "VULKAN DEBUG MESSAGE")?;
+        writeln!(f, "    SEVERITY: {}", self.severity)?;
+        writeln!(f, "    TYPE: {:?}", self.types)?;
+        writeln!(
+            f,
+            "    ID: {} ({})",
+            self.message_id_name.as_deref().unwrap_or("<unnamed>"),
+            self.message_id_number
+        )?;
+
+        for object in &self.objects {
+            writeln!(
+                f,
+                "    OBJECT: {:?} 0x{:x} {}",
+                object.object_type,
+                object.handle,
+                object.name.as_deref().unwrap_or("")
+            )?;
+        }
+
+        if !self.queue_labels.is_empty() {
+            writeln!(f, "    QUEUE LABELS: {}", self.queue_labels.join(" > "))?;
+        }
+
+        if !self.command_buffer_labels.is_empty() {
+            writeln!(f, "    COMMAND BUFFER LABELS: {}", self.command_buffer_labels.join(" > "))?;
+        }
+
+        write!(f, "    MESSAGE: {}", self.message)
+    }
+}
+
+/// Debug message receiver
+pub trait DebugSink: Send + Sync {
+    /// Message handling function
+    fn message(&self, message: &DebugMessage);
+}
+
+/// Sink that writes messages to standard error stream
+#[derive(Copy, Clone, Debug, Default)]
+pub struct StderrSink;
+
+impl DebugSink for StderrSink {
+    fn message(&self, message: &DebugMessage) {
+        eprintln!("{message}");
+    }
+}
+
+/// Sink that forwards messages to the `log` facade with "vulkan" target
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LogSink;
+
+impl DebugSink for LogSink {
+    fn message(&self, message: &DebugMessage) {
+        let level = match message.severity {
+            DebugMessageSeverity::Verbose => log::Level::Trace,
+            DebugMessageSeverity::Info => log::Level::Info,
+            DebugMessageSeverity::Warning => log::Level::Warn,
+            DebugMessageSeverity::Error => log::Level::Error,
+        };
+
+        log::log!(
+            target: "vulkan",
+            level,
+            "[{}] {}",
+            message.message_id_name.as_deref().unwrap_or("<unnamed>"),
+            message.message
+        );
+    }
+}
+
+/// Sink that stores messages in memory, e.g. to check them in tests
+#[derive(Debug, Default)]
+pub struct CollectorSink {
+    messages: Mutex<Vec<DebugMessage>>,
+}
+
+impl CollectorSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Message list locking function
+    /// Poisoned lock is recovered, as message list can't be left in invalid state.
+    fn lock(&self) -> MutexGuard<'_, Vec<DebugMessage>> {
+        self.messages.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Collected messages getting function
+    pub fn messages(&self) -> Vec<DebugMessage> {
+        self.lock().clone()
+    }
+
+    /// Collected messages with at least `severity` severity counting function
+    pub fn count(&self, severity: DebugMessageSeverity) -> usize {
+        self.lock()
+            .iter()
+            .filter(|message| message.severity >= severity)
+            .count()
+    }
+
+    /// Collected validation errors counting function
+    pub fn validation_error_count(&self) -> usize {
+        self.lock()
+            .iter()
+            .filter(|message| message.is_validation_error())
+            .count()
+    }
+
+    /// Collected messages removing function
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+}
+
+impl DebugSink for CollectorSink {
+    fn message(&self, message: &DebugMessage) {
+        self.lock().push(message.clone());
+    }
+}
+
+/// Debug messenger callback
+/// User data must point to `Arc<dyn DebugSink>`.
+pub(super) extern "system" fn vulkan_debug_callback(
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    message_types: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
+    p_user_data: *mut std::ffi::c_void,
+) -> vk::Bool32 {
+    let (Some(data), Some(sink)) = (unsafe { p_callback_data.as_ref() }, unsafe {
+        (p_user_data as *const std::sync::Arc<dyn DebugSink>).as_ref()
+    }) else {
+        return vk::FALSE;
+    };
+
+    // Panics must not unwind across FFI boundary, panic hook has already reported it
+    _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        sink.message(&unsafe { DebugMessage::from_callback_data(message_severity, message_types, data) });
+    }));
+
+    vk::FALSE
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use ash::vk::Handle;
+
+    use super::*;
+
+    fn message(severity: DebugMessageSeverity, types: vk::DebugUtilsMessageTypeFlagsEXT) -> DebugMessage {
+        DebugMessage {
+            severity,
+            types,
+            message_id_name: Some("VUID-Test".to_string()),
+            message_id_number: 42,
+            message: "test message".to_string(),
+            objects: Vec::new(),
+            queue_labels: Vec::new(),
+            command_buffer_labels: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn severity_from_flags() {
+        type Flags = vk::DebugUtilsMessageSeverityFlagsEXT;
+
+        assert_eq!(DebugMessageSeverity::from_flags(Flags::VERBOSE), DebugMessageSeverity::Verbose);
+        assert_eq!(DebugMessageSeverity::from_flags(Flags::INFO), DebugMessageSeverity::Info);
+        assert_eq!(DebugMessageSeverity::from_flags(Flags::WARNING | Flags::INFO), DebugMessageSeverity::Warning);
+        assert_eq!(DebugMessageSeverity::from_flags(Flags::ERROR | Flags::WARNING), DebugMessageSeverity::Error);
+    }
+
+    #[test]
+    fn collector_counts_messages() {
+        type Types = vk::DebugUtilsMessageTypeFlagsEXT;
+
+        let sink = CollectorSink::new();
+        assert_eq!(sink.validation_error_count(), 0);
+
+        sink.message(&message(DebugMessageSeverity::Info, Types::GENERAL));
+        sink.message(&message(DebugMessageSeverity::Warning, Types::VALIDATION));
+        sink.message(&message(DebugMessageSeverity::Error, Types::PERFORMANCE));
+        sink.message(&message(DebugMessageSeverity::Error, Types::VALIDATION | Types::GENERAL));
+
+        assert_eq!(sink.messages().len(), 4);
+        assert_eq!(sink.count(DebugMessageSeverity::Verbose), 4);
+        assert_eq!(sink.count(DebugMessageSeverity::Warning), 3);
+        assert_eq!(sink.count(DebugMessageSeverity::Error), 2);
+        assert_eq!(sink.validation_error_count(), 1);
+
+        sink.clear();
+        assert!(sink.messages().is_empty());
+        assert_eq!(sink.validation_error_count(), 0);
+    }
+
+    #[test]
+    fn collector_recovers_from_poisoning() {
+        let sink = CollectorSink::new();
+        sink.message(&message(DebugMessageSeverity::Error, vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION));
+
+        let result = std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _messages = sink.lock();
+                    panic!("poisoning collector lock");
+                })
+                .join()
+        });
+        assert!(result.is_err());
+        assert!(sink.messages.is_poisoned());
+
+        sink.message(&message(DebugMessageSeverity::Error, vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION));
+        assert_eq!(sink.validation_error_count(), 2);
+    }
+
+    #[test]
+    fn callback_forwards_messages() {
+        let collector = Arc::new(CollectorSink::new());
+        let sink: Arc<dyn DebugSink> = collector.clone();
+
+        let labels = [vk::DebugUtilsLabelEXT::default().label_name(c"frame"), vk::DebugUtilsLabelEXT::default()];
+        let objects = [vk::DebugUtilsObjectNameInfoEXT::default()
+            .object_handle(vk::Buffer::from_raw(0x10))
+            .object_name(c"vertices")];
+        let data = vk::DebugUtilsMessengerCallbackDataEXT::default()
+            .message_id_name(c"VUID-Test")
+            .message_id_number(7)
+            .message(c"validation failed")
+            .objects(&objects)
+            .cmd_buf_labels(&labels);
+
+        let result = vulkan_debug_callback(
+            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            &data,
+            &sink as *const Arc<dyn DebugSink> as *mut std::ffi::c_void,
+        );
+        assert_eq!(result, vk::FALSE);
+
+        let messages = collector.messages();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].is_validation_error());
+        assert_eq!(messages[0].message_id_name.as_deref(), Some("VUID-Test"));
+        assert_eq!(messages[0].message_id_number, 7);
+        assert_eq!(messages[0].message, "validation failed");
+        assert_eq!(messages[0].objects.len(), 1);
+        assert_eq!(messages[0].objects[0].object_type, vk::ObjectType::BUFFER);
+        assert_eq!(messages[0].objects[0].handle, 0x10);
+        assert_eq!(messages[0].objects[0].name.as_deref(), Some("vertices"));
+        assert!(messages[0].queue_labels.is_empty());
+        assert_eq!(messages[0].command_buffer_labels, ["frame"]);
+    }
+}
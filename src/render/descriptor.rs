@@ -0,0 +1,458 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ash::vk;
+
+use super::{
+    error::{VulkanCallError, VulkanResultExt},
+    kernel::Kernel,
+};
+
+/// Descriptor set layout binding
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DescriptorBinding {
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    pub count: u32,
+    pub stages: vk::ShaderStageFlags,
+}
+
+impl DescriptorBinding {
+    /// Single descriptor binding create function
+    pub fn new(binding: u32, descriptor_type: vk::DescriptorType, stages: vk::ShaderStageFlags) -> Self {
+        Self {
+            binding,
+            descriptor_type,
+            count: 1,
+            stages,
+        }
+    }
+
+    /// Descriptor array size setting function
+    pub fn count(mut self, count: u32) -> Self {
+        self.count = count;
+        self
+    }
+}
+
+/// Descriptor set layout cache
+/// Layouts with identical bindings are created once and shared.
+pub struct DescriptorLayoutCache {
+    kernel: Arc<Kernel>,
+    layouts: HashMap<Vec<DescriptorBinding>, vk::DescriptorSetLayout>,
+}
+
+impl DescriptorLayoutCache {
+    pub fn new(kernel: Arc<Kernel>) -> Self {
+        Self {
+            kernel,
+            layouts: HashMap::new(),
+        }
+    }
+
+    /// Layout with bindings getting function, layout is created if there is no such one
+    /// Binding order doesn't matter.
+    pub fn get(&mut self, bindings: &[DescriptorBinding]) -> Result<vk::DescriptorSetLayout, VulkanCallError> {
+        let mut key = bindings.to_vec();
+        key.sort_by_key(|binding| binding.binding);
+
+        if let Some(layout) = self.layouts.get(&key) {
+            return Ok(*layout);
+        }
+
+        let vk_bindings = key
+            .iter()
+            .map(|binding| {
+                vk::DescriptorSetLayoutBinding::default()
+                    .binding(binding.binding)
+                    .descriptor_type(binding.descriptor_type)
+                    .descriptor_count(binding.count)
+                    .stage_flags(binding.stages)
+            })
+            .collect::<Vec<_>>();
+
+        let layout = unsafe {
+            self.kernel.device.create_descriptor_set_layout(
+                &vk::DescriptorSetLayoutCreateInfo::default().bindings(&vk_bindings),
+                None,
+            )
+        }
+        .context("vkCreateDescriptorSetLayout")?;
+
+        self.layouts.insert(key, layout);
+
+        Ok(layout)
+    }
+
+    /// Count of cached layouts getting function
+    pub fn len(&self) -> usize {
+        self.layouts.len()
+    }
+
+    /// Cache emptiness checking function
+    pub fn is_empty(&self) -> bool {
+        self.layouts.is_empty()
+    }
+}
+
+impl Drop for DescriptorLayoutCache {
+    fn drop(&mut self) {
+        for layout in self.layouts.values() {
+            unsafe { self.kernel.device.destroy_descriptor_set_layout(*layout, None) };
+        }
+    }
+}
+
+/// Descriptor allocator configuration
+#[derive(Clone, Debug)]
+pub struct DescriptorAllocatorConfig {
+    initial_sets: u32,
+    max_sets: u32,
+    /// Count of descriptors of type per set in pool
+    pool_ratios: Vec<(vk::DescriptorType, f32)>,
+}
+
+impl Default for DescriptorAllocatorConfig {
+    fn default() -> Self {
+        Self {
+            initial_sets: 64,
+            max_sets: 4096,
+            pool_ratios: vec![
+                (vk::DescriptorType::SAMPLER, 0.5),
+                (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 4.0),
+                (vk::DescriptorType::SAMPLED_IMAGE, 4.0),
+                (vk::DescriptorType::STORAGE_IMAGE, 1.0),
+                (vk::DescriptorType::UNIFORM_BUFFER, 2.0),
+                (vk::DescriptorType::STORAGE_BUFFER, 2.0),
+                (vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 1.0),
+                (vk::DescriptorType::STORAGE_BUFFER_DYNAMIC, 1.0),
+                (vk::DescriptorType::INPUT_ATTACHMENT, 0.5),
+            ],
+        }
+    }
+}
+
+impl DescriptorAllocatorConfig {
+    /// Count of sets in the first pool setting function
+    /// Each next pool is twice as large as the previous one.
+    pub fn initial_sets(mut self, initial_sets: u32) -> Self {
+        self.initial_sets = initial_sets.max(1);
+        self
+    }
+
+    /// Maximal count of sets in pool setting function
+    pub fn max_sets(mut self, max_sets: u32) -> Self {
+        self.max_sets = max_sets.max(1);
+        self
+    }
+
+    /// Count of descriptors of type per set setting function
+    pub fn pool_ratio(mut self, descriptor_type: vk::DescriptorType, ratio: f32) -> Self {
+        match self.pool_ratios.iter_mut().find(|(ty, _)| *ty == descriptor_type) {
+            Some((_, pool_ratio)) => *pool_ratio = ratio,
+            None => self.pool_ratios.push((descriptor_type, ratio)),
+        }
+        self
+    }
+}
+
+/// Descriptor set allocation error
+#[derive(Clone, Debug)]
+pub enum DescriptorAllocateError {
+    VulkanError(VulkanCallError),
+    /// Set doesn't fit into empty pool, so pool ratios don't cover its descriptor types or counts
+    LayoutExceedsPool,
+}
+
+impl std::fmt::Display for DescriptorAllocateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
+            Self::LayoutExceedsPool => {
+                f.write_str("descriptor set layout exceeds empty pool, pool ratios must cover its descriptors")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DescriptorAllocateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::VulkanError(err) => Some(err),
+            Self::LayoutExceedsPool => None,
+        }
+    }
+}
+
+impl From<VulkanCallError> for DescriptorAllocateError {
+    fn from(value: VulkanCallError) -> Self {
+        Self::VulkanError(value)
+    }
+}
+
+/// Growable descriptor set allocator
+/// New pool is created when current one runs out of memory. Sets can't be freed
+/// individually, all of them are freed by `reset`.
+pub struct DescriptorAllocator {
+    kernel: Arc<Kernel>,
+    config: DescriptorAllocatorConfig,
+    /// Pools sets may be allocated from, the last one is current
+    ready_pools: Vec<vk::DescriptorPool>,
+    /// Pools out of memory
+    full_pools: Vec<vk::DescriptorPool>,
+    next_pool_sets: u32,
+    /// Any set is allocated from current pool since its creation or reset
+    current_pool_used: bool,
+}
+
+impl DescriptorAllocator {
+    pub fn new(kernel: Arc<Kernel>, config: DescriptorAllocatorConfig) -> Self {
+        let next_pool_sets = config.initial_sets.min(config.max_sets);
+
+        Self {
+            kernel,
+            config,
+            ready_pools: Vec::new(),
+            full_pools: Vec::new(),
+            next_pool_sets,
+            current_pool_used: false,
+        }
+    }
+
+    /// Descriptor pool create function
+    fn create_pool(&mut self) -> Result<vk::DescriptorPool, VulkanCallError> {
+        let sets = self.next_pool_sets;
+        let pool_sizes = self
+            .config
+            .pool_ratios
+            .iter()
+            .filter(|(_, ratio)| *ratio > 0.0)
+            .map(|(ty, ratio)| vk::DescriptorPoolSize {
+                ty: *ty,
+                descriptor_count: ((sets as f32 * ratio) as u32).max(1),
+            })
+            .collect::<Vec<_>>();
+
+        let pool = unsafe {
+            self.kernel.device.create_descriptor_pool(
+                &vk::DescriptorPoolCreateInfo::default()
+                    .max_sets(sets)
+                    .pool_sizes(&pool_sizes),
+                None,
+            )
+        }
+        .context("vkCreateDescriptorPool")?;
+
+        self.next_pool_sets = sets.saturating_mul(2).min(self.config.max_sets);
+
+        Ok(pool)
+    }
+
+    /// Current pool getting function
+    fn current_pool(&mut self) -> Result<vk::DescriptorPool, VulkanCallError> {
+        if let Some(pool) = self.ready_pools.last() {
+            return Ok(*pool);
+        }
+
+        let pool = self.create_pool()?;
+        self.ready_pools.push(pool);
+        self.current_pool_used = false;
+
+        Ok(pool)
+    }
+
+    /// Descriptor set allocation function
+    /// Returns `LayoutExceedsPool` if set doesn't fit even into empty pool, pool isn't retired in this case.
+    pub fn allocate(&mut self, layout: vk::DescriptorSetLayout) -> Result<vk::DescriptorSet, DescriptorAllocateError> {
+        loop {
+            let pool = self.current_pool()?;
+
+            let result = unsafe {
+                self.kernel.device.allocate_descriptor_sets(
+                    &vk::DescriptorSetAllocateInfo::default()
+                        .descriptor_pool(pool)
+                        .set_layouts(&[layout]),
+                )
+            };
+
+            match result {
+                Ok(sets) => {
+                    self.current_pool_used = true;
+                    return Ok(sets[0]);
+                }
+                // Pool is retired only if it's actually filled, otherwise the set doesn't fit into any pool
+                Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY | vk::Result::ERROR_FRAGMENTED_POOL) => {
+                    if !self.current_pool_used {
+                        return Err(DescriptorAllocateError::LayoutExceedsPool);
+                    }
+
+                    self.ready_pools.pop();
+                    self.full_pools.push(pool);
+
+                    // Ready pools left after reset are empty too
+                    self.current_pool_used = false;
+                }
+                Err(err) => {
+                    return Err(VulkanCallError { call: "vkAllocateDescriptorSets", result: err }.into())
+                }
+            }
+        }
+    }
+
+    /// All allocated sets freeing function
+    /// # Safety
+    /// Sets allocated by this allocator must not be used by GPU
+    pub unsafe fn reset(&mut self) -> Result<(), VulkanCallError> {
+        for pool in self.ready_pools.iter().chain(self.full_pools.iter()) {
+            self.kernel
+                .device
+                .reset_descriptor_pool(*pool, vk::DescriptorPoolResetFlags::empty())
+                .context("vkResetDescriptorPool")?;
+        }
+
+        self.ready_pools.append(&mut self.full_pools);
+        self.current_pool_used = false;
+
+        Ok(())
+    }
+}
+
+impl Drop for DescriptorAllocator {
+    fn drop(&mut self) {
+        for pool in self.ready_pools.iter().chain(self.full_pools.iter()) {
+            unsafe { self.kernel.device.destroy_descriptor_pool(*pool, None) };
+        }
+    }
+}
+
+/// Descriptor info written to set
+enum DescriptorInfo {
+    Buffer(usize),
+    Image(usize),
+}
+
+/// Descriptor set writer
+/// Collects descriptor writes and applies them in single `vkUpdateDescriptorSets` call.
+#[derive(Default)]
+pub struct DescriptorWriter {
+    buffers: Vec<vk::DescriptorBufferInfo>,
+    images: Vec<vk::DescriptorImageInfo>,
+    writes: Vec<(u32, u32, vk::DescriptorType, DescriptorInfo)>,
+}
+
+impl DescriptorWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Buffer descriptor (uniform or storage buffer) array element writing function
+    pub fn buffer_element(
+        &mut self,
+        binding: u32,
+        array_element: u32,
+        descriptor_type: vk::DescriptorType,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        range: vk::DeviceSize,
+    ) -> &mut Self {
+        self.buffers.push(vk::DescriptorBufferInfo { buffer, offset, range });
+        self.writes.push((
+            binding,
+            array_element,
+            descriptor_type,
+            DescriptorInfo::Buffer(self.buffers.len() - 1),
+        ));
+        self
+    }
+
+    /// Buffer descriptor writing function
+    /// `vk::WHOLE_SIZE` range may be used to bind the rest of buffer.
+    pub fn buffer(
+        &mut self,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        range: vk::DeviceSize,
+    ) -> &mut Self {
+        self.buffer_element(binding, 0, descriptor_type, buffer, offset, range)
+    }
+
+    /// Image descriptor (sampled, storage or combined image sampler) array element writing function
+    pub fn image_element(
+        &mut self,
+        binding: u32,
+        array_element: u32,
+        descriptor_type: vk::DescriptorType,
+        view: vk::ImageView,
+        layout: vk::ImageLayout,
+        sampler: vk::Sampler,
+    ) -> &mut Self {
+        self.images.push(vk::DescriptorImageInfo {
+            sampler,
+            image_view: view,
+            image_layout: layout,
+        });
+        self.writes.push((
+            binding,
+            array_element,
+            descriptor_type,
+            DescriptorInfo::Image(self.images.len() - 1),
+        ));
+        self
+    }
+
+    /// Image descriptor writing function
+    /// Sampler is used only by combined image sampler descriptors, null handle may be passed otherwise.
+    pub fn image(
+        &mut self,
+        binding: u32,
+        descriptor_type: vk::DescriptorType,
+        view: vk::ImageView,
+        layout: vk::ImageLayout,
+        sampler: vk::Sampler,
+    ) -> &mut Self {
+        self.image_element(binding, 0, descriptor_type, view, layout, sampler)
+    }
+
+    /// Sampler descriptor writing function
+    pub fn sampler(&mut self, binding: u32, sampler: vk::Sampler) -> &mut Self {
+        self.image_element(
+            binding,
+            0,
+            vk::DescriptorType::SAMPLER,
+            vk::ImageView::null(),
+            vk::ImageLayout::UNDEFINED,
+            sampler,
+        )
+    }
+
+    /// Collected writes applying function
+    /// Writer may be reused for other sets.
+    pub fn update(&self, device: &ash::Device, set: vk::DescriptorSet) {
+        let writes = self
+            .writes
+            .iter()
+            .map(|(binding, array_element, descriptor_type, info)| {
+                let write = vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(*binding)
+                    .dst_array_element(*array_element)
+                    .descriptor_type(*descriptor_type);
+
+                match info {
+                    DescriptorInfo::Buffer(index) => write.buffer_info(std::slice::from_ref(&self.buffers[*index])),
+                    DescriptorInfo::Image(index) => write.image_info(std::slice::from_ref(&self.images[*index])),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        unsafe { device.update_descriptor_sets(&writes, &[]) };
+    }
+
+    /// Collected writes clearing function
+    pub fn clear(&mut self) {
+        self.buffers.clear();
+        self.images.clear();
+        self.writes.clear();
+    }
+}
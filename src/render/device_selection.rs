@@ -0,0 +1,585 @@
+use std::ffi::{CStr, CString};
+
+use ash::{ext, khr, vk};
+
+use super::{
+    error::{VulkanCallError, VulkanResultExt},
+    queue_family_indices::QueueFamilyIndices,
+};
+
+/// Environment variable that overrides physical device selection
+/// Value is parsed as device index, UUID (32 hex digits, dashes are ignored) or device name part.
+pub const DEVICE_SELECTOR_ENV_VAR: &str = "WAT3RS_DEVICE";
+
+/// Explicit physical device selector
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeviceSelector {
+    /// Index in `vkEnumeratePhysicalDevices` output
+    Index(usize),
+    /// Case-insensitive device name part
+    Name(String),
+    /// Device UUID
+    Uuid([u8; vk::UUID_SIZE]),
+}
+
+impl DeviceSelector {
+    /// Selector from string parsing function
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+
+        if let Ok(index) = value.parse::<usize>() {
+            return Self::Index(index);
+        }
+
+        let hex = value.replace('-', "");
+        if hex.len() == vk::UUID_SIZE * 2 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            let mut uuid = [0u8; vk::UUID_SIZE];
+            for (index, byte) in uuid.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).unwrap();
+            }
+            return Self::Uuid(uuid);
+        }
+
+        Self::Name(value.to_string())
+    }
+
+    /// Selector from `WAT3RS_DEVICE` environment variable getting function
+    pub fn from_env() -> Option<Self> {
+        std::env::var(DEVICE_SELECTOR_ENV_VAR)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| Self::parse(&value))
+    }
+
+    fn matches(
+        &self,
+        index: usize,
+        properties: &vk::PhysicalDeviceProperties,
+        uuid: impl FnOnce() -> [u8; vk::UUID_SIZE],
+    ) -> bool {
+        match self {
+            Self::Index(selected_index) => *selected_index == index,
+            Self::Name(name) => device_name(properties)
+                .to_lowercase()
+                .contains(&name.to_lowercase()),
+            Self::Uuid(selected_uuid) => uuid() == *selected_uuid,
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Index(index) => f.write_fmt(format_args!("device #{index}")),
+            Self::Name(name) => f.write_fmt(format_args!("device named \"{name}\"")),
+            Self::Uuid(uuid) => {
+                f.write_str("device with UUID ")?;
+                for byte in uuid {
+                    f.write_fmt(format_args!("{byte:02x}"))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Minimal physical device limits
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DeviceLimits {
+    pub max_image_dimension_2d: u32,
+    pub max_push_constants_size: u32,
+    pub max_bound_descriptor_sets: u32,
+    pub max_sampler_anisotropy: f32,
+    /// Total size of DEVICE_LOCAL memory heaps in bytes
+    pub device_local_memory: u64,
+}
+
+/// Physical device requirements
+#[derive(Clone, Debug, Default)]
+pub struct DeviceRequirements {
+    required_extensions: Vec<CString>,
+    optional_extensions: Vec<CString>,
+    features: vk::PhysicalDeviceFeatures,
+    min_limits: DeviceLimits,
+    bindless: bool,
+}
+
+impl DeviceRequirements {
+    /// Required device extension adding function
+    /// Swapchain extension is required automatically for kernels with surface.
+    pub fn required_extension(mut self, name: &CStr) -> Self {
+        self.required_extensions.push(name.to_owned());
+        self
+    }
+
+    /// Optional device extension adding function
+    /// Extension is enabled if it's supported, devices supporting more optional extensions are preferred.
+    /// Features of known extensions are enabled too (see `ExtensionFeatures`).
+    pub fn optional_extension(mut self, name: &CStr) -> Self {
+        self.optional_extensions.push(name.to_owned());
+        self
+    }
+
+    /// Required device features setting function
+    pub fn features(mut self, features: vk::PhysicalDeviceFeatures) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Minimal device limits setting function
+    pub fn min_limits(mut self, min_limits: DeviceLimits) -> Self {
+        self.min_limits = min_limits;
+        self
+    }
+
+    /// Optional bindless descriptors requesting function
+    /// Descriptor indexing is enabled if device supports it by Vulkan 1.2 or `VK_EXT_descriptor_indexing`,
+    /// devices supporting it are preferred. Requires Vulkan 1.1 instance to query support,
+    /// so kernel raises API version for it (see `KernelConfig::api_version`).
+    pub fn bindless(mut self, bindless: bool) -> Self {
+        self.bindless = bindless;
+        self
+    }
+
+    /// Bindless descriptors request getting function
+    pub fn bindless_requested(&self) -> bool {
+        self.bindless
+    }
+
+    /// Required features getting function
+    pub fn required_features(&self) -> &vk::PhysicalDeviceFeatures {
+        &self.features
+    }
+}
+
+/// Physical device selection error
+#[derive(Clone, Debug)]
+pub enum DeviceSelectionError {
+    /// No device satisfies requirements, rejected devices are listed with rejection reasons
+    NoSuitableDevices(Vec<(String, String)>),
+    /// Explicitly selected device is not found
+    DeviceNotFound(DeviceSelector),
+    /// Explicitly selected device doesn't satisfy requirements
+    DeviceUnsuitable { name: String, reason: String },
+    VulkanError(VulkanCallError),
+}
+
+impl std::error::Error for DeviceSelectionError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::VulkanError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<VulkanCallError> for DeviceSelectionError {
+    fn from(value: VulkanCallError) -> Self {
+        Self::VulkanError(value)
+    }
+}
+
+impl std::fmt::Display for DeviceSelectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoSuitableDevices(rejected) => {
+                f.write_str("no suiting physical devices found")?;
+                for (name, reason) in rejected {
+                    f.write_fmt(format_args!("; {name}: {reason}"))?;
+                }
+                Ok(())
+            }
+            Self::DeviceNotFound(selector) => {
+                f.write_fmt(format_args!("selected {selector} is not found"))
+            }
+            Self::DeviceUnsuitable { name, reason } => {
+                f.write_fmt(format_args!("selected device {name} is unsuitable: {reason}"))
+            }
+            Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
+        }
+    }
+}
+
+/// Bindless descriptors (descriptor indexing) device support
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BindlessSupport {
+    /// Descriptor indexing is provided by Vulkan 1.2 core instead of extension
+    pub core: bool,
+    /// Maximal count of update-after-bind combined image samplers in set
+    pub max_sampled_images: u32,
+    /// Maximal count of update-after-bind storage buffers in set
+    pub max_storage_buffers: u32,
+}
+
+/// Features of known device extensions enabled along with them
+/// Features are enabled by Vulkan 1.2/1.3 feature structures if extension is promoted to core
+/// for both instance and device, and by extension feature structures otherwise.
+/// Querying them requires Vulkan 1.1 instance.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExtensionFeatures {
+    /// `timelineSemaphore` of `VK_KHR_timeline_semaphore`
+    pub timeline_semaphore: bool,
+    /// `bufferDeviceAddress` of `VK_KHR_buffer_device_address`
+    pub buffer_device_address: bool,
+    /// `dynamicRendering` of `VK_KHR_dynamic_rendering`
+    pub dynamic_rendering: bool,
+    /// `synchronization2` of `VK_KHR_synchronization2`
+    pub synchronization2: bool,
+}
+
+/// Selected physical device description
+pub struct SelectedDevice {
+    pub physical_device: vk::PhysicalDevice,
+    pub properties: vk::PhysicalDeviceProperties,
+    pub queue_family_indices: QueueFamilyIndices,
+    /// Required and supported optional extensions
+    pub extensions: Vec<CString>,
+    /// Bindless support, `None` if bindless isn't requested or isn't supported
+    pub bindless: Option<BindlessSupport>,
+    /// Supported features of enabled extensions
+    pub extension_features: ExtensionFeatures,
+}
+
+/// Device name getting function
+pub fn device_name(properties: &vk::PhysicalDeviceProperties) -> String {
+    properties
+        .device_name_as_c_str()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "<unnamed>".to_string())
+}
+
+/// Device UUID getting function
+/// Vulkan 1.0 doesn't provide device UUIDs, so pipeline cache UUID is used instead.
+unsafe fn device_uuid(
+    instance: &ash::Instance,
+    api_version: u32,
+    physical_device: vk::PhysicalDevice,
+    properties: &vk::PhysicalDeviceProperties,
+) -> [u8; vk::UUID_SIZE] {
+    if api_version >= vk::API_VERSION_1_1 && properties.api_version >= vk::API_VERSION_1_1 {
+        let mut id_properties = vk::PhysicalDeviceIDProperties::default();
+        instance.get_physical_device_properties2(
+            physical_device,
+            &mut vk::PhysicalDeviceProperties2::default().push_next(&mut id_properties),
+        );
+        id_properties.device_uuid
+    } else {
+        properties.pipeline_cache_uuid
+    }
+}
+
+/// Descriptor indexing features required for bindless descriptors checking function
+fn bindless_features_supported(features: &vk::PhysicalDeviceDescriptorIndexingFeatures) -> bool {
+    [
+        features.shader_sampled_image_array_non_uniform_indexing,
+        features.shader_storage_buffer_array_non_uniform_indexing,
+        features.descriptor_binding_sampled_image_update_after_bind,
+        features.descriptor_binding_storage_buffer_update_after_bind,
+        features.descriptor_binding_update_unused_while_pending,
+        features.descriptor_binding_partially_bound,
+        features.runtime_descriptor_array,
+    ]
+    .iter()
+    .all(|feature| *feature != vk::FALSE)
+}
+
+/// Bindless descriptors support checking function
+unsafe fn bindless_support(
+    instance: &ash::Instance,
+    api_version: u32,
+    physical_device: vk::PhysicalDevice,
+    properties: &vk::PhysicalDeviceProperties,
+    available_extensions: &[CString],
+) -> Option<BindlessSupport> {
+    // Features of extensions can be queried only by vkGetPhysicalDeviceFeatures2
+    if api_version < vk::API_VERSION_1_1 || properties.api_version < vk::API_VERSION_1_1 {
+        return None;
+    }
+
+    let core = api_version >= vk::API_VERSION_1_2 && properties.api_version >= vk::API_VERSION_1_2;
+    if !core && !available_extensions.iter().any(|name| name.as_c_str() == ext::descriptor_indexing::NAME) {
+        return None;
+    }
+
+    // Descriptor indexing feature structure is valid for Vulkan 1.2 devices too
+    let mut features = vk::PhysicalDeviceDescriptorIndexingFeatures::default();
+    instance.get_physical_device_features2(
+        physical_device,
+        &mut vk::PhysicalDeviceFeatures2::default().push_next(&mut features),
+    );
+    if !bindless_features_supported(&features) {
+        return None;
+    }
+
+    let mut indexing_properties = vk::PhysicalDeviceDescriptorIndexingProperties::default();
+    instance.get_physical_device_properties2(
+        physical_device,
+        &mut vk::PhysicalDeviceProperties2::default().push_next(&mut indexing_properties),
+    );
+
+    // Combined image samplers are counted both as samplers and sampled images
+    let max_sampled_images = [
+        indexing_properties.max_descriptor_set_update_after_bind_sampled_images,
+        indexing_properties.max_descriptor_set_update_after_bind_samplers,
+        indexing_properties.max_per_stage_descriptor_update_after_bind_sampled_images,
+        indexing_properties.max_per_stage_descriptor_update_after_bind_samplers,
+    ]
+    .into_iter()
+    .min()
+    .unwrap_or(0);
+    let max_storage_buffers = indexing_properties
+        .max_descriptor_set_update_after_bind_storage_buffers
+        .min(indexing_properties.max_per_stage_descriptor_update_after_bind_storage_buffers);
+
+    Some(BindlessSupport {
+        core,
+        max_sampled_images,
+        max_storage_buffers,
+    })
+}
+
+/// Supported features of enabled known extensions querying function
+unsafe fn extension_features(
+    instance: &ash::Instance,
+    api_version: u32,
+    physical_device: vk::PhysicalDevice,
+    properties: &vk::PhysicalDeviceProperties,
+    extensions: &[CString],
+) -> ExtensionFeatures {
+    let enabled = |name: &CStr| extensions.iter().any(|extension| extension.as_c_str() == name);
+    let (timeline_semaphore, buffer_device_address, dynamic_rendering, synchronization2) = (
+        enabled(khr::timeline_semaphore::NAME),
+        enabled(khr::buffer_device_address::NAME),
+        enabled(khr::dynamic_rendering::NAME),
+        enabled(khr::synchronization2::NAME),
+    );
+
+    if !(timeline_semaphore || buffer_device_address || dynamic_rendering || synchronization2) {
+        return ExtensionFeatures::default();
+    }
+
+    // Features of extensions can be queried only by vkGetPhysicalDeviceFeatures2
+    if api_version < vk::API_VERSION_1_1 || properties.api_version < vk::API_VERSION_1_1 {
+        log::warn!(
+            "extension features can't be queried without Vulkan 1.1 instance and device, they stay disabled on {}",
+            device_name(properties)
+        );
+        return ExtensionFeatures::default();
+    }
+
+    let mut timeline_semaphore_features = vk::PhysicalDeviceTimelineSemaphoreFeatures::default();
+    let mut buffer_device_address_features = vk::PhysicalDeviceBufferDeviceAddressFeatures::default();
+    let mut dynamic_rendering_features = vk::PhysicalDeviceDynamicRenderingFeatures::default();
+    let mut synchronization2_features = vk::PhysicalDeviceSynchronization2Features::default();
+
+    // Only structures of enabled extensions may be chained
+    let mut features = vk::PhysicalDeviceFeatures2::default();
+    if timeline_semaphore {
+        features = features.push_next(&mut timeline_semaphore_features);
+    }
+    if buffer_device_address {
+        features = features.push_next(&mut buffer_device_address_features);
+    }
+    if dynamic_rendering {
+        features = features.push_next(&mut dynamic_rendering_features);
+    }
+    if synchronization2 {
+        features = features.push_next(&mut synchronization2_features);
+    }
+    instance.get_physical_device_features2(physical_device, &mut features);
+
+    ExtensionFeatures {
+        timeline_semaphore: timeline_semaphore_features.timeline_semaphore != vk::FALSE,
+        buffer_device_address: buffer_device_address_features.buffer_device_address != vk::FALSE,
+        dynamic_rendering: dynamic_rendering_features.dynamic_rendering != vk::FALSE,
+        synchronization2: synchronization2_features.synchronization2 != vk::FALSE,
+    }
+}
+
+/// Device type rank, greater is better
+fn device_type_rank(device_type: vk::PhysicalDeviceType) -> u32 {
+    match device_type {
+        vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+        vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+        vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+        vk::PhysicalDeviceType::CPU => 1,
+        _ => 0,
+    }
+}
+
+/// Feature set as flag array representation function
+fn feature_flags(features: &vk::PhysicalDeviceFeatures) -> &[vk::Bool32] {
+    // PhysicalDeviceFeatures is a repr(C) structure that consists of Bool32 fields only
+    unsafe {
+        std::slice::from_raw_parts(
+            features as *const vk::PhysicalDeviceFeatures as *const vk::Bool32,
+            std::mem::size_of::<vk::PhysicalDeviceFeatures>() / std::mem::size_of::<vk::Bool32>(),
+        )
+    }
+}
+
+/// Candidate device with score
+struct Candidate {
+    device: SelectedDevice,
+    score: (u32, usize, u64, u32),
+}
+
+/// Device against requirements checking function
+/// Returns device description and score on success and rejection reason on failure.
+unsafe fn evaluate_device(
+    instance: &ash::Instance,
+    api_version: u32,
+    physical_device: vk::PhysicalDevice,
+    surface: Option<(vk::SurfaceKHR, &khr::surface::Instance)>,
+    requirements: &DeviceRequirements,
+) -> Result<Candidate, String> {
+    let properties = instance.get_physical_device_properties(physical_device);
+
+    let queue_family_indices = QueueFamilyIndices::new(physical_device, instance, surface)
+        .ok_or_else(|| "no suitable queue families".to_string())?;
+
+    let available_extensions = instance
+        .enumerate_device_extension_properties(physical_device)
+        .context("vkEnumerateDeviceExtensionProperties")
+        .map_err(|err| err.to_string())?
+        .iter()
+        .filter_map(|properties| properties.extension_name_as_c_str().ok().map(CStr::to_owned))
+        .collect::<Vec<_>>();
+
+    let mut extensions = requirements.required_extensions.clone();
+    if surface.is_some() {
+        extensions.push(khr::swapchain::NAME.to_owned());
+    }
+
+    if let Some(missing) = extensions.iter().find(|name| !available_extensions.contains(name)) {
+        return Err(format!("missing {} extension", missing.to_string_lossy()));
+    }
+
+    let optional_count = requirements
+        .optional_extensions
+        .iter()
+        .filter(|name| available_extensions.contains(name))
+        .inspect(|name| extensions.push((*name).clone()))
+        .count();
+
+    let bindless = if requirements.bindless {
+        bindless_support(instance, api_version, physical_device, &properties, &available_extensions)
+    } else {
+        None
+    };
+    if let Some(BindlessSupport { core: false, .. }) = bindless {
+        extensions.push(ext::descriptor_indexing::NAME.to_owned());
+    }
+
+    extensions.sort();
+    extensions.dedup();
+
+    let extension_features = extension_features(instance, api_version, physical_device, &properties, &extensions);
+
+    let supported_features = instance.get_physical_device_features(physical_device);
+    if feature_flags(&requirements.features)
+        .iter()
+        .zip(feature_flags(&supported_features))
+        .any(|(required, supported)| *required != vk::FALSE && *supported == vk::FALSE)
+    {
+        return Err("required features are not supported".to_string());
+    }
+
+    let memory_properties = instance.get_physical_device_memory_properties(physical_device);
+    let device_local_memory = memory_properties.memory_heaps_as_slice()
+        .iter()
+        .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+        .map(|heap| heap.size)
+        .sum::<u64>();
+
+    let limits = &properties.limits;
+    let min_limits = &requirements.min_limits;
+
+    if limits.max_image_dimension2_d < min_limits.max_image_dimension_2d
+        || limits.max_push_constants_size < min_limits.max_push_constants_size
+        || limits.max_bound_descriptor_sets < min_limits.max_bound_descriptor_sets
+        || limits.max_sampler_anisotropy < min_limits.max_sampler_anisotropy
+    {
+        return Err("device limits are too low".to_string());
+    }
+
+    if device_local_memory < min_limits.device_local_memory {
+        return Err(format!("not enough device local memory ({device_local_memory} bytes)"));
+    }
+
+    Ok(Candidate {
+        score: (
+            device_type_rank(properties.device_type),
+            optional_count + usize::from(bindless.is_some()),
+            device_local_memory,
+            limits.max_image_dimension2_d,
+        ),
+        device: SelectedDevice {
+            physical_device,
+            properties,
+            queue_family_indices,
+            extensions,
+            bindless,
+            extension_features,
+        },
+    })
+}
+
+/// Physical device selection function
+/// If selector is passed, the selected device is used (and must satisfy requirements),
+/// otherwise the best scored device is used. Discrete GPUs are preferred over integrated
+/// ones and integrated GPUs over CPU implementations.
+pub fn select_physical_device(
+    instance: &ash::Instance,
+    api_version: u32,
+    surface: Option<(vk::SurfaceKHR, &khr::surface::Instance)>,
+    requirements: &DeviceRequirements,
+    selector: Option<&DeviceSelector>,
+) -> Result<SelectedDevice, DeviceSelectionError> {
+    let physical_devices = unsafe { instance.enumerate_physical_devices() }
+        .context("vkEnumeratePhysicalDevices")?;
+
+    if let Some(selector) = selector {
+        let (physical_device, properties) = physical_devices
+            .iter()
+            .copied()
+            .enumerate()
+            .map(|(index, physical_device)| {
+                (index, physical_device, unsafe { instance.get_physical_device_properties(physical_device) })
+            })
+            .find(|(index, physical_device, properties)| {
+                selector.matches(*index, properties, || unsafe {
+                    device_uuid(instance, api_version, *physical_device, properties)
+                })
+            })
+            .map(|(_, physical_device, properties)| (physical_device, properties))
+            .ok_or_else(|| DeviceSelectionError::DeviceNotFound(selector.clone()))?;
+
+        return unsafe { evaluate_device(instance, api_version, physical_device, surface, requirements) }
+            .map(|candidate| candidate.device)
+            .map_err(|reason| DeviceSelectionError::DeviceUnsuitable {
+                name: device_name(&properties),
+                reason,
+            });
+    }
+
+    let mut rejected = Vec::new();
+    let mut best = Option::<Candidate>::None;
+
+    for physical_device in physical_devices {
+        match unsafe { evaluate_device(instance, api_version, physical_device, surface, requirements) } {
+            Ok(candidate) => {
+                if best.as_ref().map(|best| best.score < candidate.score).unwrap_or(true) {
+                    best = Some(candidate);
+                }
+            }
+            Err(reason) => {
+                let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+                rejected.push((device_name(&properties), reason));
+            }
+        }
+    }
+
+    best.map(|candidate| candidate.device)
+        .ok_or(DeviceSelectionError::NoSuitableDevices(rejected))
+}
@@ -0,0 +1,33 @@
+use ash::vk;
+
+/// Failed Vulkan call description
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VulkanCallError {
+    /// Name of the failed function (e.g. "vkCreateDevice")
+    pub call: &'static str,
+    pub result: vk::Result,
+}
+
+impl std::fmt::Display for VulkanCallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{} failed with {}", self.call, self.result))
+    }
+}
+
+impl std::error::Error for VulkanCallError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.result)
+    }
+}
+
+/// Failed Vulkan call context attaching trait
+pub trait VulkanResultExt<T> {
+    /// Called function name attaching function
+    fn context(self, call: &'static str) -> Result<T, VulkanCallError>;
+}
+
+impl<T> VulkanResultExt<T> for Result<T, vk::Result> {
+    fn context(self, call: &'static str) -> Result<T, VulkanCallError> {
+        self.map_err(|result| VulkanCallError { call, result })
+    }
+}
@@ -0,0 +1,213 @@
+use std::{any::Any, sync::Arc};
+
+use ash::vk;
+
+use super::{
+    descriptor::{DescriptorAllocator, DescriptorAllocatorConfig},
+    error::{VulkanCallError, VulkanResultExt},
+    kernel::Kernel,
+};
+
+/// Default count of frames that may be processed by GPU simultaneously
+pub const DEFAULT_FRAMES_IN_FLIGHT: usize = 2;
+
+/// Per-frame command recording and synchronization objects
+pub(super) struct Frame {
+    pub command_pool: vk::CommandPool,
+    pub command_buffer: vk::CommandBuffer,
+    pub image_available_semaphore: vk::Semaphore,
+    pub in_flight_fence: vk::Fence,
+    /// Descriptor sets used only by this frame, reset when the frame is reused
+    pub descriptors: DescriptorAllocator,
+    /// Objects to destroy after the frame's fence is signaled
+    deferred: Vec<Box<dyn Any + Send>>,
+    /// Frame was waited for since the last submission
+    waited: bool,
+}
+
+impl Frame {
+    fn new(kernel: &Arc<Kernel>) -> Result<Self, VulkanCallError> {
+        unsafe {
+            let command_pool = kernel.device.create_command_pool(
+                &vk::CommandPoolCreateInfo::default()
+                    .queue_family_index(kernel.queue_family_indices.main)
+                    .flags(vk::CommandPoolCreateFlags::TRANSIENT),
+                None,
+            ).context("vkCreateCommandPool")?;
+
+            let mut frame = Self {
+                command_pool,
+                command_buffer: vk::CommandBuffer::null(),
+                image_available_semaphore: vk::Semaphore::null(),
+                in_flight_fence: vk::Fence::null(),
+                descriptors: DescriptorAllocator::new(kernel.clone(), DescriptorAllocatorConfig::default()),
+                deferred: Vec::new(),
+                waited: false,
+            };
+
+            // Null handles are ignored by destroy functions, so partially created frame may be destroyed
+            let result = (|| {
+                frame.command_buffer = kernel.device.allocate_command_buffers(
+                    &vk::CommandBufferAllocateInfo::default()
+                        .command_pool(command_pool)
+                        .level(vk::CommandBufferLevel::PRIMARY)
+                        .command_buffer_count(1),
+                ).context("vkAllocateCommandBuffers")?[0];
+
+                frame.image_available_semaphore = kernel
+                    .device
+                    .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+                    .context("vkCreateSemaphore")?;
+
+                // Fence is created signaled, so the first wait on it doesn't block
+                frame.in_flight_fence = kernel.device.create_fence(
+                    &vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED),
+                    None,
+                ).context("vkCreateFence")?;
+
+                Ok(())
+            })();
+
+            match result {
+                Ok(()) => Ok(frame),
+                Err(err) => {
+                    frame.destroy(kernel);
+                    Err(err)
+                }
+            }
+        }
+    }
+
+    /// Frame objects destroy function
+    /// # Safety
+    /// Frame objects must not be used by GPU
+    unsafe fn destroy(&mut self, kernel: &Kernel) {
+        self.deferred.clear();
+
+        kernel.device.destroy_fence(self.in_flight_fence, None);
+        kernel.device.destroy_semaphore(self.image_available_semaphore, None);
+        kernel.device.destroy_command_pool(self.command_pool, None);
+    }
+}
+
+/// Ring of frames in flight
+pub(super) struct FrameRing {
+    kernel: Arc<Kernel>,
+    frames: Vec<Frame>,
+    index: usize,
+}
+
+impl FrameRing {
+    pub fn new(kernel: Arc<Kernel>, count: usize) -> Result<Self, VulkanCallError> {
+        let mut frames = Vec::with_capacity(count.max(1));
+
+        for _ in 0..count.max(1) {
+            match Frame::new(&kernel) {
+                Ok(frame) => frames.push(frame),
+                Err(err) => {
+                    for frame in &mut frames {
+                        unsafe { frame.destroy(&kernel) };
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(Self {
+            kernel,
+            frames,
+            index: 0,
+        })
+    }
+
+    /// Frame count getting function
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Current frame getting function
+    pub fn current(&self) -> &Frame {
+        &self.frames[self.index]
+    }
+
+    /// Current frame availability waiting function
+    /// Objects deferred during the previous use of the frame are destroyed and its descriptors are reset.
+    /// Does nothing if the frame is already waited for since its last submission.
+    pub fn wait(&mut self) -> Result<(), VulkanCallError> {
+        let frame = &mut self.frames[self.index];
+        if frame.waited {
+            return Ok(());
+        }
+
+        unsafe {
+            self.kernel
+                .device
+                .wait_for_fences(&[frame.in_flight_fence], true, u64::MAX)
+                .context("vkWaitForFences")?;
+
+            frame.descriptors.reset()?;
+        }
+
+        frame.deferred.clear();
+        frame.waited = true;
+
+        Ok(())
+    }
+
+    /// Current frame getting function, frame is waited for first
+    pub fn current_mut(&mut self) -> Result<&mut Frame, VulkanCallError> {
+        self.wait()?;
+        Ok(&mut self.frames[self.index])
+    }
+
+    /// Current frame fence and command pool resetting function
+    /// Must be called right before frame commands recording, after `wait`.
+    pub fn reset(&self) -> Result<(), VulkanCallError> {
+        let frame = self.current();
+
+        unsafe {
+            self.kernel
+                .device
+                .reset_fences(&[frame.in_flight_fence])
+                .context("vkResetFences")?;
+            self.kernel
+                .device
+                .reset_command_pool(frame.command_pool, vk::CommandPoolResetFlags::empty())
+                .context("vkResetCommandPool")?;
+        }
+
+        Ok(())
+    }
+
+    /// Next frame switching function
+    /// Must be called after current frame submission.
+    pub fn advance(&mut self) {
+        self.frames[self.index].waited = false;
+        self.index = (self.index + 1) % self.frames.len();
+    }
+
+    /// Deferred object destruction function
+    /// Object may be used by any submitted frame, so it's destroyed when the
+    /// most recently submitted one is complete.
+    pub fn defer(&mut self, object: Box<dyn Any + Send>) {
+        let last_submitted = (self.index + self.frames.len() - 1) % self.frames.len();
+        self.frames[last_submitted].deferred.push(object);
+    }
+}
+
+impl Drop for FrameRing {
+    fn drop(&mut self) {
+        unsafe {
+            let fences = self
+                .frames
+                .iter()
+                .map(|frame| frame.in_flight_fence)
+                .collect::<Vec<_>>();
+            _ = self.kernel.device.wait_for_fences(&fences, true, u64::MAX);
+
+            for frame in &mut self.frames {
+                frame.destroy(&self.kernel);
+            }
+        }
+    }
+}
@@ -0,0 +1,1271 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use crate::utility::math::Ext2;
+
+use super::{
+    allocator::Allocator,
+    buffer::ResourceCreateError,
+    image::{Image, ImageDesc},
+};
+
+/// Stages that may access images and buffers from shaders
+const SHADER_STAGES: vk::PipelineStageFlags = vk::PipelineStageFlags::from_raw(
+    vk::PipelineStageFlags::VERTEX_SHADER.as_raw()
+        | vk::PipelineStageFlags::FRAGMENT_SHADER.as_raw()
+        | vk::PipelineStageFlags::COMPUTE_SHADER.as_raw(),
+);
+
+/// Graph image handle
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GraphImage(usize);
+
+/// Graph buffer handle
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GraphBuffer(usize);
+
+/// Image access kind
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ImageAccess {
+    ColorAttachment,
+    DepthAttachment,
+    /// Read-only depth attachment or depth sampling
+    DepthRead,
+    Sampled,
+    StorageRead,
+    StorageWrite,
+    TransferSrc,
+    TransferDst,
+}
+
+/// Buffer access kind
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BufferAccess {
+    Vertex,
+    Index,
+    Indirect,
+    Uniform,
+    StorageRead,
+    StorageWrite,
+    TransferSrc,
+    TransferDst,
+}
+
+/// Synchronization scope of an access
+#[derive(Copy, Clone, Debug)]
+struct AccessScope {
+    stages: vk::PipelineStageFlags,
+    access: vk::AccessFlags,
+    layout: vk::ImageLayout,
+    write: bool,
+}
+
+impl ImageAccess {
+    fn scope(self) -> AccessScope {
+        let (stages, access, layout, write) = match self {
+            Self::ColorAttachment => (
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                true,
+            ),
+            Self::DepthAttachment => (
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                true,
+            ),
+            Self::DepthRead => (
+                vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS | SHADER_STAGES,
+                vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ | vk::AccessFlags::SHADER_READ,
+                vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL,
+                false,
+            ),
+            Self::Sampled => (
+                SHADER_STAGES,
+                vk::AccessFlags::SHADER_READ,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                false,
+            ),
+            Self::StorageRead => (SHADER_STAGES, vk::AccessFlags::SHADER_READ, vk::ImageLayout::GENERAL, false),
+            Self::StorageWrite => (
+                SHADER_STAGES,
+                vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                vk::ImageLayout::GENERAL,
+                true,
+            ),
+            Self::TransferSrc => (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_READ,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                false,
+            ),
+            Self::TransferDst => (
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                true,
+            ),
+        };
+
+        AccessScope { stages, access, layout, write }
+    }
+
+    /// Image usage required for the access getting function
+    fn usage(self) -> vk::ImageUsageFlags {
+        match self {
+            Self::ColorAttachment => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            Self::DepthAttachment => vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            Self::DepthRead => vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            Self::Sampled => vk::ImageUsageFlags::SAMPLED,
+            Self::StorageRead | Self::StorageWrite => vk::ImageUsageFlags::STORAGE,
+            Self::TransferSrc => vk::ImageUsageFlags::TRANSFER_SRC,
+            Self::TransferDst => vk::ImageUsageFlags::TRANSFER_DST,
+        }
+    }
+}
+
+impl BufferAccess {
+    fn scope(self) -> AccessScope {
+        let (stages, access, write) = match self {
+            Self::Vertex => (vk::PipelineStageFlags::VERTEX_INPUT, vk::AccessFlags::VERTEX_ATTRIBUTE_READ, false),
+            Self::Index => (vk::PipelineStageFlags::VERTEX_INPUT, vk::AccessFlags::INDEX_READ, false),
+            Self::Indirect => (vk::PipelineStageFlags::DRAW_INDIRECT, vk::AccessFlags::INDIRECT_COMMAND_READ, false),
+            Self::Uniform => (SHADER_STAGES, vk::AccessFlags::UNIFORM_READ, false),
+            Self::StorageRead => (SHADER_STAGES, vk::AccessFlags::SHADER_READ, false),
+            Self::StorageWrite => (SHADER_STAGES, vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE, true),
+            Self::TransferSrc => (vk::PipelineStageFlags::TRANSFER, vk::AccessFlags::TRANSFER_READ, false),
+            Self::TransferDst => (vk::PipelineStageFlags::TRANSFER, vk::AccessFlags::TRANSFER_WRITE, true),
+        };
+
+        AccessScope {
+            stages,
+            access,
+            layout: vk::ImageLayout::UNDEFINED,
+            write,
+        }
+    }
+}
+
+/// Externally owned image description
+#[derive(Copy, Clone, Debug)]
+pub struct ImportedImage {
+    pub image: vk::Image,
+    pub view: vk::ImageView,
+    pub format: vk::Format,
+    pub extent: Ext2<u32>,
+    pub subresource_range: vk::ImageSubresourceRange,
+}
+
+impl ImportedImage {
+    /// Imported description of the whole image create function
+    pub fn from_image(image: &Image) -> Self {
+        Self {
+            image: image.handle(),
+            view: image.view(),
+            format: image.format(),
+            extent: image.extent(),
+            subresource_range: image.subresource_range(),
+        }
+    }
+}
+
+/// Graph owned attachment description
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TransientImageDesc {
+    pub format: vk::Format,
+    pub extent: Ext2<u32>,
+    pub samples: vk::SampleCountFlags,
+}
+
+impl TransientImageDesc {
+    /// Single sampled transient image description create function
+    pub fn new(format: vk::Format, extent: Ext2<u32>) -> Self {
+        Self {
+            format,
+            extent,
+            samples: vk::SampleCountFlags::TYPE_1,
+        }
+    }
+
+    /// Sample count setting function
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+}
+
+enum ImageSource {
+    Imported {
+        image: ImportedImage,
+        initial_layout: vk::ImageLayout,
+        final_layout: Option<vk::ImageLayout>,
+    },
+    Transient {
+        desc: TransientImageDesc,
+        /// Union of usages of all accesses to the image
+        usage: vk::ImageUsageFlags,
+    },
+}
+
+struct ImageResource {
+    name: &'static str,
+    source: ImageSource,
+}
+
+struct BufferResource {
+    buffer: vk::Buffer,
+}
+
+/// Pass resource access declaring structure
+#[derive(Default)]
+pub struct PassBuilder {
+    images: Vec<(GraphImage, ImageAccess)>,
+    buffers: Vec<(GraphBuffer, BufferAccess)>,
+    side_effects: bool,
+}
+
+impl PassBuilder {
+    /// Image access declaring function
+    pub fn image(&mut self, image: GraphImage, access: ImageAccess) -> &mut Self {
+        self.images.push((image, access));
+        self
+    }
+
+    /// Buffer access declaring function
+    pub fn buffer(&mut self, buffer: GraphBuffer, access: BufferAccess) -> &mut Self {
+        self.buffers.push((buffer, access));
+        self
+    }
+
+    /// Pass culling disabling function
+    /// Should be used for passes with effects invisible to the graph (e.g. queries).
+    pub fn side_effects(&mut self) -> &mut Self {
+        self.side_effects = true;
+        self
+    }
+}
+
+struct Pass<'a> {
+    name: &'static str,
+    declaration: PassBuilder,
+    execute: Box<dyn FnOnce(&PassContext) + 'a>,
+}
+
+/// Resolved image
+#[derive(Copy, Clone)]
+struct ResolvedImage {
+    image: vk::Image,
+    view: vk::ImageView,
+    format: vk::Format,
+    extent: Ext2<u32>,
+    subresource_range: vk::ImageSubresourceRange,
+    /// Index of the state tracking slot, shared by aliased transient images
+    state_index: usize,
+}
+
+/// Pass execution context
+pub struct PassContext<'c> {
+    pub device: &'c ash::Device,
+    pub command_buffer: vk::CommandBuffer,
+    images: &'c [Option<ResolvedImage>],
+    buffers: &'c [BufferResource],
+}
+
+impl PassContext<'_> {
+    fn resolved(&self, image: GraphImage) -> &ResolvedImage {
+        self.images[image.0]
+            .as_ref()
+            .expect("image must be declared by pass to be accessed")
+    }
+
+    /// Image handle getting function
+    pub fn image(&self, image: GraphImage) -> vk::Image {
+        self.resolved(image).image
+    }
+
+    /// Image view getting function
+    pub fn view(&self, image: GraphImage) -> vk::ImageView {
+        self.resolved(image).view
+    }
+
+    pub fn format(&self, image: GraphImage) -> vk::Format {
+        self.resolved(image).format
+    }
+
+    pub fn extent(&self, image: GraphImage) -> Ext2<u32> {
+        self.resolved(image).extent
+    }
+
+    pub fn subresource_range(&self, image: GraphImage) -> vk::ImageSubresourceRange {
+        self.resolved(image).subresource_range
+    }
+
+    /// Buffer handle getting function
+    pub fn buffer(&self, buffer: GraphBuffer) -> vk::Buffer {
+        self.buffers[buffer.0].buffer
+    }
+}
+
+/// Render graph execution error
+#[derive(Clone, Debug)]
+pub enum GraphError {
+    /// Transient image creation error
+    ResourceCreateError(ResourceCreateError),
+    /// Pass accesses the same image in different layouts
+    ConflictingLayouts {
+        pass: &'static str,
+        image: &'static str,
+    },
+}
+
+impl std::fmt::Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ResourceCreateError(err) => f.write_fmt(format_args!("transient image creation error: {err}")),
+            Self::ConflictingLayouts { pass, image } => f.write_fmt(format_args!(
+                "pass \"{pass}\" accesses image \"{image}\" in different layouts"
+            )),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::ResourceCreateError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ResourceCreateError> for GraphError {
+    fn from(value: ResourceCreateError) -> Self {
+        Self::ResourceCreateError(value)
+    }
+}
+
+/// Transient image reuse key
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct TransientKey {
+    desc: TransientImageDesc,
+    usage: vk::ImageUsageFlags,
+}
+
+/// Default count of frames transient image may stay unused before eviction
+pub const DEFAULT_TRANSIENT_MAX_UNUSED_FRAMES: u32 = 8;
+
+/// Transient pool image with its usage tracking
+struct TransientEntry {
+    key: TransientKey,
+    image: Image,
+    /// Count of frames ended since the image was acquired last time
+    unused_frames: u32,
+}
+
+/// Transient images storage, images are reused by graphs of the following frames
+/// Images not acquired during `max_unused_frames` frames are evicted by `end_frame`.
+pub struct TransientPool {
+    allocator: Arc<Allocator>,
+    images: Vec<TransientEntry>,
+    max_unused_frames: u32,
+}
+
+impl TransientPool {
+    pub fn new(allocator: Arc<Allocator>) -> Self {
+        Self {
+            allocator,
+            images: Vec::new(),
+            max_unused_frames: DEFAULT_TRANSIENT_MAX_UNUSED_FRAMES,
+        }
+    }
+
+    /// Count of frames image may stay unused before eviction setting function
+    pub fn max_unused_frames(mut self, max_unused_frames: u32) -> Self {
+        self.max_unused_frames = max_unused_frames;
+        self
+    }
+
+    /// Stored images count getting function
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Pool emptiness checking function
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Stored images destroy function
+    /// # Note
+    /// Images must not be used by GPU, e.g. pool should be cleared after device wait on resize.
+    pub fn clear(&mut self) {
+        self.images.clear();
+    }
+
+    /// Frame end function
+    /// Returns images evicted as unused for too long. They may still be used by frames in flight,
+    /// so they should be destroyed by deferred destruction (e.g. `FrameRing::defer`).
+    pub fn end_frame(&mut self) -> Vec<Image> {
+        let mut evicted = Vec::new();
+        let mut index = 0;
+
+        while index < self.images.len() {
+            let entry = &mut self.images[index];
+            entry.unused_frames += 1;
+
+            if entry.unused_frames > self.max_unused_frames {
+                evicted.push(self.images.swap_remove(index).image);
+            } else {
+                index += 1;
+            }
+        }
+
+        evicted
+    }
+
+    /// Images for keys getting function
+    /// Returns indices of distinct pool images, one per key.
+    fn acquire(&mut self, keys: &[TransientKey]) -> Result<Vec<usize>, ResourceCreateError> {
+        let mut taken = vec![false; self.images.len()];
+        let mut indices = Vec::with_capacity(keys.len());
+
+        for key in keys {
+            let existing = self
+                .images
+                .iter()
+                .enumerate()
+                .position(|(index, entry)| !taken[index] && entry.key == *key);
+
+            let index = match existing {
+                Some(index) => index,
+                None => {
+                    let image = Image::new(
+                        &self.allocator,
+                        ImageDesc::new("render graph transient", key.desc.format, key.desc.extent, key.usage)
+                            .samples(key.desc.samples),
+                    )?;
+                    self.images.push(TransientEntry {
+                        key: *key,
+                        image,
+                        unused_frames: 0,
+                    });
+                    taken.push(false);
+                    self.images.len() - 1
+                }
+            };
+
+            taken[index] = true;
+            self.images[index].unused_frames = 0;
+            indices.push(index);
+        }
+
+        Ok(indices)
+    }
+}
+
+/// Resource synchronization state
+#[derive(Copy, Clone)]
+struct ResourceState {
+    layout: vk::ImageLayout,
+    /// Stages and accesses of the last write (or layout transition)
+    write_stages: vk::PipelineStageFlags,
+    write_access: vk::AccessFlags,
+    /// Stages and accesses the last write is made visible to
+    visible_stages: vk::PipelineStageFlags,
+    visible_access: vk::AccessFlags,
+    /// Stages of reads after the last write
+    read_stages: vk::PipelineStageFlags,
+}
+
+impl ResourceState {
+    /// State of a resource with unknown previous usage
+    fn external(layout: vk::ImageLayout) -> Self {
+        Self {
+            layout,
+            write_stages: vk::PipelineStageFlags::empty(),
+            write_access: vk::AccessFlags::empty(),
+            visible_stages: vk::PipelineStageFlags::empty(),
+            visible_access: vk::AccessFlags::empty(),
+            read_stages: vk::PipelineStageFlags::ALL_COMMANDS,
+        }
+    }
+
+    /// State transition function
+    /// Returns barrier (src stages, src access, old layout) if it's required for the access.
+    fn access(&mut self, scope: AccessScope, discard: bool) -> Option<(vk::PipelineStageFlags, vk::AccessFlags, vk::ImageLayout)> {
+        let transition = discard || scope.layout != self.layout;
+
+        if transition || scope.write {
+            let barrier = (
+                self.write_stages | self.read_stages,
+                self.write_access,
+                if discard { vk::ImageLayout::UNDEFINED } else { self.layout },
+            );
+
+            self.layout = scope.layout;
+            if scope.write {
+                self.write_stages = scope.stages;
+                self.write_access = scope.access;
+                self.visible_stages = vk::PipelineStageFlags::empty();
+                self.visible_access = vk::AccessFlags::empty();
+                self.read_stages = vk::PipelineStageFlags::empty();
+            } else {
+                // Layout transition is visible to the barrier destination scope only
+                self.write_stages = scope.stages;
+                self.write_access = vk::AccessFlags::empty();
+                self.visible_stages = scope.stages;
+                self.visible_access = scope.access;
+                self.read_stages = scope.stages;
+            }
+
+            return Some(barrier);
+        }
+
+        self.read_stages |= scope.stages;
+
+        let visible = self.visible_stages.contains(scope.stages) && self.visible_access.contains(scope.access);
+
+        if !self.write_stages.is_empty() && !visible {
+            self.visible_stages |= scope.stages;
+            self.visible_access |= scope.access;
+
+            return Some((self.write_stages, self.write_access, self.layout));
+        }
+
+        None
+    }
+}
+
+/// Physical transient image shared by graph images with disjoint lifetimes
+#[derive(Copy, Clone, Debug)]
+struct TransientSlot {
+    desc: TransientImageDesc,
+    usage: vk::ImageUsageFlags,
+    /// Execution position of the last pass accessing the slot
+    last: usize,
+}
+
+/// Resource access hazard tracking state used by pass scheduling
+#[derive(Clone, Default)]
+struct HazardState {
+    last_write: Option<usize>,
+    /// Passes reading the resource after the last write
+    reads: Vec<usize>,
+}
+
+impl HazardState {
+    /// Pass access registering function, pass dependencies are added to `dependencies`
+    fn access(&mut self, pass: usize, write: bool, dependencies: &mut Vec<usize>) {
+        dependencies.extend(self.last_write);
+
+        if write {
+            dependencies.append(&mut self.reads);
+            self.last_write = Some(pass);
+        } else {
+            self.reads.push(pass);
+        }
+    }
+}
+
+/// Render graph
+/// Dependencies between passes are derived from their accesses to the same resources in declaration
+/// order (reads depend on the previous write, writes depend on the previous write and reads). During
+/// execution passes whose results are unused are culled, the rest are topologically sorted by dependencies,
+/// transient images are allocated (images with disjoint lifetimes share one physical image) and barriers
+/// with layout transitions are inserted between passes.
+/// # Note
+/// Independent passes may be recorded in order different from declaration one, passes with side effects
+/// keep their declaration order relative to all other passes.
+/// Attachments are left in attachment layouts, so render passes used by passes must have
+/// matching initial and final layouts.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    images: Vec<ImageResource>,
+    buffers: Vec<BufferResource>,
+    passes: Vec<Pass<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// External image importing function
+    /// Image is transitioned to `final_layout` (if any) after all passes. Passes writing
+    /// imported images are never culled.
+    pub fn import_image(
+        &mut self,
+        name: &'static str,
+        image: ImportedImage,
+        initial_layout: vk::ImageLayout,
+        final_layout: Option<vk::ImageLayout>,
+    ) -> GraphImage {
+        self.images.push(ImageResource {
+            name,
+            source: ImageSource::Imported {
+                image,
+                initial_layout,
+                final_layout,
+            },
+        });
+
+        GraphImage(self.images.len() - 1)
+    }
+
+    /// Transient image declaring function
+    /// Image contents are undefined before the first write in the graph.
+    pub fn create_image(&mut self, name: &'static str, desc: TransientImageDesc) -> GraphImage {
+        self.images.push(ImageResource {
+            name,
+            source: ImageSource::Transient {
+                desc,
+                usage: vk::ImageUsageFlags::empty(),
+            },
+        });
+
+        GraphImage(self.images.len() - 1)
+    }
+
+    /// External buffer importing function
+    pub fn import_buffer(&mut self, buffer: vk::Buffer) -> GraphBuffer {
+        self.buffers.push(BufferResource { buffer });
+
+        GraphBuffer(self.buffers.len() - 1)
+    }
+
+    /// Pass adding function
+    /// `setup` declares pass resource accesses, `execute` records pass commands.
+    pub fn add_pass(
+        &mut self,
+        name: &'static str,
+        setup: impl FnOnce(&mut PassBuilder),
+        execute: impl FnOnce(&PassContext) + 'a,
+    ) {
+        let mut declaration = PassBuilder::default();
+        setup(&mut declaration);
+
+        for (image, access) in &declaration.images {
+            if let ImageSource::Transient { usage, .. } = &mut self.images[image.0].source {
+                *usage |= access.usage();
+            }
+        }
+
+        self.passes.push(Pass {
+            name,
+            declaration,
+            execute: Box::new(execute),
+        });
+    }
+
+    fn is_imported(&self, image: GraphImage) -> bool {
+        matches!(self.images[image.0].source, ImageSource::Imported { .. })
+    }
+
+    /// Alive passes flags getting function
+    /// Pass is alive if it has side effects, writes imported resources or writes
+    /// transient images read by later alive passes.
+    fn cull(&self) -> Vec<bool> {
+        let mut needed = vec![false; self.images.len()];
+        let mut alive = vec![false; self.passes.len()];
+
+        for (index, pass) in self.passes.iter().enumerate().rev() {
+            let declaration = &pass.declaration;
+
+            alive[index] = declaration.side_effects
+                || declaration
+                    .buffers
+                    .iter()
+                    .any(|(_, access)| access.scope().write)
+                || declaration.images.iter().any(|(image, access)| {
+                    access.scope().write && (self.is_imported(*image) || needed[image.0])
+                });
+
+            if alive[index] {
+                for (image, access) in &declaration.images {
+                    // Attachment writes may depend on previous contents (e.g. blending or depth test)
+                    if !access.scope().write
+                        || matches!(access, ImageAccess::ColorAttachment | ImageAccess::DepthAttachment)
+                    {
+                        needed[image.0] = true;
+                    }
+                }
+            }
+        }
+
+        alive
+    }
+
+    /// Alive passes execution order getting function
+    /// Among passes with satisfied dependencies the one whose dependencies were executed earliest
+    /// (and then the first declared one) is selected, so dependent passes are spread apart.
+    fn schedule(&self, alive: &[bool]) -> Vec<usize> {
+        let mut image_hazards = vec![HazardState::default(); self.images.len()];
+        let mut buffer_hazards = vec![HazardState::default(); self.buffers.len()];
+        let mut last_side_effects = None;
+        let mut since_side_effects = Vec::new();
+        let mut dependencies = vec![Vec::new(); self.passes.len()];
+
+        for (index, pass) in self.passes.iter().enumerate().filter(|(index, _)| alive[*index]) {
+            let declaration = &pass.declaration;
+            let pass_dependencies = &mut dependencies[index];
+
+            for (image, access) in &declaration.images {
+                image_hazards[image.0].access(index, access.scope().write, pass_dependencies);
+            }
+            for (buffer, access) in &declaration.buffers {
+                buffer_hazards[buffer.0].access(index, access.scope().write, pass_dependencies);
+            }
+
+            pass_dependencies.extend(last_side_effects);
+            if declaration.side_effects {
+                pass_dependencies.append(&mut since_side_effects);
+                last_side_effects = Some(index);
+            } else {
+                since_side_effects.push(index);
+            }
+
+            pass_dependencies.retain(|dependency| *dependency != index);
+        }
+
+        let mut positions: Vec<Option<usize>> = vec![None; self.passes.len()];
+        let mut order = Vec::new();
+        let pass_count = alive.iter().filter(|alive| **alive).count();
+
+        while order.len() < pass_count {
+            // Position after which pass may be executed, `None` if some dependency isn't executed yet
+            let ready_position = |index: usize| {
+                dependencies[index]
+                    .iter()
+                    .try_fold(0, |ready, dependency| Some(ready.max(positions[*dependency]? + 1)))
+            };
+
+            let next = (0..self.passes.len())
+                .filter(|index| alive[*index] && positions[*index].is_none())
+                .filter_map(|index| Some((ready_position(index)?, index)))
+                .min()
+                .expect("declaration order dependencies can't be cyclic")
+                .1;
+
+            positions[next] = Some(order.len());
+            order.push(next);
+        }
+
+        order
+    }
+
+    /// Transient images to physical image slots assigning function
+    /// Transient images with equal descriptions and disjoint lifetimes in `order` share slot.
+    fn transient_slots(&self, order: &[usize]) -> (Vec<TransientSlot>, Vec<Option<usize>>) {
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.images.len()];
+        for (position, pass_index) in order.iter().enumerate() {
+            for (image, _) in &self.passes[*pass_index].declaration.images {
+                let lifetime = &mut lifetimes[image.0];
+                *lifetime = Some(match *lifetime {
+                    Some((first, _)) => (first, position),
+                    None => (position, position),
+                });
+            }
+        }
+
+        let mut slots: Vec<TransientSlot> = Vec::new();
+        let mut image_slots = vec![None; self.images.len()];
+        let mut transient_order = (0..self.images.len())
+            .filter(|index| lifetimes[*index].is_some())
+            .filter_map(|index| match self.images[index].source {
+                ImageSource::Transient { desc, usage } => Some((index, desc, usage)),
+                ImageSource::Imported { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        transient_order.sort_by_key(|(index, _, _)| lifetimes[*index].unwrap().0);
+
+        for (index, desc, usage) in transient_order {
+            let (first, last) = lifetimes[index].unwrap();
+
+            let slot_index = match slots.iter().position(|slot| slot.desc == desc && slot.last < first) {
+                Some(slot_index) => {
+                    slots[slot_index].usage |= usage;
+                    slots[slot_index].last = last;
+                    slot_index
+                }
+                None => {
+                    slots.push(TransientSlot { desc, usage, last });
+                    slots.len() - 1
+                }
+            };
+
+            image_slots[index] = Some(slot_index);
+        }
+
+        (slots, image_slots)
+    }
+
+    /// Graph commands recording function
+    pub fn execute(
+        self,
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        transients: &mut TransientPool,
+    ) -> Result<(), GraphError> {
+        let alive = self.cull();
+        let order = self.schedule(&alive);
+        let (slots, image_slots) = self.transient_slots(&order);
+
+        let keys = slots
+            .iter()
+            .map(|slot| TransientKey {
+                desc: slot.desc,
+                usage: slot.usage,
+            })
+            .collect::<Vec<_>>();
+        let pool_indices = transients.acquire(&keys)?;
+
+        // State slots: imported images go first, physical transient images follow
+        let mut states = Vec::with_capacity(self.images.len() + slots.len());
+        let mut resolved = vec![None; self.images.len()];
+
+        for (index, resource) in self.images.iter().enumerate() {
+            match &resource.source {
+                ImageSource::Imported { image, initial_layout, .. } => {
+                    states.push(ResourceState::external(*initial_layout));
+                    resolved[index] = Some(ResolvedImage {
+                        image: image.image,
+                        view: image.view,
+                        format: image.format,
+                        extent: image.extent,
+                        subresource_range: image.subresource_range,
+                        state_index: states.len() - 1,
+                    });
+                }
+                ImageSource::Transient { .. } => {}
+            }
+        }
+
+        let transient_state_base = states.len();
+        states.extend((0..slots.len()).map(|_| ResourceState::external(vk::ImageLayout::UNDEFINED)));
+
+        for (index, slot) in image_slots.iter().enumerate() {
+            if let Some(slot) = slot {
+                let image = &transients.images[pool_indices[*slot]].image;
+                resolved[index] = Some(ResolvedImage {
+                    image: image.handle(),
+                    view: image.view(),
+                    format: image.format(),
+                    extent: image.extent(),
+                    subresource_range: image.subresource_range(),
+                    state_index: transient_state_base + slot,
+                });
+            }
+        }
+
+        let mut buffer_states = vec![ResourceState::external(vk::ImageLayout::UNDEFINED); self.buffers.len()];
+        let imported = (0..self.images.len())
+            .map(|index| self.is_imported(GraphImage(index)))
+            .collect::<Vec<_>>();
+        let mut started = vec![false; self.images.len()];
+        let mut passes = self.passes.into_iter().map(Some).collect::<Vec<_>>();
+
+        for pass_index in order {
+            let pass = passes[pass_index].take().expect("pass is scheduled once");
+
+            // Accesses to the same image are merged, as barriers of one batch aren't ordered
+            let mut image_scopes: Vec<(GraphImage, AccessScope)> = Vec::new();
+            for (image, access) in &pass.declaration.images {
+                let scope = access.scope();
+
+                match image_scopes.iter_mut().find(|(other, _)| other == image) {
+                    Some((_, merged)) => {
+                        if merged.layout != scope.layout {
+                            return Err(GraphError::ConflictingLayouts {
+                                pass: pass.name,
+                                image: self.images[image.0].name,
+                            });
+                        }
+                        merged.stages |= scope.stages;
+                        merged.access |= scope.access;
+                        merged.write |= scope.write;
+                    }
+                    None => image_scopes.push((*image, scope)),
+                }
+            }
+
+            let mut src_stages = vk::PipelineStageFlags::empty();
+            let mut dst_stages = vk::PipelineStageFlags::empty();
+            let mut image_barriers = Vec::new();
+            let mut buffer_barriers = Vec::new();
+
+            for (image, scope) in image_scopes {
+                let resolved_image = resolved[image.0].unwrap();
+
+                // Aliased transient image contents are discarded on its first access
+                let discard = !started[image.0] && !imported[image.0];
+                started[image.0] = true;
+
+                if let Some((stages, access, old_layout)) = states[resolved_image.state_index].access(scope, discard) {
+                    src_stages |= stages;
+                    dst_stages |= scope.stages;
+                    image_barriers.push(
+                        vk::ImageMemoryBarrier::default()
+                            .src_access_mask(access)
+                            .dst_access_mask(scope.access)
+                            .old_layout(old_layout)
+                            .new_layout(scope.layout)
+                            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .image(resolved_image.image)
+                            .subresource_range(resolved_image.subresource_range),
+                    );
+                }
+            }
+
+            for (buffer, access) in &pass.declaration.buffers {
+                let scope = access.scope();
+
+                if let Some((stages, access, _)) = buffer_states[buffer.0].access(scope, false) {
+                    src_stages |= stages;
+                    dst_stages |= scope.stages;
+                    buffer_barriers.push(
+                        vk::BufferMemoryBarrier::default()
+                            .src_access_mask(access)
+                            .dst_access_mask(scope.access)
+                            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                            .buffer(self.buffers[buffer.0].buffer)
+                            .offset(0)
+                            .size(vk::WHOLE_SIZE),
+                    );
+                }
+            }
+
+            unsafe {
+                Self::record_barriers(device, command_buffer, src_stages, dst_stages, &buffer_barriers, &image_barriers);
+            }
+
+            (pass.execute)(&PassContext {
+                device,
+                command_buffer,
+                images: &resolved,
+                buffers: &self.buffers,
+            });
+        }
+
+        // Imported images are transitioned to their final layouts
+        let mut src_stages = vk::PipelineStageFlags::empty();
+        let mut image_barriers = Vec::new();
+
+        for (index, resource) in self.images.iter().enumerate() {
+            let ImageSource::Imported { final_layout: Some(final_layout), .. } = resource.source else {
+                continue;
+            };
+            let resolved_image = resolved[index].unwrap();
+            let state = &states[resolved_image.state_index];
+
+            if state.layout != final_layout {
+                src_stages |= state.write_stages | state.read_stages;
+                image_barriers.push(
+                    vk::ImageMemoryBarrier::default()
+                        .src_access_mask(state.write_access)
+                        .dst_access_mask(vk::AccessFlags::empty())
+                        .old_layout(state.layout)
+                        .new_layout(final_layout)
+                        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                        .image(resolved_image.image)
+                        .subresource_range(resolved_image.subresource_range),
+                );
+            }
+        }
+
+        unsafe {
+            Self::record_barriers(
+                device,
+                command_buffer,
+                src_stages,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                &[],
+                &image_barriers,
+            );
+        }
+
+        Ok(())
+    }
+
+    unsafe fn record_barriers(
+        device: &ash::Device,
+        command_buffer: vk::CommandBuffer,
+        src_stages: vk::PipelineStageFlags,
+        dst_stages: vk::PipelineStageFlags,
+        buffer_barriers: &[vk::BufferMemoryBarrier],
+        image_barriers: &[vk::ImageMemoryBarrier],
+    ) {
+        if buffer_barriers.is_empty() && image_barriers.is_empty() {
+            return;
+        }
+
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            if src_stages.is_empty() {
+                vk::PipelineStageFlags::TOP_OF_PIPE
+            } else {
+                src_stages
+            },
+            dst_stages,
+            vk::DependencyFlags::empty(),
+            &[],
+            buffer_barriers,
+            image_barriers,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transient(graph: &mut RenderGraph, name: &'static str, size: u32) -> GraphImage {
+        graph.create_image(name, TransientImageDesc::new(vk::Format::R8G8B8A8_UNORM, Ext2::new(size, size)))
+    }
+
+    fn imported(graph: &mut RenderGraph, name: &'static str) -> GraphImage {
+        let image = ImportedImage {
+            image: vk::Image::null(),
+            view: vk::ImageView::null(),
+            format: vk::Format::B8G8R8A8_SRGB,
+            extent: Ext2::new(64, 64),
+            subresource_range: vk::ImageSubresourceRange::default(),
+        };
+
+        graph.import_image(name, image, vk::ImageLayout::UNDEFINED, Some(vk::ImageLayout::PRESENT_SRC_KHR))
+    }
+
+    fn pass(graph: &mut RenderGraph, name: &'static str, images: &[(GraphImage, ImageAccess)]) {
+        let images = images.to_vec();
+        graph.add_pass(
+            name,
+            move |builder| {
+                for (image, access) in images {
+                    builder.image(image, access);
+                }
+            },
+            |_| {},
+        );
+    }
+
+    fn names(graph: &RenderGraph, order: &[usize]) -> Vec<&'static str> {
+        order.iter().map(|index| graph.passes[*index].name).collect()
+    }
+
+    #[test]
+    fn cull() {
+        let mut graph = RenderGraph::new();
+        let target = imported(&mut graph, "target");
+        let used = transient(&mut graph, "used", 64);
+        let unused = transient(&mut graph, "unused", 64);
+
+        pass(&mut graph, "write used", &[(used, ImageAccess::ColorAttachment)]);
+        pass(&mut graph, "write unused", &[(unused, ImageAccess::ColorAttachment)]);
+        pass(&mut graph, "read unused", &[(unused, ImageAccess::Sampled)]);
+        pass(
+            &mut graph,
+            "compose",
+            &[(used, ImageAccess::Sampled), (target, ImageAccess::ColorAttachment)],
+        );
+        graph.add_pass("query", |builder| _ = builder.side_effects(), |_| {});
+        graph.add_pass(
+            "buffer write",
+            |builder| _ = builder.buffer(GraphBuffer(0), BufferAccess::StorageWrite),
+            |_| {},
+        );
+        graph.import_buffer(vk::Buffer::null());
+
+        assert_eq!(graph.cull(), [true, false, false, true, true, true]);
+    }
+
+    #[test]
+    fn cull_keeps_attachment_contents() {
+        let mut graph = RenderGraph::new();
+        let target = imported(&mut graph, "target");
+        let color = transient(&mut graph, "color", 64);
+
+        pass(&mut graph, "clear", &[(color, ImageAccess::ColorAttachment)]);
+        pass(&mut graph, "blend", &[(color, ImageAccess::ColorAttachment)]);
+        pass(&mut graph, "blit", &[(color, ImageAccess::TransferSrc), (target, ImageAccess::TransferDst)]);
+        pass(&mut graph, "discarded", &[(color, ImageAccess::TransferDst)]);
+
+        // Attachment writes may depend on previous contents (e.g. blending), so "clear" is kept
+        assert_eq!(graph.cull(), [true, true, true, false]);
+    }
+
+    #[test]
+    fn schedule_spreads_dependent_passes() {
+        let mut graph = RenderGraph::new();
+        let target = imported(&mut graph, "target");
+        let shadow = transient(&mut graph, "shadow", 64);
+        let bloom = transient(&mut graph, "bloom", 32);
+
+        pass(&mut graph, "shadow", &[(shadow, ImageAccess::ColorAttachment)]);
+        pass(&mut graph, "light", &[(shadow, ImageAccess::Sampled), (target, ImageAccess::ColorAttachment)]);
+        pass(&mut graph, "bloom", &[(bloom, ImageAccess::ColorAttachment)]);
+        pass(&mut graph, "compose", &[(bloom, ImageAccess::Sampled), (target, ImageAccess::ColorAttachment)]);
+
+        let alive = graph.cull();
+        let order = graph.schedule(&alive);
+        assert_eq!(names(&graph, &order), ["shadow", "bloom", "light", "compose"]);
+    }
+
+    #[test]
+    fn schedule_respects_hazards() {
+        let mut graph = RenderGraph::new();
+        let target = imported(&mut graph, "target");
+        let data = transient(&mut graph, "data", 64);
+
+        // Write after read: "update" mustn't be moved before "read"
+        pass(&mut graph, "init", &[(data, ImageAccess::StorageWrite)]);
+        pass(&mut graph, "read", &[(data, ImageAccess::StorageRead), (target, ImageAccess::StorageWrite)]);
+        pass(&mut graph, "update", &[(data, ImageAccess::StorageWrite)]);
+        pass(&mut graph, "present", &[(data, ImageAccess::Sampled), (target, ImageAccess::ColorAttachment)]);
+        graph.add_pass("query", |builder| _ = builder.side_effects(), |_| {});
+
+        let alive = graph.cull();
+        let order = graph.schedule(&alive);
+        assert_eq!(names(&graph, &order), ["init", "read", "update", "present", "query"]);
+    }
+
+    #[test]
+    fn schedule_keeps_side_effects_order() {
+        let mut graph = RenderGraph::new();
+        let first = imported(&mut graph, "first");
+        let second = imported(&mut graph, "second");
+
+        pass(&mut graph, "first", &[(first, ImageAccess::ColorAttachment)]);
+        graph.add_pass("timestamp", |builder| _ = builder.side_effects(), |_| {});
+        pass(&mut graph, "second", &[(second, ImageAccess::ColorAttachment)]);
+
+        let alive = graph.cull();
+        let order = graph.schedule(&alive);
+        assert_eq!(names(&graph, &order), ["first", "timestamp", "second"]);
+    }
+
+    #[test]
+    fn transient_aliasing() {
+        let mut graph = RenderGraph::new();
+        let target = imported(&mut graph, "target");
+        let a = transient(&mut graph, "a", 64);
+        let b = transient(&mut graph, "b", 64);
+        let c = transient(&mut graph, "c", 64);
+        let small = transient(&mut graph, "small", 32);
+
+        pass(&mut graph, "write a", &[(a, ImageAccess::ColorAttachment)]);
+        pass(&mut graph, "a to b", &[(a, ImageAccess::Sampled), (b, ImageAccess::StorageWrite)]);
+        pass(&mut graph, "b to c", &[(b, ImageAccess::Sampled), (c, ImageAccess::ColorAttachment)]);
+        pass(&mut graph, "c to small", &[(c, ImageAccess::Sampled), (small, ImageAccess::ColorAttachment)]);
+        pass(&mut graph, "compose", &[(small, ImageAccess::Sampled), (target, ImageAccess::ColorAttachment)]);
+
+        let alive = graph.cull();
+        let order = graph.schedule(&alive);
+        let (slots, image_slots) = graph.transient_slots(&order);
+
+        // "a" and "c" lifetimes are disjoint, "b" overlaps both of them, "small" has another description
+        assert_eq!(slots.len(), 3);
+        assert_eq!(image_slots[target.0], None);
+        assert_eq!(image_slots[a.0], image_slots[c.0]);
+        assert_ne!(image_slots[a.0], image_slots[b.0]);
+        assert_ne!(image_slots[small.0], image_slots[a.0]);
+        assert_ne!(image_slots[small.0], image_slots[b.0]);
+
+        let shared = slots[image_slots[a.0].unwrap()];
+        assert_eq!(shared.usage, vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED);
+        assert_eq!(shared.last, 3);
+        assert_eq!(
+            slots[image_slots[b.0].unwrap()].usage,
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED
+        );
+    }
+
+    #[test]
+    fn culled_passes_dont_extend_lifetimes() {
+        let mut graph = RenderGraph::new();
+        let target = imported(&mut graph, "target");
+        let a = transient(&mut graph, "a", 64);
+        let b = transient(&mut graph, "b", 64);
+
+        pass(&mut graph, "write a", &[(a, ImageAccess::ColorAttachment)]);
+        pass(&mut graph, "a to target", &[(a, ImageAccess::Sampled), (target, ImageAccess::ColorAttachment)]);
+        pass(&mut graph, "unused", &[(a, ImageAccess::Sampled), (b, ImageAccess::ColorAttachment)]);
+
+        let alive = graph.cull();
+        let order = graph.schedule(&alive);
+        let (slots, image_slots) = graph.transient_slots(&order);
+
+        assert_eq!(names(&graph, &order), ["write a", "a to target"]);
+        assert_eq!(slots.len(), 1);
+        assert_eq!(image_slots[b.0], None);
+    }
+
+    #[test]
+    fn image_state_barriers() {
+        let mut state = ResourceState::external(vk::ImageLayout::UNDEFINED);
+
+        // Initial write waits for all previous commands
+        let barrier = state.access(ImageAccess::ColorAttachment.scope(), false);
+        assert_eq!(
+            barrier,
+            Some((vk::PipelineStageFlags::ALL_COMMANDS, vk::AccessFlags::empty(), vk::ImageLayout::UNDEFINED))
+        );
+
+        // Read in another layout transitions image after the write
+        let barrier = state.access(ImageAccess::Sampled.scope(), false);
+        assert_eq!(
+            barrier,
+            Some((
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            ))
+        );
+        assert_eq!(state.layout, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+
+        // Transition is visible to the following reads in the same layout
+        assert_eq!(state.access(ImageAccess::Sampled.scope(), false), None);
+
+        // Write after read waits for the reads only
+        let barrier = state.access(ImageAccess::StorageWrite.scope(), false);
+        assert_eq!(
+            barrier,
+            Some((SHADER_STAGES, vk::AccessFlags::empty(), vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL))
+        );
+
+        // Discarded contents are transitioned from undefined layout
+        let barrier = state.access(ImageAccess::StorageWrite.scope(), true);
+        assert_eq!(barrier.map(|(_, _, layout)| layout), Some(vk::ImageLayout::UNDEFINED));
+    }
+
+    #[test]
+    fn buffer_state_barriers() {
+        let mut state = ResourceState::external(vk::ImageLayout::UNDEFINED);
+
+        assert!(state.access(BufferAccess::TransferDst.scope(), false).is_some());
+
+        // Each new read access kind needs the write to be made visible
+        let upload = Some((
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::ImageLayout::UNDEFINED,
+        ));
+        assert_eq!(state.access(BufferAccess::Vertex.scope(), false), upload);
+        assert_eq!(state.access(BufferAccess::Vertex.scope(), false), None);
+        assert_eq!(state.access(BufferAccess::Index.scope(), false), upload);
+        assert_eq!(state.access(BufferAccess::Index.scope(), false), None);
+
+        // Write after reads waits for stages of all reads
+        let barrier = state.access(BufferAccess::StorageWrite.scope(), false);
+        assert_eq!(
+            barrier.map(|(stages, access, _)| (stages, access)),
+            Some((
+                vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::VERTEX_INPUT,
+                vk::AccessFlags::TRANSFER_WRITE,
+            ))
+        );
+    }
+}
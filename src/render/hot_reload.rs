@@ -0,0 +1,120 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use notify::Watcher;
+
+use super::shader_compiler::{self, CompiledShader};
+
+/// Shader source directory the crate is built from
+pub(super) const SHADER_SOURCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/shaders");
+
+/// Shader source directory watcher
+/// Recompiles changed shaders and shaders including changed files.
+pub(super) struct ShaderWatcher {
+    root: PathBuf,
+    events: mpsc::Receiver<notify::Result<notify::Event>>,
+    /// Files each shader source is built from, by shader source path
+    dependencies: HashMap<PathBuf, Vec<PathBuf>>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl ShaderWatcher {
+    pub fn new(root: &Path) -> Result<Self, notify::Error> {
+        let root = root.canonicalize()?;
+
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(&root, notify::RecursiveMode::Recursive)?;
+
+        let mut shader_watcher = Self {
+            root,
+            events,
+            dependencies: HashMap::new(),
+            _watcher: watcher,
+        };
+
+        for source in shader_compiler::find_shaders(&shader_watcher.root)? {
+            shader_watcher.update_dependencies(&source);
+        }
+
+        Ok(shader_watcher)
+    }
+
+    /// Shader source dependencies updating function
+    fn update_dependencies(&mut self, source: &Path) {
+        if !matches!(shader_compiler::shader_stage(source), Ok(Some(_))) {
+            return;
+        }
+
+        match shader_compiler::dependencies(&self.root, source) {
+            Ok(dependencies) => {
+                self.dependencies.insert(source.to_path_buf(), dependencies);
+            }
+            // Source is still compiled on change, so error is reported then
+            Err(_) => {
+                self.dependencies.insert(source.to_path_buf(), vec![source.to_path_buf()]);
+            }
+        }
+    }
+
+    /// Changed shaders recompiling function
+    /// Compilation errors are logged and failed shaders are skipped, so previous versions stay in use.
+    pub fn poll(&mut self) -> Vec<CompiledShader> {
+        let mut changed = HashSet::new();
+
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                Ok(event) if event.kind.is_create() || event.kind.is_modify() => {
+                    changed.extend(event.paths.into_iter().map(|path| path.canonicalize().unwrap_or(path)));
+                }
+                Ok(_) => {}
+                Err(err) => log::warn!("shader watcher error: {err}"),
+            }
+        }
+
+        if changed.is_empty() {
+            return Vec::new();
+        }
+
+        // New shader sources are compiled as well
+        for path in &changed {
+            if !self.dependencies.contains_key(path) {
+                self.update_dependencies(path);
+            }
+        }
+
+        let mut affected = self
+            .dependencies
+            .iter()
+            .filter(|(_, dependencies)| dependencies.iter().any(|path| changed.contains(path)))
+            .map(|(source, _)| source.clone())
+            .collect::<Vec<_>>();
+        affected.sort();
+
+        let mut shaders = Vec::new();
+
+        for source in affected {
+            match shader_compiler::compile(&self.root, &source) {
+                Ok(compiled) => {
+                    self.dependencies.insert(source, compiled.dependencies);
+                    shaders.extend(compiled.shaders);
+                }
+                Err(errors) => {
+                    for error in errors {
+                        log::error!("{error}");
+                    }
+                    log::error!(
+                        "failed to recompile {}, previous version is kept",
+                        shader_compiler::shader_name(&self.root, &source)
+                    );
+                    self.update_dependencies(&source);
+                }
+            }
+        }
+
+        shaders
+    }
+}
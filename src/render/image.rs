@@ -0,0 +1,518 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use crate::utility::math::Ext2;
+
+use super::{
+    allocator::{Allocation, AllocationDesc, Allocator, MemoryLocation},
+    buffer::ResourceCreateError,
+    error::{VulkanCallError, VulkanResultExt},
+    kernel::Kernel,
+};
+
+/// Image kind
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ImageKind {
+    /// 2D image or 2D image array
+    #[default]
+    D2,
+    /// Cube image or cube image array, layer count must be divisible by 6
+    Cube,
+}
+
+/// Image description
+#[derive(Copy, Clone, Debug)]
+pub struct ImageDesc {
+    name: &'static str,
+    kind: ImageKind,
+    format: vk::Format,
+    extent: Ext2<u32>,
+    mip_levels: u32,
+    array_layers: u32,
+    samples: vk::SampleCountFlags,
+    usage: vk::ImageUsageFlags,
+}
+
+impl ImageDesc {
+    /// Single level 2D image description create function
+    pub fn new(name: &'static str, format: vk::Format, extent: Ext2<u32>, usage: vk::ImageUsageFlags) -> Self {
+        Self {
+            name,
+            kind: ImageKind::D2,
+            format,
+            extent,
+            mip_levels: 1,
+            array_layers: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+            usage,
+        }
+    }
+
+    /// Cube image description create function
+    pub fn cube(name: &'static str, format: vk::Format, size: u32, usage: vk::ImageUsageFlags) -> Self {
+        Self::new(name, format, Ext2::new(size, size), usage)
+            .kind(ImageKind::Cube)
+            .array_layers(6)
+    }
+
+    /// Image kind setting function
+    pub fn kind(mut self, kind: ImageKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Mip level count setting function
+    pub fn mip_levels(mut self, mip_levels: u32) -> Self {
+        self.mip_levels = mip_levels;
+        self
+    }
+
+    /// Full mip chain (down to 1x1 level) setting function
+    pub fn full_mip_chain(mut self) -> Self {
+        self.mip_levels = mip_level_count(self.extent);
+        self
+    }
+
+    /// Array layer count setting function
+    pub fn array_layers(mut self, array_layers: u32) -> Self {
+        self.array_layers = array_layers;
+        self
+    }
+
+    /// Sample count setting function
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+}
+
+/// Full mip chain level count getting function
+pub fn mip_level_count(extent: Ext2<u32>) -> u32 {
+    u32::BITS - extent.w.max(extent.h).max(1).leading_zeros()
+}
+
+/// Image aspect for format getting function
+pub fn format_aspect(format: vk::Format) -> vk::ImageAspectFlags {
+    match format {
+        vk::Format::D16_UNORM | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => {
+            vk::ImageAspectFlags::DEPTH
+        }
+        vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+        vk::Format::D16_UNORM_S8_UINT | vk::Format::D24_UNORM_S8_UINT | vk::Format::D32_SFLOAT_S8_UINT => {
+            vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+        }
+        _ => vk::ImageAspectFlags::COLOR,
+    }
+}
+
+/// Texel block size in bytes getting function
+/// Returns `None` for formats without known block size (e.g. multi-planar ones) and for combined
+/// depth/stencil formats, as their aspects are copied separately and have different sizes.
+pub fn format_block_size(format: vk::Format) -> Option<u64> {
+    use vk::Format as F;
+
+    let size = match format {
+        F::R4G4_UNORM_PACK8 | F::R8_UNORM | F::R8_SNORM | F::R8_USCALED | F::R8_SSCALED | F::R8_UINT
+        | F::R8_SINT | F::R8_SRGB | F::S8_UINT => 1,
+
+        F::R4G4B4A4_UNORM_PACK16 | F::B4G4R4A4_UNORM_PACK16 | F::R5G6B5_UNORM_PACK16 | F::B5G6R5_UNORM_PACK16
+        | F::R5G5B5A1_UNORM_PACK16 | F::B5G5R5A1_UNORM_PACK16 | F::A1R5G5B5_UNORM_PACK16 | F::R8G8_UNORM
+        | F::R8G8_SNORM | F::R8G8_USCALED | F::R8G8_SSCALED | F::R8G8_UINT | F::R8G8_SINT | F::R8G8_SRGB
+        | F::R16_UNORM | F::R16_SNORM | F::R16_USCALED | F::R16_SSCALED | F::R16_UINT | F::R16_SINT
+        | F::R16_SFLOAT | F::D16_UNORM => 2,
+
+        F::R8G8B8_UNORM | F::R8G8B8_SNORM | F::R8G8B8_USCALED | F::R8G8B8_SSCALED | F::R8G8B8_UINT
+        | F::R8G8B8_SINT | F::R8G8B8_SRGB | F::B8G8R8_UNORM | F::B8G8R8_SNORM | F::B8G8R8_USCALED
+        | F::B8G8R8_SSCALED | F::B8G8R8_UINT | F::B8G8R8_SINT | F::B8G8R8_SRGB => 3,
+
+        F::R8G8B8A8_UNORM | F::R8G8B8A8_SNORM | F::R8G8B8A8_USCALED | F::R8G8B8A8_SSCALED | F::R8G8B8A8_UINT
+        | F::R8G8B8A8_SINT | F::R8G8B8A8_SRGB | F::B8G8R8A8_UNORM | F::B8G8R8A8_SNORM | F::B8G8R8A8_USCALED
+        | F::B8G8R8A8_SSCALED | F::B8G8R8A8_UINT | F::B8G8R8A8_SINT | F::B8G8R8A8_SRGB
+        | F::A8B8G8R8_UNORM_PACK32 | F::A8B8G8R8_SNORM_PACK32 | F::A8B8G8R8_USCALED_PACK32
+        | F::A8B8G8R8_SSCALED_PACK32 | F::A8B8G8R8_UINT_PACK32 | F::A8B8G8R8_SINT_PACK32
+        | F::A8B8G8R8_SRGB_PACK32 | F::A2R10G10B10_UNORM_PACK32 | F::A2R10G10B10_SNORM_PACK32
+        | F::A2R10G10B10_USCALED_PACK32 | F::A2R10G10B10_SSCALED_PACK32 | F::A2R10G10B10_UINT_PACK32
+        | F::A2R10G10B10_SINT_PACK32 | F::A2B10G10R10_UNORM_PACK32 | F::A2B10G10R10_SNORM_PACK32
+        | F::A2B10G10R10_USCALED_PACK32 | F::A2B10G10R10_SSCALED_PACK32 | F::A2B10G10R10_UINT_PACK32
+        | F::A2B10G10R10_SINT_PACK32 | F::R16G16_UNORM | F::R16G16_SNORM | F::R16G16_USCALED
+        | F::R16G16_SSCALED | F::R16G16_UINT | F::R16G16_SINT | F::R16G16_SFLOAT | F::R32_UINT | F::R32_SINT
+        | F::R32_SFLOAT | F::B10G11R11_UFLOAT_PACK32 | F::E5B9G9R9_UFLOAT_PACK32 | F::X8_D24_UNORM_PACK32
+        | F::D32_SFLOAT => 4,
+
+        F::R16G16B16_UNORM | F::R16G16B16_SNORM | F::R16G16B16_USCALED | F::R16G16B16_SSCALED
+        | F::R16G16B16_UINT | F::R16G16B16_SINT | F::R16G16B16_SFLOAT => 6,
+
+        F::R16G16B16A16_UNORM | F::R16G16B16A16_SNORM | F::R16G16B16A16_USCALED | F::R16G16B16A16_SSCALED
+        | F::R16G16B16A16_UINT | F::R16G16B16A16_SINT | F::R16G16B16A16_SFLOAT | F::R32G32_UINT
+        | F::R32G32_SINT | F::R32G32_SFLOAT | F::R64_UINT | F::R64_SINT | F::R64_SFLOAT => 8,
+
+        F::R32G32B32_UINT | F::R32G32B32_SINT | F::R32G32B32_SFLOAT => 12,
+
+        F::R32G32B32A32_UINT | F::R32G32B32A32_SINT | F::R32G32B32A32_SFLOAT | F::R64G64_UINT | F::R64G64_SINT
+        | F::R64G64_SFLOAT => 16,
+
+        F::R64G64B64_UINT | F::R64G64B64_SINT | F::R64G64B64_SFLOAT => 24,
+
+        F::R64G64B64A64_UINT | F::R64G64B64A64_SINT | F::R64G64B64A64_SFLOAT => 32,
+
+        // Block compressed formats
+        F::BC1_RGB_UNORM_BLOCK | F::BC1_RGB_SRGB_BLOCK | F::BC1_RGBA_UNORM_BLOCK | F::BC1_RGBA_SRGB_BLOCK
+        | F::BC4_UNORM_BLOCK | F::BC4_SNORM_BLOCK | F::ETC2_R8G8B8_UNORM_BLOCK | F::ETC2_R8G8B8_SRGB_BLOCK
+        | F::ETC2_R8G8B8A1_UNORM_BLOCK | F::ETC2_R8G8B8A1_SRGB_BLOCK | F::EAC_R11_UNORM_BLOCK
+        | F::EAC_R11_SNORM_BLOCK => 8,
+
+        F::BC2_UNORM_BLOCK | F::BC2_SRGB_BLOCK | F::BC3_UNORM_BLOCK | F::BC3_SRGB_BLOCK | F::BC5_UNORM_BLOCK
+        | F::BC5_SNORM_BLOCK | F::BC6H_UFLOAT_BLOCK | F::BC6H_SFLOAT_BLOCK | F::BC7_UNORM_BLOCK
+        | F::BC7_SRGB_BLOCK | F::ETC2_R8G8B8A8_UNORM_BLOCK | F::ETC2_R8G8B8A8_SRGB_BLOCK
+        | F::EAC_R11G11_UNORM_BLOCK | F::EAC_R11G11_SNORM_BLOCK => 16,
+
+        _ => return None,
+    };
+
+    Some(size)
+}
+
+/// Texel block extent getting function
+/// Block compressed formats have 4x4 blocks, other formats have single texel blocks.
+pub fn format_block_extent(format: vk::Format) -> Ext2<u32> {
+    use vk::Format as F;
+
+    match format {
+        F::BC1_RGB_UNORM_BLOCK
+        | F::BC1_RGB_SRGB_BLOCK
+        | F::BC1_RGBA_UNORM_BLOCK
+        | F::BC1_RGBA_SRGB_BLOCK
+        | F::BC2_UNORM_BLOCK
+        | F::BC2_SRGB_BLOCK
+        | F::BC3_UNORM_BLOCK
+        | F::BC3_SRGB_BLOCK
+        | F::BC4_UNORM_BLOCK
+        | F::BC4_SNORM_BLOCK
+        | F::BC5_UNORM_BLOCK
+        | F::BC5_SNORM_BLOCK
+        | F::BC6H_UFLOAT_BLOCK
+        | F::BC6H_SFLOAT_BLOCK
+        | F::BC7_UNORM_BLOCK
+        | F::BC7_SRGB_BLOCK
+        | F::ETC2_R8G8B8_UNORM_BLOCK
+        | F::ETC2_R8G8B8_SRGB_BLOCK
+        | F::ETC2_R8G8B8A1_UNORM_BLOCK
+        | F::ETC2_R8G8B8A1_SRGB_BLOCK
+        | F::ETC2_R8G8B8A8_UNORM_BLOCK
+        | F::ETC2_R8G8B8A8_SRGB_BLOCK
+        | F::EAC_R11_UNORM_BLOCK
+        | F::EAC_R11_SNORM_BLOCK
+        | F::EAC_R11G11_UNORM_BLOCK
+        | F::EAC_R11G11_SNORM_BLOCK => Ext2::new(4, 4),
+        _ => Ext2::new(1, 1),
+    }
+}
+
+/// Format features required for image usage getting function
+fn required_format_features(usage: vk::ImageUsageFlags) -> vk::FormatFeatureFlags {
+    let mut features = vk::FormatFeatureFlags::empty();
+
+    for (usage_flag, feature_flag) in [
+        (vk::ImageUsageFlags::SAMPLED, vk::FormatFeatureFlags::SAMPLED_IMAGE),
+        (vk::ImageUsageFlags::STORAGE, vk::FormatFeatureFlags::STORAGE_IMAGE),
+        (vk::ImageUsageFlags::COLOR_ATTACHMENT, vk::FormatFeatureFlags::COLOR_ATTACHMENT),
+        (vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT, vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT),
+        (vk::ImageUsageFlags::TRANSFER_SRC, vk::FormatFeatureFlags::TRANSFER_SRC),
+        (vk::ImageUsageFlags::TRANSFER_DST, vk::FormatFeatureFlags::TRANSFER_DST),
+    ] {
+        if usage.contains(usage_flag) {
+            features |= feature_flag;
+        }
+    }
+
+    features
+}
+
+/// Image view
+/// View is destroyed on drop, so it must be dropped before the image it's created from.
+pub struct ImageView {
+    kernel: Arc<Kernel>,
+    view: vk::ImageView,
+}
+
+impl ImageView {
+    /// View handle getting function
+    pub fn handle(&self) -> vk::ImageView {
+        self.view
+    }
+}
+
+impl Drop for ImageView {
+    fn drop(&mut self) {
+        unsafe { self.kernel.device.destroy_image_view(self.view, None) };
+    }
+}
+
+/// Optimal tiling device local image
+/// Image and its default view are destroyed and its memory is returned to the allocator on drop.
+pub struct Image {
+    kernel: Arc<Kernel>,
+    allocator: Arc<Allocator>,
+    image: vk::Image,
+    view: vk::ImageView,
+    allocation: Option<Allocation>,
+    desc: ImageDesc,
+}
+
+impl Image {
+    pub fn new(allocator: &Arc<Allocator>, desc: ImageDesc) -> Result<Self, ResourceCreateError> {
+        if desc.extent.w == 0 || desc.extent.h == 0 || desc.mip_levels == 0 || desc.array_layers == 0 {
+            return Err(ResourceCreateError::EmptyResource);
+        }
+
+        if desc.mip_levels > mip_level_count(desc.extent) {
+            return Err(ResourceCreateError::InvalidDescription("mip level count exceeds full mip chain length"));
+        }
+
+        if desc.kind == ImageKind::Cube && (!desc.array_layers.is_multiple_of(6) || desc.extent.w != desc.extent.h) {
+            return Err(ResourceCreateError::InvalidDescription(
+                "cube image must be square and have layer count divisible by 6",
+            ));
+        }
+
+        let kernel = allocator.kernel().clone();
+        let device = &kernel.device;
+
+        let format_properties = unsafe {
+            kernel
+                .instance
+                .get_physical_device_format_properties(kernel.physical_device, desc.format)
+        };
+        if !format_properties
+            .optimal_tiling_features
+            .contains(required_format_features(desc.usage))
+        {
+            return Err(ResourceCreateError::UnsupportedFormat {
+                format: desc.format,
+                usage: desc.usage,
+            });
+        }
+
+        let flags = match desc.kind {
+            ImageKind::D2 => vk::ImageCreateFlags::empty(),
+            ImageKind::Cube => vk::ImageCreateFlags::CUBE_COMPATIBLE,
+        };
+
+        let image = unsafe {
+            device.create_image(
+                &vk::ImageCreateInfo::default()
+                    .flags(flags)
+                    .image_type(vk::ImageType::TYPE_2D)
+                    .format(desc.format)
+                    .extent(vk::Extent3D {
+                        width: desc.extent.w,
+                        height: desc.extent.h,
+                        depth: 1,
+                    })
+                    .mip_levels(desc.mip_levels)
+                    .array_layers(desc.array_layers)
+                    .samples(desc.samples)
+                    .tiling(vk::ImageTiling::OPTIMAL)
+                    .usage(desc.usage)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                    .initial_layout(vk::ImageLayout::UNDEFINED),
+                None,
+            )
+        }
+        .context("vkCreateImage")?;
+
+        let requirements = unsafe { device.get_image_memory_requirements(image) };
+
+        // Render targets are usually recreated as a whole, so they don't fragment pools
+        let dedicated = desc.usage.intersects(
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        );
+
+        let allocation = match allocator.allocate(
+            &AllocationDesc::new(desc.name, requirements, MemoryLocation::GpuOnly, false).dedicated(dedicated),
+        ) {
+            Ok(allocation) => allocation,
+            Err(err) => {
+                unsafe { device.destroy_image(image, None) };
+                return Err(err.into());
+            }
+        };
+
+        let mut result = Self {
+            kernel: kernel.clone(),
+            allocator: allocator.clone(),
+            image,
+            view: vk::ImageView::null(),
+            allocation: Some(allocation),
+            desc,
+        };
+
+        // Image is destroyed by `Drop` of the result in case of error
+        unsafe {
+            let allocation = result.allocation();
+            device
+                .bind_image_memory(image, allocation.memory(), allocation.offset())
+                .context("vkBindImageMemory")?;
+        }
+
+        result.view = result.create_raw_view(
+            result.default_view_type(),
+            desc.format,
+            result.default_view_subresource_range(),
+        )?;
+
+        Ok(result)
+    }
+
+    /// Default view type getting function
+    fn default_view_type(&self) -> vk::ImageViewType {
+        match (self.desc.kind, self.desc.array_layers) {
+            (ImageKind::D2, 1) => vk::ImageViewType::TYPE_2D,
+            (ImageKind::D2, _) => vk::ImageViewType::TYPE_2D_ARRAY,
+            (ImageKind::Cube, 6) => vk::ImageViewType::CUBE,
+            (ImageKind::Cube, _) => vk::ImageViewType::CUBE_ARRAY,
+        }
+    }
+
+    /// Default view subresource range getting function
+    /// Sampled views must have single aspect, so depth+stencil images are viewed by depth aspect.
+    fn default_view_subresource_range(&self) -> vk::ImageSubresourceRange {
+        let range = self.subresource_range();
+        if range.aspect_mask.contains(vk::ImageAspectFlags::DEPTH) {
+            range.aspect_mask(vk::ImageAspectFlags::DEPTH)
+        } else {
+            range
+        }
+    }
+
+    fn create_raw_view(
+        &self,
+        view_type: vk::ImageViewType,
+        format: vk::Format,
+        subresource_range: vk::ImageSubresourceRange,
+    ) -> Result<vk::ImageView, VulkanCallError> {
+        unsafe {
+            self.kernel.device.create_image_view(
+                &vk::ImageViewCreateInfo::default()
+                    .image(self.image)
+                    .view_type(view_type)
+                    .format(format)
+                    .subresource_range(subresource_range),
+                None,
+            )
+        }
+        .context("vkCreateImageView")
+    }
+
+    /// Additional image view create function
+    /// Allows viewing separate mip levels, layers (e.g. cube faces) or compatible formats.
+    pub fn create_view(
+        &self,
+        view_type: vk::ImageViewType,
+        format: vk::Format,
+        subresource_range: vk::ImageSubresourceRange,
+    ) -> Result<ImageView, VulkanCallError> {
+        Ok(ImageView {
+            kernel: self.kernel.clone(),
+            view: self.create_raw_view(view_type, format, subresource_range)?,
+        })
+    }
+
+    /// Image handle getting function
+    pub fn handle(&self) -> vk::Image {
+        self.image
+    }
+
+    /// Default view (covering all levels and layers) getting function
+    /// # Note
+    /// View of depth+stencil image covers only depth aspect, view of both aspects
+    /// can be created by `create_view` with `subresource_range`.
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    /// Image memory allocation getting function
+    pub fn allocation(&self) -> &Allocation {
+        self.allocation.as_ref().unwrap()
+    }
+
+    pub fn kind(&self) -> ImageKind {
+        self.desc.kind
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.desc.format
+    }
+
+    pub fn extent(&self) -> Ext2<u32> {
+        self.desc.extent
+    }
+
+    pub fn mip_levels(&self) -> u32 {
+        self.desc.mip_levels
+    }
+
+    pub fn array_layers(&self) -> u32 {
+        self.desc.array_layers
+    }
+
+    pub fn usage(&self) -> vk::ImageUsageFlags {
+        self.desc.usage
+    }
+
+    /// Image aspect getting function
+    pub fn aspect(&self) -> vk::ImageAspectFlags {
+        format_aspect(self.desc.format)
+    }
+
+    /// Whole image subresource range getting function
+    pub fn subresource_range(&self) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange::default()
+            .aspect_mask(self.aspect())
+            .base_mip_level(0)
+            .level_count(self.desc.mip_levels)
+            .base_array_layer(0)
+            .layer_count(self.desc.array_layers)
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe {
+            if self.view != vk::ImageView::null() {
+                self.kernel.device.destroy_image_view(self.view, None);
+            }
+            self.kernel.device.destroy_image(self.image, None);
+        }
+
+        if let Some(allocation) = self.allocation.take() {
+            self.allocator.free(allocation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_sizes() {
+        assert_eq!(format_block_size(vk::Format::R8G8B8A8_SRGB), Some(4));
+        assert_eq!(format_block_size(vk::Format::R32G32B32_SFLOAT), Some(12));
+        assert_eq!(format_block_size(vk::Format::BC1_RGB_UNORM_BLOCK), Some(8));
+        assert_eq!(format_block_size(vk::Format::BC7_SRGB_BLOCK), Some(16));
+        assert_eq!(format_block_size(vk::Format::D16_UNORM), Some(2));
+        assert_eq!(format_block_size(vk::Format::X8_D24_UNORM_PACK32), Some(4));
+        assert_eq!(format_block_size(vk::Format::S8_UINT), Some(1));
+        assert_eq!(format_block_size(vk::Format::G8_B8_R8_3PLANE_420_UNORM), None);
+
+        for format in [
+            vk::Format::D16_UNORM_S8_UINT,
+            vk::Format::D24_UNORM_S8_UINT,
+            vk::Format::D32_SFLOAT_S8_UINT,
+        ] {
+            assert_eq!(format_block_size(format), None);
+        }
+    }
+}
@@ -1,30 +1,219 @@
 use ash::{khr, ext, vk, Entry};
 
-use std::{borrow::Cow, ffi::CString};
+use std::{
+    ffi::{CStr, CString},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use super::{
+    debug::{vulkan_debug_callback, DebugSink, StderrSink},
+    device_selection::{
+        device_name, select_physical_device, BindlessSupport, DeviceRequirements, DeviceSelectionError,
+        DeviceSelector, ExtensionFeatures, SelectedDevice,
+    },
+    error::{VulkanCallError, VulkanResultExt},
+    pipeline_cache::{self, default_pipeline_cache_dir, PipelineCacheError},
+    queue_family_indices::QueueFamilyIndices,
+};
+
+/// Guard running cleanup function on drop, used to release handles if kernel creation fails
+struct CleanupGuard<F: FnOnce()>(Option<F>);
+
+impl<F: FnOnce()> CleanupGuard<F> {
+    fn new(cleanup: F) -> Self {
+        Self(Some(cleanup))
+    }
+
+    /// Cleanup cancelling function
+    fn dismiss(mut self) {
+        self.0 = None;
+    }
+}
 
-use super::queue_family_indices::QueueFamilyIndices;
+impl<F: FnOnce()> Drop for CleanupGuard<F> {
+    fn drop(&mut self) {
+        if let Some(cleanup) = self.0.take() {
+            cleanup();
+        }
+    }
+}
 
+/// Vulkan instance and device holder
+/// Surface-related fields are `None` for headless kernels.
 pub struct Kernel {
+    pub entry: Entry,
+
     pub instance: ash::Instance,
-    pub instance_ext_surface: khr::surface::Instance,
-    pub instance_ext_debug: ext::debug_utils::Instance,
+    pub instance_ext_surface: Option<khr::surface::Instance>,
+    pub instance_ext_debug: Option<ext::debug_utils::Instance>,
 
-    pub surface: vk::SurfaceKHR,
+    pub surface: Option<vk::SurfaceKHR>,
     pub physical_device: vk::PhysicalDevice,
+    pub physical_device_properties: vk::PhysicalDeviceProperties,
 
     pub device: ash::Device,
-    pub device_ext_swapchain: khr::swapchain::Device,
-
-    pub debug_messenger: vk::DebugUtilsMessengerEXT,
+    /// Enabled device extensions
+    pub device_extensions: Vec<CString>,
+    /// Enabled device features
+    pub device_features: vk::PhysicalDeviceFeatures,
+    /// Enabled bindless descriptors support, `None` if bindless isn't requested or isn't supported
+    pub bindless: Option<BindlessSupport>,
+    /// Enabled features of known device extensions
+    pub extension_features: ExtensionFeatures,
+    pub device_ext_swapchain: Option<khr::swapchain::Device>,
+
+    pub main_queue: vk::Queue,
+    pub present_queue: Option<vk::Queue>,
+    /// Dedicated transfer queue, main queue if there is no dedicated transfer family
+    pub transfer_queue: vk::Queue,
+    /// Dedicated compute queue, main queue if there is no dedicated compute family
+    pub compute_queue: vk::Queue,
+
+    pub debug_messenger: Option<vk::DebugUtilsMessengerEXT>,
+
+    /// Pipeline cache, should be used for all pipeline creations
+    pub pipeline_cache: vk::PipelineCache,
+    /// Pipeline cache file, `None` if cache isn't persistent
+    pub pipeline_cache_path: Option<PathBuf>,
 
     pub queue_family_indices: QueueFamilyIndices,
+
+    /// Vulkan API version the instance is created with
+    pub api_version: u32,
+
+    /// Debug messenger user data, must outlive the messenger
+    _debug_sink: Box<Arc<dyn DebugSink>>,
+}
+
+/// Name of the Khronos validation layer
+const VALIDATION_LAYER_NAME: &CStr = c"VK_LAYER_KHRONOS_validation";
+
+/// Kernel configuration
+#[derive(Clone)]
+pub struct KernelConfig {
+    api_version: u32,
+    layers: Vec<CString>,
+    extensions: Vec<CString>,
+    required_extensions: Vec<CString>,
+    validation: bool,
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    debug_sink: Arc<dyn DebugSink>,
+    device_requirements: DeviceRequirements,
+    device_selector: Option<DeviceSelector>,
+    pipeline_cache_dir: Option<PathBuf>,
+}
+
+impl Default for KernelConfig {
+    fn default() -> Self {
+        Self {
+            api_version: vk::API_VERSION_1_0,
+            layers: Vec::new(),
+            extensions: Vec::new(),
+            required_extensions: Vec::new(),
+            validation: cfg!(debug_assertions),
+            message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            debug_sink: Arc::new(StderrSink),
+            device_requirements: DeviceRequirements::default(),
+            device_selector: None,
+            pipeline_cache_dir: default_pipeline_cache_dir(),
+        }
+    }
+}
+
+impl KernelConfig {
+    /// Vulkan API version setting function
+    /// Version is raised up to 1.2 if bindless descriptors are requested and loader supports it.
+    pub fn api_version(mut self, api_version: u32) -> Self {
+        self.api_version = api_version;
+        self
+    }
+
+    /// Optional instance layer adding function
+    /// Layer is enabled only if it is available.
+    pub fn layer(mut self, name: &CStr) -> Self {
+        self.layers.push(name.to_owned());
+        self
+    }
+
+    /// Optional instance extension adding function
+    /// Extension is enabled only if it is available.
+    pub fn extension(mut self, name: &CStr) -> Self {
+        self.extensions.push(name.to_owned());
+        self
+    }
+
+    /// Required instance extension adding function
+    /// Kernel creation fails if extension is not available.
+    pub fn required_extension(mut self, name: &CStr) -> Self {
+        self.required_extensions.push(name.to_owned());
+        self
+    }
+
+    /// Validation setting function
+    /// Validation enables Khronos validation layer (if it's available) and debug messenger.
+    /// It's enabled by default in debug builds and disabled in release ones.
+    pub fn validation(mut self, validation: bool) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    /// Minimal debug message severity setting function
+    /// Messages with less severity are not reported.
+    pub fn message_severity(mut self, message_severity: vk::DebugUtilsMessageSeverityFlagsEXT) -> Self {
+        self.message_severity = message_severity;
+        self
+    }
+
+    /// Debug message sink setting function
+    /// Messages are written to stderr by default.
+    pub fn debug_sink(mut self, debug_sink: Arc<dyn DebugSink>) -> Self {
+        self.debug_sink = debug_sink;
+        self
+    }
+
+    /// Physical device requirements setting function
+    pub fn device_requirements(mut self, device_requirements: DeviceRequirements) -> Self {
+        self.device_requirements = device_requirements;
+        self
+    }
+
+    /// Explicit physical device selector setting function
+    /// `WAT3RS_DEVICE` environment variable overrides this selector.
+    pub fn device_selector(mut self, device_selector: DeviceSelector) -> Self {
+        self.device_selector = Some(device_selector);
+        self
+    }
+
+    /// Pipeline cache directory setting function
+    /// Cache is loaded from this directory on kernel creation and written back on drop,
+    /// `None` disables persistent cache. `WAT3RS_CACHE_DIR` or user cache directory is used by default.
+    pub fn pipeline_cache_dir(mut self, pipeline_cache_dir: Option<PathBuf>) -> Self {
+        self.pipeline_cache_dir = pipeline_cache_dir;
+        self
+    }
+
+    /// Reported message severities getting function
+    fn message_severities(&self) -> vk::DebugUtilsMessageSeverityFlagsEXT {
+        [
+            vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE,
+            vk::DebugUtilsMessageSeverityFlagsEXT::INFO,
+            vk::DebugUtilsMessageSeverityFlagsEXT::WARNING,
+            vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+        ]
+        .into_iter()
+        .filter(|severity| severity.as_raw() >= self.message_severity.as_raw())
+        .fold(vk::DebugUtilsMessageSeverityFlagsEXT::empty(), |flags, severity| flags | severity)
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub enum KernelCreateError {
-    VulkanError(vk::Result),
-    AshLoadingError,
-    NoSuitablePhysicalDevices,
+    VulkanError(VulkanCallError),
+    AshLoadingError(Arc<ash::LoadingError>),
+    UnsupportedApiVersion { requested: u32, supported: u32 },
+    MissingInstanceExtension(CString),
+    DeviceSelectionError(DeviceSelectionError),
 
     #[default]
     Unknown,
@@ -34,82 +223,154 @@ impl std::fmt::Display for KernelCreateError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
-            Self::AshLoadingError => f.write_str("ash instance functions loading error"),
-            Self::NoSuitablePhysicalDevices => f.write_str("no suiting physical devices found"),
+            Self::AshLoadingError(err) => f.write_fmt(format_args!("vulkan library loading error: {err}")),
+            Self::UnsupportedApiVersion { requested, supported } => f.write_fmt(format_args!(
+                "vulkan {}.{} requested, but only {}.{} is supported",
+                vk::api_version_major(*requested),
+                vk::api_version_minor(*requested),
+                vk::api_version_major(*supported),
+                vk::api_version_minor(*supported),
+            )),
+            Self::MissingInstanceExtension(name) => f.write_fmt(format_args!(
+                "required instance extension {} is not available",
+                name.to_string_lossy()
+            )),
+            Self::DeviceSelectionError(err) => f.write_fmt(format_args!("physical device selection error: {err}")),
             Self::Unknown => f.write_str("unknown error"),
         }
     }
 }
 
-impl From<vk::Result> for KernelCreateError {
-    fn from(value: vk::Result) -> Self {
-        Self::VulkanError(value)
+impl std::error::Error for KernelCreateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::VulkanError(err) => Some(err),
+            Self::AshLoadingError(err) => Some(err.as_ref()),
+            Self::DeviceSelectionError(err) => Some(err),
+            _ => None,
+        }
     }
 }
 
-extern "system" fn vulkan_debug_callback(
-    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
-    message_types: vk::DebugUtilsMessageTypeFlagsEXT,
-    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT<'_>,
-    _p_user_data: *mut std::ffi::c_void,
-) -> vk::Bool32 {
-    fn fold_flags<F>(input: impl Iterator<Item = (F, &'static str)>, test_fn: impl Fn(&F) -> bool) -> String {
-        input
-            .filter_map(|(f, name)| if test_fn(&f) { Some(name) } else { None })
-            .fold(String::new(), |mut accumulator, value| {
-                if !accumulator.is_empty() {
-                    accumulator.push_str(" | ");
-                }
-                accumulator.push_str(value);
-                accumulator
-            })
-    }
-
-    println!(
-        "\
-        VULKAN VALIDATION MESSAGE\n\
-            SEVERITY: {}\n\
-            TYPE: {}\n\
-            MESSAGE: {}\n\
-        ",
-        fold_flags([
-            (vk::DebugUtilsMessageSeverityFlagsEXT::ERROR, "ERROR"),
-            (vk::DebugUtilsMessageSeverityFlagsEXT::INFO, "INFO"),
-            (vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE, "VERBOSE"),
-            (vk::DebugUtilsMessageSeverityFlagsEXT::WARNING, "WARNING"),
-        ].into_iter(), |f| message_severity.contains(*f)),
-        fold_flags([
-            (vk::DebugUtilsMessageTypeFlagsEXT::DEVICE_ADDRESS_BINDING, "DEVICE_ADDRESS_BINDING"),
-            (vk::DebugUtilsMessageTypeFlagsEXT::GENERAL,                "GENERAL"),
-            (vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE,            "PERFORMANCE"),
-            (vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,             "VALIDATION"),
-        ].into_iter(), |f| message_types.contains(*f)),
-        if let Some(r) = unsafe { p_callback_data.as_ref() } {
-            unsafe { core::ffi::CStr::from_ptr(r.p_message) }
-                .to_string_lossy()
-        } else {
-            Cow::Borrowed("<unknown>")
-        }
-    );
-
-    return vk::FALSE;
+impl From<VulkanCallError> for KernelCreateError {
+    fn from(value: VulkanCallError) -> Self {
+        Self::VulkanError(value)
+    }
 }
 
+impl From<DeviceSelectionError> for KernelCreateError {
+    fn from(value: DeviceSelectionError) -> Self {
+        Self::DeviceSelectionError(value)
+    }
+}
 
 impl Kernel {
+    /// Kernel with window surface create function
     pub fn new(
+        config: KernelConfig,
         window_handle: raw_window_handle::RawWindowHandle,
         display_handle: raw_window_handle::RawDisplayHandle,
     ) -> Result<Self, KernelCreateError> {
-        let entry = unsafe { Entry::load() }.map_err(|_| KernelCreateError::AshLoadingError)?;
+        Self::create(config, Some((window_handle, display_handle)))
+    }
+
+    /// Headless kernel create function
+    /// Kernel is created without surface and swapchain extension, so it
+    /// may be used for offscreen rendering on machines without display.
+    pub fn new_headless(config: KernelConfig) -> Result<Self, KernelCreateError> {
+        Self::create(config, None)
+    }
+
+    fn create(
+        config: KernelConfig,
+        window: Option<(raw_window_handle::RawWindowHandle, raw_window_handle::RawDisplayHandle)>,
+    ) -> Result<Self, KernelCreateError> {
+        let entry = unsafe { Entry::load() }.map_err(|err| KernelCreateError::AshLoadingError(Arc::new(err)))?;
+
+        // Vulkan 1.0 loaders don't provide vkEnumerateInstanceVersion at all
+        let supported_api_version = unsafe { entry.try_enumerate_instance_version() }
+            .context("vkEnumerateInstanceVersion")?
+            .unwrap_or(vk::API_VERSION_1_0);
+
+        if supported_api_version < config.api_version {
+            return Err(KernelCreateError::UnsupportedApiVersion {
+                requested: config.api_version,
+                supported: supported_api_version,
+            });
+        }
+
+        // Bindless support can't be queried by Vulkan 1.0 instance, so version is raised if loader allows it
+        let mut config = config;
+        if config.device_requirements.bindless_requested() && config.api_version < vk::API_VERSION_1_2 {
+            let raised_api_version = supported_api_version.min(vk::API_VERSION_1_2);
+
+            if raised_api_version < vk::API_VERSION_1_1 {
+                log::warn!("bindless descriptors require Vulkan 1.1 instance, loader supports only 1.0, bindless is disabled");
+            } else if raised_api_version > config.api_version {
+                log::info!(
+                    "Vulkan API version is raised to {}.{} for bindless descriptors",
+                    vk::api_version_major(raised_api_version),
+                    vk::api_version_minor(raised_api_version),
+                );
+                config.api_version = raised_api_version;
+            }
+        }
+
+        let available_layers = unsafe { entry.enumerate_instance_layer_properties() }
+            .context("vkEnumerateInstanceLayerProperties")?
+            .iter()
+            .filter_map(|properties| properties.layer_name_as_c_str().ok().map(CStr::to_owned))
+            .collect::<Vec<_>>();
+
+        let mut layers = config
+            .layers
+            .iter()
+            .filter(|name| available_layers.contains(name))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if config.validation {
+            if available_layers.iter().any(|name| name.as_c_str() == VALIDATION_LAYER_NAME) {
+                if !layers.iter().any(|name| name.as_c_str() == VALIDATION_LAYER_NAME) {
+                    layers.push(VALIDATION_LAYER_NAME.to_owned());
+                }
+            } else {
+                log::warn!("{} is not available, validation is disabled", VALIDATION_LAYER_NAME.to_string_lossy());
+            }
+        }
+
+        // Layers may provide their own extensions (e.g. validation layer provides debug utils)
+        let available_extensions = {
+            let mut extensions = Vec::new();
+
+            for layer in std::iter::once(None).chain(layers.iter().map(|name| Some(name.as_c_str()))) {
+                extensions.extend(
+                    unsafe { entry.enumerate_instance_extension_properties(layer) }
+                        .context("vkEnumerateInstanceExtensionProperties")?
+                        .iter()
+                        .filter_map(|properties| properties.extension_name_as_c_str().ok().map(CStr::to_owned))
+                );
+            }
+
+            extensions
+        };
+
+        if let Some(missing) = config
+            .required_extensions
+            .iter()
+            .find(|name| !available_extensions.contains(name))
+        {
+            return Err(KernelCreateError::MissingInstanceExtension(missing.clone()));
+        }
+
+        let debug_utils_enabled = config.validation
+            && available_extensions.iter().any(|name| name.as_c_str() == ext::debug_utils::NAME);
+
+        // Sink is boxed to pass it to the callback by thin pointer
+        let debug_sink = Box::new(config.debug_sink.clone());
 
         let mut debug_messenger_create_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
-            .message_severity(vk::DebugUtilsMessageSeverityFlagsEXT::empty()
-                | vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE
-                | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
-                | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
-                | vk::DebugUtilsMessageSeverityFlagsEXT::WARNING
-            )
+            .message_severity(config.message_severities())
             .message_type(vk::DebugUtilsMessageTypeFlagsEXT::empty()
                 | vk::DebugUtilsMessageTypeFlagsEXT::GENERAL
                 | vk::DebugUtilsMessageTypeFlagsEXT::DEVICE_ADDRESS_BINDING
@@ -117,138 +378,292 @@ impl Kernel {
                 | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
             )
             .pfn_user_callback(Some(vulkan_debug_callback))
+            .user_data(&*debug_sink as *const Arc<dyn DebugSink> as *mut std::ffi::c_void)
             ;
 
         let instance = unsafe {
             let app_name = CString::new("WAT3RS").unwrap();
 
             let extension_names = {
-                let mut names = vec![
-                    ext::debug_utils::NAME.as_ptr()
-                ];
+                let mut names = config
+                    .required_extensions
+                    .iter()
+                    .chain(config.extensions.iter().filter(|name| available_extensions.contains(name)))
+                    .map(|name| name.as_ptr())
+                    .collect::<Vec<_>>();
+
+                if debug_utils_enabled {
+                    names.push(ext::debug_utils::NAME.as_ptr());
+                }
 
-                names.extend_from_slice(ash_window::enumerate_required_extensions(display_handle)?);
+                if let Some((_, display_handle)) = window {
+                    names.extend_from_slice(
+                        ash_window::enumerate_required_extensions(display_handle)
+                            .context("ash_window::enumerate_required_extensions")?
+                    );
+                }
 
                 names
             };
 
-            let layer_names = {
-                let names = vec![
-                    c"VK_LAYER_KHRONOS_validation".as_ptr(),
-                    c"VK_LAYER_RENDERDOC_Capture".as_ptr(),
-                ];
-                names
-            };
+            let layer_names = layers
+                .iter()
+                .map(|name| name.as_ptr())
+                .collect::<Vec<_>>();
+
+            let mut create_info = vk::InstanceCreateInfo::default()
+                .enabled_extension_names(&extension_names)
+                .enabled_layer_names(&layer_names);
 
-            entry
-                .create_instance(
-                    &vk::InstanceCreateInfo::default()
-                        .application_info(
-                            &vk::ApplicationInfo::default()
-                                .api_version(vk::API_VERSION_1_0)
-                                .application_name(app_name.as_c_str()),
-                        )
-                        .enabled_extension_names(&extension_names)
-                        .enabled_layer_names(&layer_names)
-                        .push_next(&mut debug_messenger_create_info),
-                    None,
-                )?
+            let application_info = vk::ApplicationInfo::default()
+                .api_version(config.api_version)
+                .application_name(app_name.as_c_str());
+
+            create_info = create_info.application_info(&application_info);
+
+            // Debug messenger create info is chained to catch instance creation messages
+            if debug_utils_enabled {
+                create_info = create_info.push_next(&mut debug_messenger_create_info);
+            }
+
+            entry.create_instance(&create_info, None).context("vkCreateInstance")?
         };
-        let instance_ext_surface = khr::surface::Instance::new(&entry, &instance);
-        let instance_ext_debug = ext::debug_utils::Instance::new(&entry, &instance);
+        let instance_guard = CleanupGuard::new(|| unsafe { instance.destroy_instance(None) });
 
-        let debug_messenger = unsafe { instance_ext_debug.create_debug_utils_messenger(&debug_messenger_create_info, None)? };
+        let (instance_ext_debug, debug_messenger) = if debug_utils_enabled {
+            let instance_ext_debug = ext::debug_utils::Instance::new(&entry, &instance);
+            let debug_messenger = unsafe { instance_ext_debug.create_debug_utils_messenger(&debug_messenger_create_info, None) }
+                .context("vkCreateDebugUtilsMessengerEXT")?;
 
-        let surface = unsafe { ash_window::create_surface(&entry, &instance, display_handle, window_handle, None) }?;
+            (Some(instance_ext_debug), Some(debug_messenger))
+        } else {
+            (None, None)
+        };
+        let debug_messenger_guard = CleanupGuard::new(|| {
+            if let Some((instance_ext_debug, debug_messenger)) = instance_ext_debug.as_ref().zip(debug_messenger) {
+                unsafe { instance_ext_debug.destroy_debug_utils_messenger(debug_messenger, None) };
+            }
+        });
+
+        let (instance_ext_surface, surface) = match window {
+            Some((window_handle, display_handle)) => {
+                let instance_ext_surface = khr::surface::Instance::new(&entry, &instance);
+                let surface = unsafe { ash_window::create_surface(&entry, &instance, display_handle, window_handle, None) }
+                    .context("ash_window::create_surface")?;
+
+                (Some(instance_ext_surface), Some(surface))
+            }
+            None => (None, None),
+        };
+        let surface_guard = CleanupGuard::new(|| {
+            if let Some((instance_ext_surface, surface)) = instance_ext_surface.as_ref().zip(surface) {
+                unsafe { instance_ext_surface.destroy_surface(surface, None) };
+            }
+        });
 
-        let (physical_device, queue_family_indices) =
-            unsafe { instance.enumerate_physical_devices() }
-                ?
-                .iter()
-                .copied()
-                .filter_map(|physical_device| {
-                    let queue_family_indices = QueueFamilyIndices::new(
-                        physical_device,
-                        surface,
-                        &instance,
-                        &instance_ext_surface,
-                    )?;
-                    let properties =
-                        unsafe { instance.get_physical_device_properties(physical_device) };
-
-                    let rate = properties.limits.max_image_dimension2_d;
-
-                    Some((physical_device, queue_family_indices, rate))
-                })
-                .fold(
-                    Option::<(vk::PhysicalDevice, QueueFamilyIndices, u32)>::None,
-                    |best, info| {
-                        if best
-                            .as_ref()
-                            .map(|best_info| best_info.2 < info.2)
-                            .unwrap_or(true)
-                        {
-                            Some(info)
-                        } else {
-                            best
-                        }
-                    },
-                )
-                .map(|(device, queue_family_indices, _)| (device, queue_family_indices))
-                .ok_or(KernelCreateError::NoSuitablePhysicalDevices)?;
+        let SelectedDevice {
+            physical_device,
+            properties: physical_device_properties,
+            queue_family_indices,
+            extensions: device_extensions,
+            bindless,
+            extension_features,
+        } = select_physical_device(
+            &instance,
+            config.api_version,
+            surface.zip(instance_ext_surface.as_ref()),
+            &config.device_requirements,
+            DeviceSelector::from_env().or(config.device_selector.clone()).as_ref(),
+        )?;
+        if config.device_requirements.bindless_requested() && bindless.is_none() {
+            log::warn!(
+                "{} doesn't support bindless descriptors, bindless is disabled",
+                device_name(&physical_device_properties)
+            );
+        }
+        let device_features = *config.device_requirements.required_features();
 
         let device = {
-            let queue_create_infos = if queue_family_indices.main == queue_family_indices.present {
-                vec![
+            let queue_create_infos = queue_family_indices
+                .unique()
+                .into_iter()
+                .map(|index| {
                     vk::DeviceQueueCreateInfo::default()
-                        .queue_family_index(queue_family_indices.main)
-                        .queue_priorities(&[1.0, 0.5])
-                ]
+                        .queue_family_index(index)
+                        .queue_priorities(if index == queue_family_indices.main { &[1.0] } else { &[0.5] })
+                })
+                .collect::<Vec<_>>();
+
+            let enabled_extension_names = device_extensions
+                .iter()
+                .map(|name| name.as_ptr())
+                .collect::<Vec<_>>();
+
+            let mut create_info = vk::DeviceCreateInfo::default()
+                .enabled_extension_names(&enabled_extension_names)
+                .enabled_features(&device_features)
+                .queue_create_infos(&queue_create_infos);
+
+            // Vulkan 1.2/1.3 feature structures mustn't be chained together with feature structures of
+            // extensions promoted to these versions, so either core or extension structures are used
+            let core_12 = config.api_version >= vk::API_VERSION_1_2
+                && physical_device_properties.api_version >= vk::API_VERSION_1_2;
+            let core_13 = config.api_version >= vk::API_VERSION_1_3
+                && physical_device_properties.api_version >= vk::API_VERSION_1_3;
+            let bindless_enabled = bindless.is_some();
+
+            let mut vulkan_12_features = vk::PhysicalDeviceVulkan12Features::default()
+                .shader_sampled_image_array_non_uniform_indexing(bindless_enabled)
+                .shader_storage_buffer_array_non_uniform_indexing(bindless_enabled)
+                .descriptor_binding_sampled_image_update_after_bind(bindless_enabled)
+                .descriptor_binding_storage_buffer_update_after_bind(bindless_enabled)
+                .descriptor_binding_update_unused_while_pending(bindless_enabled)
+                .descriptor_binding_partially_bound(bindless_enabled)
+                .runtime_descriptor_array(bindless_enabled)
+                .timeline_semaphore(extension_features.timeline_semaphore)
+                .buffer_device_address(extension_features.buffer_device_address);
+            let mut vulkan_13_features = vk::PhysicalDeviceVulkan13Features::default()
+                .dynamic_rendering(extension_features.dynamic_rendering)
+                .synchronization2(extension_features.synchronization2);
+
+            let mut descriptor_indexing_features = vk::PhysicalDeviceDescriptorIndexingFeatures::default()
+                .shader_sampled_image_array_non_uniform_indexing(true)
+                .shader_storage_buffer_array_non_uniform_indexing(true)
+                .descriptor_binding_sampled_image_update_after_bind(true)
+                .descriptor_binding_storage_buffer_update_after_bind(true)
+                .descriptor_binding_update_unused_while_pending(true)
+                .descriptor_binding_partially_bound(true)
+                .runtime_descriptor_array(true);
+            let mut timeline_semaphore_features =
+                vk::PhysicalDeviceTimelineSemaphoreFeatures::default().timeline_semaphore(true);
+            let mut buffer_device_address_features =
+                vk::PhysicalDeviceBufferDeviceAddressFeatures::default().buffer_device_address(true);
+            let mut dynamic_rendering_features =
+                vk::PhysicalDeviceDynamicRenderingFeatures::default().dynamic_rendering(true);
+            let mut synchronization2_features =
+                vk::PhysicalDeviceSynchronization2Features::default().synchronization2(true);
+
+            if core_12 {
+                if bindless_enabled || extension_features.timeline_semaphore || extension_features.buffer_device_address {
+                    create_info = create_info.push_next(&mut vulkan_12_features);
+                }
             } else {
-                vec![
-                    vk::DeviceQueueCreateInfo::default()
-                        .queue_family_index(queue_family_indices.main)
-                        .queue_priorities(&[1.0]),
-                    vk::DeviceQueueCreateInfo::default()
-                        .queue_family_index(queue_family_indices.present)
-                        .queue_priorities(&[0.5]),
-                ]
-            };
+                if bindless_enabled {
+                    create_info = create_info.push_next(&mut descriptor_indexing_features);
+                }
+                if extension_features.timeline_semaphore {
+                    create_info = create_info.push_next(&mut timeline_semaphore_features);
+                }
+                if extension_features.buffer_device_address {
+                    create_info = create_info.push_next(&mut buffer_device_address_features);
+                }
+            }
 
-            let enabled_extension_names = vec![khr::swapchain::NAME.as_ptr()];
-
-            unsafe {
-                instance.create_device(
-                    physical_device,
-                    &vk::DeviceCreateInfo::default()
-                        .enabled_extension_names(&enabled_extension_names)
-                        .queue_create_infos(&queue_create_infos),
-                    None,
-                )
-            }?
+            if core_13 {
+                if extension_features.dynamic_rendering || extension_features.synchronization2 {
+                    create_info = create_info.push_next(&mut vulkan_13_features);
+                }
+            } else {
+                if extension_features.dynamic_rendering {
+                    create_info = create_info.push_next(&mut dynamic_rendering_features);
+                }
+                if extension_features.synchronization2 {
+                    create_info = create_info.push_next(&mut synchronization2_features);
+                }
+            }
+
+            unsafe { instance.create_device(physical_device, &create_info, None) }
+                .context("vkCreateDevice")?
         };
-        let device_ext_swapchain = khr::swapchain::Device::new(&instance, &device);
+        let device_guard = CleanupGuard::new(|| unsafe { device.destroy_device(None) });
+        let device_ext_swapchain = surface.map(|_| khr::swapchain::Device::new(&instance, &device));
+
+        let main_queue = unsafe { device.get_device_queue(queue_family_indices.main, 0) };
+        let present_queue = queue_family_indices
+            .present
+            .map(|present| unsafe { device.get_device_queue(present, 0) });
+        let transfer_queue = unsafe { device.get_device_queue(queue_family_indices.transfer_or_main(), 0) };
+        let compute_queue = unsafe { device.get_device_queue(queue_family_indices.compute_or_main(), 0) };
+
+        // Mismatched cache data is discarded, so driver never sees data of another device
+        let pipeline_cache_path = config
+            .pipeline_cache_dir
+            .as_ref()
+            .map(|dir| pipeline_cache::pipeline_cache_path(dir, &physical_device_properties));
+        let pipeline_cache_data = pipeline_cache_path
+            .as_ref()
+            .map(|path| pipeline_cache::load(path, &physical_device_properties))
+            .unwrap_or_default();
+        let pipeline_cache = unsafe {
+            device.create_pipeline_cache(
+                &vk::PipelineCacheCreateInfo::default().initial_data(&pipeline_cache_data),
+                None,
+            )
+        }
+        .context("vkCreatePipelineCache")?;
+
+        // Handles are owned by the kernel from now on
+        device_guard.dismiss();
+        surface_guard.dismiss();
+        debug_messenger_guard.dismiss();
+        instance_guard.dismiss();
 
         Ok(Kernel {
+            entry,
             device,
             instance,
             physical_device,
+            physical_device_properties,
+            device_extensions,
+            device_features,
+            bindless,
+            extension_features,
             surface,
             instance_ext_surface,
             instance_ext_debug,
             debug_messenger,
+            pipeline_cache,
+            pipeline_cache_path,
             device_ext_swapchain,
+            api_version: config.api_version,
+            _debug_sink: debug_sink,
+            main_queue,
+            present_queue,
+            transfer_queue,
+            compute_queue,
             queue_family_indices,
         })
     }
+
+    /// Pipeline cache to file writing function
+    /// Cache is also written on kernel drop.
+    pub fn save_pipeline_cache(&self) -> Result<(), PipelineCacheError> {
+        let Some(path) = &self.pipeline_cache_path else {
+            return Ok(());
+        };
+
+        let data = unsafe { self.device.get_pipeline_cache_data(self.pipeline_cache) }
+            .context("vkGetPipelineCacheData")?;
+
+        pipeline_cache::store(path, &data)
+    }
 }
 
 impl Drop for Kernel {
     fn drop(&mut self) {
+        if let Err(err) = self.save_pipeline_cache() {
+            log::warn!("pipeline cache saving error: {err}");
+        }
+
         unsafe {
+            self.device.destroy_pipeline_cache(self.pipeline_cache, None);
             self.device.destroy_device(None);
-            self.instance_ext_debug.destroy_debug_utils_messenger(self.debug_messenger, None);
-            self.instance_ext_surface.destroy_surface(self.surface, None);
+            if let Some((instance_ext_debug, debug_messenger)) = self.instance_ext_debug.as_ref().zip(self.debug_messenger) {
+                instance_ext_debug.destroy_debug_utils_messenger(debug_messenger, None);
+            }
+            if let Some((instance_ext_surface, surface)) = self.instance_ext_surface.as_ref().zip(self.surface) {
+                instance_ext_surface.destroy_surface(surface, None);
+            }
             self.instance.destroy_instance(None);
         }
     }
@@ -1,27 +1,526 @@
 use std::sync::Arc;
 
-use kernel::Kernel;
+use ash::vk;
+use error::VulkanResultExt;
+use frame::FrameRing;
+use swapchain::Swapchain;
 
-pub(self) mod kernel;
-pub(self) mod queue_family_indices;
-pub(self) mod swapchain;
+use crate::utility::math::Ext2;
+
+mod allocator;
+mod bindless;
+mod buffer;
+mod debug;
+mod descriptor;
+mod device_selection;
+mod error;
+mod frame;
+mod graph;
+#[cfg(feature = "hot-reload")]
+mod hot_reload;
+mod image;
+mod kernel;
+mod pipeline;
+mod pipeline_cache;
+mod pipeline_library;
+mod queue_family_indices;
+mod shader;
+#[cfg(feature = "hot-reload")]
+mod shader_compiler;
+mod swapchain;
+mod upload;
+
+pub use allocator::{
+    Allocation, AllocationDesc, AllocationError, AllocationStrategy, Allocator, AllocatorConfig,
+    AllocatorStats, MemoryLocation,
+};
+pub use bindless::{
+    BindlessBuffer, BindlessConfig, BindlessError, BindlessImage, BindlessTable, BINDLESS_BUFFER_BINDING,
+    BINDLESS_IMAGE_BINDING,
+};
+pub use buffer::{Buffer, BufferWriteError, ResourceCreateError};
+pub use debug::{
+    CollectorSink, DebugMessage, DebugMessageSeverity, DebugObject, DebugSink, LogSink, StderrSink,
+};
+pub use descriptor::{
+    DescriptorAllocateError, DescriptorAllocator, DescriptorAllocatorConfig, DescriptorBinding,
+    DescriptorLayoutCache, DescriptorWriter,
+};
+pub use device_selection::{
+    BindlessSupport, DeviceLimits, DeviceRequirements, DeviceSelectionError, DeviceSelector, ExtensionFeatures,
+    DEVICE_SELECTOR_ENV_VAR,
+};
+pub use error::VulkanCallError;
+pub use frame::DEFAULT_FRAMES_IN_FLIGHT;
+pub use graph::{
+    BufferAccess, GraphBuffer, GraphError, GraphImage, ImageAccess, ImportedImage, PassBuilder,
+    PassContext, RenderGraph, TransientImageDesc, TransientPool, DEFAULT_TRANSIENT_MAX_UNUSED_FRAMES,
+};
+pub use image::{
+    format_aspect, format_block_extent, format_block_size, mip_level_count, Image, ImageDesc, ImageKind, ImageView,
+};
+pub use kernel::{Kernel, KernelConfig, KernelCreateError};
+pub use pipeline::{
+    vertex_field_format, BlendMode, GraphicsPipeline, GraphicsPipelineBuilder, PipelineError, Vertex,
+    VertexAttribute, VertexFormat,
+};
+pub use pipeline_cache::{default_pipeline_cache_dir, PipelineCacheError, PIPELINE_CACHE_DIR_ENV_VAR};
+pub use pipeline_library::{PipelineId, PipelineLibrary};
+pub use queue_family_indices::QueueFamilyIndices;
+pub use shader::{
+    embedded_shader, embedded_shader_names, read_spirv, ShaderError, ShaderModule, SPIRV_MAGIC,
+};
+pub use swapchain::{SwapchainConfig, SwapchainCreateError};
+pub use upload::{UploadError, UploadTicket, Uploader, UploaderConfig};
+
+/// Color swapchain images are cleared with
+const CLEAR_COLOR: [f32; 4] = [0.30, 0.47, 0.80, 1.0];
 
 pub struct Render {
+    frames: FrameRing,
+    extent: vk::Extent2D,
+    swapchain_outdated: bool,
+    swapchain: Swapchain,
+    transients: TransientPool,
+    #[cfg(feature = "hot-reload")]
+    shader_watcher: Option<hot_reload::ShaderWatcher>,
+    pipelines: PipelineLibrary,
+    bindless: Option<BindlessTable>,
+    descriptors: DescriptorAllocator,
+    descriptor_layouts: DescriptorLayoutCache,
+    uploader: Uploader,
+    allocator: Arc<Allocator>,
     kernel: Arc<Kernel>,
 }
 
+#[derive(Clone, Debug)]
 pub enum RenderCreateError {
-    Unknown,
+    KernelCreateError(KernelCreateError),
+    SwapchainCreateError(SwapchainCreateError),
+    UploadError(UploadError),
+    BindlessError(BindlessError),
+    /// Frame resources creation error
+    VulkanError(VulkanCallError),
+}
+
+impl std::fmt::Display for RenderCreateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::KernelCreateError(err) => f.write_fmt(format_args!("kernel creation error: {err}")),
+            Self::SwapchainCreateError(err) => {
+                f.write_fmt(format_args!("swapchain creation error: {err}"))
+            }
+            Self::UploadError(err) => f.write_fmt(format_args!("uploader creation error: {err}")),
+            Self::BindlessError(err) => f.write_fmt(format_args!("bindless table creation error: {err}")),
+            Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
+        }
+    }
+}
+
+impl std::error::Error for RenderCreateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::KernelCreateError(err) => Some(err),
+            Self::SwapchainCreateError(err) => Some(err),
+            Self::UploadError(err) => Some(err),
+            Self::BindlessError(err) => Some(err),
+            Self::VulkanError(err) => Some(err),
+        }
+    }
+}
+
+impl From<KernelCreateError> for RenderCreateError {
+    fn from(value: KernelCreateError) -> Self {
+        Self::KernelCreateError(value)
+    }
+}
+
+impl From<VulkanCallError> for RenderCreateError {
+    fn from(value: VulkanCallError) -> Self {
+        Self::VulkanError(value)
+    }
+}
+
+impl From<SwapchainCreateError> for RenderCreateError {
+    fn from(value: SwapchainCreateError) -> Self {
+        Self::SwapchainCreateError(value)
+    }
+}
+
+impl From<UploadError> for RenderCreateError {
+    fn from(value: UploadError) -> Self {
+        Self::UploadError(value)
+    }
+}
+
+impl From<BindlessError> for RenderCreateError {
+    fn from(value: BindlessError) -> Self {
+        Self::BindlessError(value)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum RenderFrameError {
+    VulkanError(VulkanCallError),
+    SwapchainCreateError(SwapchainCreateError),
+    UploadError(UploadError),
+    GraphError(GraphError),
+}
+
+impl std::fmt::Display for RenderFrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
+            Self::SwapchainCreateError(err) => {
+                f.write_fmt(format_args!("swapchain recreation error: {err}"))
+            }
+            Self::UploadError(err) => f.write_fmt(format_args!("upload flush error: {err}")),
+            Self::GraphError(err) => f.write_fmt(format_args!("render graph error: {err}")),
+        }
+    }
+}
+
+impl std::error::Error for RenderFrameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::VulkanError(err) => Some(err),
+            Self::SwapchainCreateError(err) => Some(err),
+            Self::UploadError(err) => Some(err),
+            Self::GraphError(err) => Some(err),
+        }
+    }
+}
+
+impl From<VulkanCallError> for RenderFrameError {
+    fn from(value: VulkanCallError) -> Self {
+        Self::VulkanError(value)
+    }
+}
+
+impl From<SwapchainCreateError> for RenderFrameError {
+    fn from(value: SwapchainCreateError) -> Self {
+        Self::SwapchainCreateError(value)
+    }
+}
+
+impl From<UploadError> for RenderFrameError {
+    fn from(value: UploadError) -> Self {
+        Self::UploadError(value)
+    }
+}
+
+impl From<GraphError> for RenderFrameError {
+    fn from(value: GraphError) -> Self {
+        Self::GraphError(value)
+    }
 }
 
 impl Render {
     pub fn new(
         window_handle: raw_window_handle::RawWindowHandle,
         display_handle: raw_window_handle::RawDisplayHandle,
+        extent: Ext2<u32>,
+        kernel_config: KernelConfig,
+        swapchain_config: SwapchainConfig,
     ) -> Result<Self, RenderCreateError> {
-        let kernel =
-            Arc::new(Kernel::new(window_handle, display_handle).expect("Error creating kernel"));
+        let kernel = Arc::new(Kernel::new(kernel_config, window_handle, display_handle)?);
+
+        let extent = vk::Extent2D {
+            width: extent.w,
+            height: extent.h,
+        };
+        let swapchain = Swapchain::new(kernel.clone(), swapchain_config, extent)?;
+        let swapchain_outdated = swapchain.handle() == vk::SwapchainKHR::null();
+        let allocator = Arc::new(Allocator::new(kernel.clone(), AllocatorConfig::default()));
+        let uploader = Uploader::new(&allocator, UploaderConfig::default())?;
+        let transients = TransientPool::new(allocator.clone());
+        let pipelines = PipelineLibrary::new(kernel.clone());
+        // Devices without descriptor indexing use classic descriptor sets
+        let bindless = match kernel.bindless {
+            Some(_) => Some(BindlessTable::new(kernel.clone(), BindlessConfig::default())?),
+            None => None,
+        };
+        let descriptors = DescriptorAllocator::new(kernel.clone(), DescriptorAllocatorConfig::default());
+        let descriptor_layouts = DescriptorLayoutCache::new(kernel.clone());
+
+        #[cfg(feature = "hot-reload")]
+        let shader_watcher = match hot_reload::ShaderWatcher::new(std::path::Path::new(hot_reload::SHADER_SOURCE_DIR)) {
+            Ok(watcher) => Some(watcher),
+            Err(err) => {
+                log::warn!("shader hot reload is disabled: {err}");
+                None
+            }
+        };
+
+        let frames = FrameRing::new(kernel.clone(), DEFAULT_FRAMES_IN_FLIGHT)?;
+
+        Ok(Self {
+            frames,
+            extent,
+            swapchain_outdated,
+            swapchain,
+            transients,
+            #[cfg(feature = "hot-reload")]
+            shader_watcher,
+            pipelines,
+            bindless,
+            descriptors,
+            descriptor_layouts,
+            uploader,
+            allocator,
+            kernel,
+        })
+    }
+
+    /// Kernel getting function
+    pub fn kernel(&self) -> &Arc<Kernel> {
+        &self.kernel
+    }
+
+    /// Device memory allocator getting function
+    pub fn allocator(&self) -> &Arc<Allocator> {
+        &self.allocator
+    }
+
+    /// Uploader getting function
+    /// Recorded uploads are submitted before the next frame, so it sees their results.
+    pub fn uploader(&mut self) -> &mut Uploader {
+        &mut self.uploader
+    }
+
+    /// Pipeline library getting function
+    pub fn pipelines(&self) -> &PipelineLibrary {
+        &self.pipelines
+    }
+
+    /// Pipeline library mutable getting function
+    pub fn pipelines_mut(&mut self) -> &mut PipelineLibrary {
+        &mut self.pipelines
+    }
+
+    /// Bindless table getting function
+    /// Table exists only if bindless is requested by `DeviceRequirements::bindless` and supported by device,
+    /// classic descriptor sets should be used otherwise.
+    pub fn bindless(&mut self) -> Option<&mut BindlessTable> {
+        self.bindless.as_mut()
+    }
+
+    /// Descriptor set layout cache getting function
+    pub fn descriptor_layouts(&mut self) -> &mut DescriptorLayoutCache {
+        &mut self.descriptor_layouts
+    }
+
+    /// Persistent descriptor set allocator getting function
+    /// Sets allocated from it live as long as `Render` (e.g. material sets).
+    pub fn descriptors(&mut self) -> &mut DescriptorAllocator {
+        &mut self.descriptors
+    }
+
+    /// Next frame descriptor set allocator getting function
+    /// Sets allocated from it are valid until the next frame rendered in the same frame slot,
+    /// so they should be rewritten every frame (e.g. camera data sets).
+    /// May wait for the frame slot to become available.
+    pub fn frame_descriptors(&mut self) -> Result<&mut DescriptorAllocator, RenderFrameError> {
+        Ok(&mut self.frames.current_mut()?.descriptors)
+    }
+
+    /// Count of frames in flight getting function
+    pub fn frames_in_flight(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Count of frames in flight setting function
+    /// Waits for all submitted frames to complete.
+    pub fn set_frames_in_flight(&mut self, count: usize) -> Result<(), RenderFrameError> {
+        unsafe { self.kernel.device.device_wait_idle() }.context("vkDeviceWaitIdle")?;
+
+        self.frames = FrameRing::new(self.kernel.clone(), count)?;
+
+        Ok(())
+    }
+
+    /// Deferred destruction function
+    /// Object (e.g. `Buffer` or `Image`) is dropped after all frames submitted so far are complete,
+    /// so resources still used by GPU may be replaced mid-frame.
+    pub fn defer_destroy<T: Send + 'static>(&mut self, object: T) {
+        self.frames.defer(Box::new(object));
+    }
+
+    /// Render target resize function
+    /// Swapchain is recreated lazily, during next `render_frame` call.
+    pub fn resize(&mut self, extent: Ext2<u32>) {
+        self.extent = vk::Extent2D {
+            width: extent.w,
+            height: extent.h,
+        };
+        self.swapchain_outdated = true;
+    }
+
+    /// Swapchain configuration setting function
+    /// Swapchain is recreated with new configuration during next `render_frame` call.
+    pub fn set_swapchain_config(&mut self, config: SwapchainConfig) {
+        self.swapchain.set_config(config);
+        self.swapchain_outdated = true;
+    }
+
+    /// Swapchain recreation function
+    /// Returns true if swapchain is ready for rendering.
+    fn recreate_swapchain(&mut self) -> Result<bool, RenderFrameError> {
+        if self.extent.width == 0 || self.extent.height == 0 {
+            return Ok(false);
+        }
+
+        // Swapchain images may still be used by frames in flight
+        unsafe { self.kernel.device.device_wait_idle() }.context("vkDeviceWaitIdle")?;
+
+        let recreated = self.swapchain.recreate(self.extent)?;
+
+        // Transient images usually depend on swapchain extent, so they are recreated on demand
+        self.transients.clear();
+
+        self.swapchain_outdated = !recreated;
+
+        Ok(recreated)
+    }
+
+    /// Changed shaders reloading function
+    /// Replaced pipelines are destroyed after frames in flight are complete.
+    #[cfg(feature = "hot-reload")]
+    fn reload_shaders(&mut self) {
+        let Some(watcher) = &mut self.shader_watcher else {
+            return;
+        };
+
+        let shaders = watcher.poll();
+        if shaders.is_empty() {
+            return;
+        }
+
+        for pipeline in self.pipelines.reload_shaders(shaders) {
+            self.frames.defer(Box::new(pipeline));
+        }
+    }
+
+    /// Frame rendering function
+    /// Frame is silently skipped if there is no surface to render to (e.g. window is minimized).
+    pub fn render_frame(&mut self) -> Result<(), RenderFrameError> {
+        // Uploads are submitted to the main queue before frame commands, so the frame sees their results
+        self.uploader.flush()?;
+
+        #[cfg(feature = "hot-reload")]
+        self.reload_shaders();
+
+        if self.swapchain_outdated && !self.recreate_swapchain()? {
+            return Ok(());
+        }
+
+        self.frames.wait()?;
+
+        let device = &self.kernel.device;
+        let frame = self.frames.current();
+
+        unsafe {
+            let acquire_result = self.swapchain.acquire_next_image(frame.image_available_semaphore);
+            let image_index = match acquire_result {
+                Ok((image_index, suboptimal)) => {
+                    // Suboptimal swapchain is still usable, so current frame is rendered anyway
+                    self.swapchain_outdated |= suboptimal;
+                    image_index
+                }
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                    self.swapchain_outdated = true;
+                    return Ok(());
+                }
+                Err(err) => return Err(VulkanCallError { call: "vkAcquireNextImageKHR", result: err }.into()),
+            };
+            let image = self.swapchain.images()[image_index as usize];
+
+            self.frames.reset()?;
+
+            device.begin_command_buffer(
+                frame.command_buffer,
+                &vk::CommandBufferBeginInfo::default()
+                    .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            ).context("vkBeginCommandBuffer")?;
+
+            let mut graph = RenderGraph::new();
+            let target = graph.import_image(
+                "swapchain image",
+                ImportedImage {
+                    image,
+                    view: self.swapchain.image_views()[image_index as usize],
+                    format: self.swapchain.format(),
+                    extent: Ext2::new(self.swapchain.extent().width, self.swapchain.extent().height),
+                    subresource_range: vk::ImageSubresourceRange::default()
+                        .aspect_mask(vk::ImageAspectFlags::COLOR)
+                        .level_count(1)
+                        .layer_count(1),
+                },
+                vk::ImageLayout::UNDEFINED,
+                Some(vk::ImageLayout::PRESENT_SRC_KHR),
+            );
+
+            graph.add_pass(
+                "clear",
+                |pass| {
+                    pass.image(target, ImageAccess::TransferDst);
+                },
+                |context| {
+                    context.device.cmd_clear_color_image(
+                        context.command_buffer,
+                        context.image(target),
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        &vk::ClearColorValue { float32: CLEAR_COLOR },
+                        &[context.subresource_range(target)],
+                    );
+                },
+            );
+
+            graph.execute(device, frame.command_buffer, &mut self.transients)?;
+
+            device
+                .end_command_buffer(frame.command_buffer)
+                .context("vkEndCommandBuffer")?;
+
+            // Presentation of the image may still wait for semaphore of the previous frame, so it's per image
+            let render_finished_semaphore = self.swapchain.render_finished_semaphore(image_index);
+            device.queue_submit(
+                self.kernel.main_queue,
+                &[vk::SubmitInfo::default()
+                    .wait_semaphores(&[frame.image_available_semaphore])
+                    .wait_dst_stage_mask(&[vk::PipelineStageFlags::TRANSFER])
+                    .command_buffers(&[frame.command_buffer])
+                    .signal_semaphores(&[render_finished_semaphore])],
+                frame.in_flight_fence,
+            ).context("vkQueueSubmit")?;
+
+            let present_result = self
+                .swapchain
+                .present(image_index, render_finished_semaphore);
+
+            match present_result {
+                Ok(suboptimal) => self.swapchain_outdated |= suboptimal,
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.swapchain_outdated = true,
+                Err(err) => return Err(VulkanCallError { call: "vkQueuePresentKHR", result: err }.into()),
+            }
+        }
+
+        self.frames.advance();
+
+        // Evicted images may still be used by frames in flight, so they're destroyed after the just submitted one
+        for image in self.transients.end_frame() {
+            self.frames.defer(Box::new(image));
+        }
+
+        Ok(())
+    }
+}
 
-        Ok(Self { kernel })
+impl Drop for Render {
+    fn drop(&mut self) {
+        // Deferred objects and frame resources are destroyed by field drops
+        unsafe {
+            _ = self.kernel.device.device_wait_idle();
+        }
     }
 }
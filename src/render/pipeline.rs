@@ -0,0 +1,541 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use super::{
+    error::{VulkanCallError, VulkanResultExt},
+    kernel::Kernel,
+    shader::{ShaderError, ShaderModule},
+};
+
+/// Vertex attribute type
+pub trait VertexFormat {
+    const FORMAT: vk::Format;
+}
+
+macro_rules! impl_vertex_format {
+    ($($type: ty => $format: ident),* $(,)?) => {
+        $(
+            impl VertexFormat for $type {
+                const FORMAT: vk::Format = vk::Format::$format;
+            }
+        )*
+    };
+}
+
+impl_vertex_format!(
+    f32 => R32_SFLOAT,
+    [f32; 2] => R32G32_SFLOAT,
+    [f32; 3] => R32G32B32_SFLOAT,
+    [f32; 4] => R32G32B32A32_SFLOAT,
+    u32 => R32_UINT,
+    [u32; 2] => R32G32_UINT,
+    [u32; 3] => R32G32B32_UINT,
+    [u32; 4] => R32G32B32A32_UINT,
+    i32 => R32_SINT,
+    [i32; 2] => R32G32_SINT,
+    [i32; 3] => R32G32B32_SINT,
+    [i32; 4] => R32G32B32A32_SINT,
+    [u8; 4] => R8G8B8A8_UNORM,
+    [i8; 4] => R8G8B8A8_SNORM,
+    [u16; 2] => R16G16_UNORM,
+    [u16; 4] => R16G16B16A16_UNORM,
+);
+
+/// Vertex attribute description
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub format: vk::Format,
+    /// Offset in vertex
+    pub offset: u32,
+}
+
+/// Vertex structure trait
+/// Should be implemented by `impl_vertex!` macro.
+pub trait Vertex: bytemuck::Pod {
+    /// Vertex attributes getting function, attribute index is its shader location offset
+    fn attributes() -> Vec<VertexAttribute>;
+}
+
+/// Vertex field format getting function, used by `impl_vertex!`
+pub fn vertex_field_format<V, F: VertexFormat>(_field: impl Fn(&V) -> &F) -> vk::Format {
+    F::FORMAT
+}
+
+/// Vertex trait implementation macro
+/// Attributes get consecutive locations in field listing order.
+/// ```ignore
+/// impl_vertex!(MeshVertex { position, normal, uv });
+/// ```
+#[macro_export]
+macro_rules! impl_vertex {
+    ($struct_name: ty { $($field: ident),* $(,)? }) => {
+        impl $crate::render::Vertex for $struct_name {
+            fn attributes() -> Vec<$crate::render::VertexAttribute> {
+                vec![$(
+                    $crate::render::VertexAttribute {
+                        format: $crate::render::vertex_field_format(|vertex: &$struct_name| &vertex.$field),
+                        offset: std::mem::offset_of!($struct_name, $field) as u32,
+                    }
+                ),*]
+            }
+        }
+    };
+}
+
+/// Color blending mode
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Blending is disabled
+    #[default]
+    Opaque,
+    /// Non-premultiplied alpha blending
+    Alpha,
+    /// Premultiplied alpha blending
+    Premultiplied,
+    Additive,
+}
+
+impl BlendMode {
+    fn attachment_state(self) -> vk::PipelineColorBlendAttachmentState {
+        let state = vk::PipelineColorBlendAttachmentState::default()
+            .color_write_mask(vk::ColorComponentFlags::RGBA)
+            .color_blend_op(vk::BlendOp::ADD)
+            .alpha_blend_op(vk::BlendOp::ADD);
+
+        let (src_color, dst_color, src_alpha, dst_alpha) = match self {
+            Self::Opaque => return state.blend_enable(false),
+            Self::Alpha => (
+                vk::BlendFactor::SRC_ALPHA,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+            Self::Premultiplied => (
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+            ),
+            Self::Additive => (
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE,
+                vk::BlendFactor::ONE,
+            ),
+        };
+
+        state
+            .blend_enable(true)
+            .src_color_blend_factor(src_color)
+            .dst_color_blend_factor(dst_color)
+            .src_alpha_blend_factor(src_alpha)
+            .dst_alpha_blend_factor(dst_alpha)
+    }
+}
+
+/// Pipeline creation error
+#[derive(Clone, Debug)]
+pub enum PipelineError {
+    VulkanError(VulkanCallError),
+    ShaderError(ShaderError),
+    /// Pipeline lacks required shader stage
+    MissingStage(vk::ShaderStageFlags),
+    /// Viewport and scissor are neither static nor dynamic
+    MissingViewport,
+}
+
+impl std::fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
+            Self::ShaderError(err) => f.write_fmt(format_args!("shader error: {err}")),
+            Self::MissingStage(stage) => f.write_fmt(format_args!("missing {stage:?} shader stage")),
+            Self::MissingViewport => f.write_str("viewport and scissor must be either set or dynamic"),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::VulkanError(err) => Some(err),
+            Self::ShaderError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<VulkanCallError> for PipelineError {
+    fn from(value: VulkanCallError) -> Self {
+        Self::VulkanError(value)
+    }
+}
+
+impl From<ShaderError> for PipelineError {
+    fn from(value: ShaderError) -> Self {
+        Self::ShaderError(value)
+    }
+}
+
+/// Graphics pipeline with its layout
+/// Pipeline and layout are destroyed on drop.
+pub struct GraphicsPipeline {
+    kernel: Arc<Kernel>,
+    layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+impl GraphicsPipeline {
+    /// Pipeline handle getting function
+    pub fn handle(&self) -> vk::Pipeline {
+        self.pipeline
+    }
+
+    /// Pipeline layout getting function
+    pub fn layout(&self) -> vk::PipelineLayout {
+        self.layout
+    }
+}
+
+impl Drop for GraphicsPipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.kernel.device.destroy_pipeline(self.pipeline, None);
+            self.kernel.device.destroy_pipeline_layout(self.layout, None);
+        }
+    }
+}
+
+/// Graphics pipeline builder
+/// By default pipeline has triangle list topology, back face culling, no depth test,
+/// one opaque color attachment and dynamic viewport and scissor.
+/// # Note
+/// Non-default features (e.g. wide lines or non-solid fill) must be enabled by `DeviceRequirements`.
+pub struct GraphicsPipelineBuilder<'a> {
+    render_pass: vk::RenderPass,
+    subpass: u32,
+    stages: Vec<(vk::ShaderStageFlags, &'a ShaderModule)>,
+    vertex_bindings: Vec<vk::VertexInputBindingDescription>,
+    vertex_attributes: Vec<vk::VertexInputAttributeDescription>,
+    topology: vk::PrimitiveTopology,
+    primitive_restart: bool,
+    polygon_mode: vk::PolygonMode,
+    cull_mode: vk::CullModeFlags,
+    front_face: vk::FrontFace,
+    line_width: f32,
+    depth_bias: Option<(f32, f32, f32)>,
+    samples: vk::SampleCountFlags,
+    depth_test: Option<vk::CompareOp>,
+    depth_write: bool,
+    stencil: Option<(vk::StencilOpState, vk::StencilOpState)>,
+    blend_attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    dynamic_states: Vec<vk::DynamicState>,
+    viewport: Option<(vk::Viewport, vk::Rect2D)>,
+    push_constant_ranges: Vec<vk::PushConstantRange>,
+    set_layouts: Vec<vk::DescriptorSetLayout>,
+}
+
+impl<'a> GraphicsPipelineBuilder<'a> {
+    /// Builder of pipeline for render pass subpass create function
+    pub fn new(render_pass: vk::RenderPass, subpass: u32) -> Self {
+        Self {
+            render_pass,
+            subpass,
+            stages: Vec::new(),
+            vertex_bindings: Vec::new(),
+            vertex_attributes: Vec::new(),
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            primitive_restart: false,
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::BACK,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            line_width: 1.0,
+            depth_bias: None,
+            samples: vk::SampleCountFlags::TYPE_1,
+            depth_test: None,
+            depth_write: false,
+            stencil: None,
+            blend_attachments: vec![BlendMode::Opaque.attachment_state()],
+            dynamic_states: vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR],
+            viewport: None,
+            push_constant_ranges: Vec::new(),
+            set_layouts: Vec::new(),
+        }
+    }
+
+    /// Shader stage adding function
+    pub fn stage(mut self, stage: vk::ShaderStageFlags, module: &'a ShaderModule) -> Self {
+        self.stages.push((stage, module));
+        self
+    }
+
+    /// Vertex shader setting function
+    pub fn vertex_shader(self, module: &'a ShaderModule) -> Self {
+        self.stage(vk::ShaderStageFlags::VERTEX, module)
+    }
+
+    /// Fragment shader setting function
+    pub fn fragment_shader(self, module: &'a ShaderModule) -> Self {
+        self.stage(vk::ShaderStageFlags::FRAGMENT, module)
+    }
+
+    /// Vertex buffer binding adding function
+    /// Binding index is the count of previously added bindings, attributes get locations
+    /// after attributes of previous bindings.
+    pub fn vertex_binding<V: Vertex>(mut self, input_rate: vk::VertexInputRate) -> Self {
+        let binding = self.vertex_bindings.len() as u32;
+
+        self.vertex_bindings.push(vk::VertexInputBindingDescription {
+            binding,
+            stride: std::mem::size_of::<V>() as u32,
+            input_rate,
+        });
+
+        for attribute in V::attributes() {
+            self.vertex_attributes.push(vk::VertexInputAttributeDescription {
+                location: self.vertex_attributes.len() as u32,
+                binding,
+                format: attribute.format,
+                offset: attribute.offset,
+            });
+        }
+
+        self
+    }
+
+    /// Primitive topology setting function
+    pub fn topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Primitive restart enabling function
+    pub fn primitive_restart(mut self, primitive_restart: bool) -> Self {
+        self.primitive_restart = primitive_restart;
+        self
+    }
+
+    /// Polygon mode setting function
+    pub fn polygon_mode(mut self, polygon_mode: vk::PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    /// Cull mode setting function
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    /// Front face winding setting function
+    pub fn front_face(mut self, front_face: vk::FrontFace) -> Self {
+        self.front_face = front_face;
+        self
+    }
+
+    /// Line width setting function
+    pub fn line_width(mut self, line_width: f32) -> Self {
+        self.line_width = line_width;
+        self
+    }
+
+    /// Depth bias (constant factor, clamp, slope factor) setting function
+    pub fn depth_bias(mut self, constant_factor: f32, clamp: f32, slope_factor: f32) -> Self {
+        self.depth_bias = Some((constant_factor, clamp, slope_factor));
+        self
+    }
+
+    /// Rasterization sample count setting function
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    /// Depth test setting function
+    pub fn depth_test(mut self, compare_op: vk::CompareOp, write: bool) -> Self {
+        self.depth_test = Some(compare_op);
+        self.depth_write = write;
+        self
+    }
+
+    /// Stencil test (front and back face operations) setting function
+    pub fn stencil(mut self, front: vk::StencilOpState, back: vk::StencilOpState) -> Self {
+        self.stencil = Some((front, back));
+        self
+    }
+
+    /// Color attachments count setting function
+    /// Blending is disabled for all attachments.
+    pub fn color_attachments(mut self, count: usize) -> Self {
+        self.blend_attachments = vec![BlendMode::Opaque.attachment_state(); count];
+        self
+    }
+
+    /// Color attachment blend mode setting function
+    pub fn blend(self, attachment: usize, mode: BlendMode) -> Self {
+        self.blend_state(attachment, mode.attachment_state())
+    }
+
+    /// Color attachment blend state setting function
+    pub fn blend_state(mut self, attachment: usize, state: vk::PipelineColorBlendAttachmentState) -> Self {
+        if attachment >= self.blend_attachments.len() {
+            self.blend_attachments
+                .resize(attachment + 1, BlendMode::Opaque.attachment_state());
+        }
+        self.blend_attachments[attachment] = state;
+        self
+    }
+
+    /// Dynamic state adding function
+    pub fn dynamic_state(mut self, state: vk::DynamicState) -> Self {
+        if !self.dynamic_states.contains(&state) {
+            self.dynamic_states.push(state);
+        }
+        self
+    }
+
+    /// Static viewport and scissor setting function
+    /// Viewport and scissor are removed from dynamic states.
+    pub fn viewport(mut self, viewport: vk::Viewport, scissor: vk::Rect2D) -> Self {
+        self.viewport = Some((viewport, scissor));
+        self.dynamic_states
+            .retain(|state| *state != vk::DynamicState::VIEWPORT && *state != vk::DynamicState::SCISSOR);
+        self
+    }
+
+    /// Push constant range adding function
+    pub fn push_constant_range(mut self, stages: vk::ShaderStageFlags, offset: u32, size: u32) -> Self {
+        self.push_constant_ranges.push(vk::PushConstantRange {
+            stage_flags: stages,
+            offset,
+            size,
+        });
+        self
+    }
+
+    /// Descriptor set layout adding function, set index is the count of previously added layouts
+    pub fn descriptor_set_layout(mut self, layout: vk::DescriptorSetLayout) -> Self {
+        self.set_layouts.push(layout);
+        self
+    }
+
+    /// Pipeline building function
+    pub fn build(self, kernel: &Arc<Kernel>, cache: vk::PipelineCache) -> Result<GraphicsPipeline, PipelineError> {
+        for required in [vk::ShaderStageFlags::VERTEX, vk::ShaderStageFlags::FRAGMENT] {
+            if !self.stages.iter().any(|(stage, _)| *stage == required) {
+                return Err(PipelineError::MissingStage(required));
+            }
+        }
+
+        let dynamic_viewport = self.dynamic_states.contains(&vk::DynamicState::VIEWPORT)
+            && self.dynamic_states.contains(&vk::DynamicState::SCISSOR);
+        if self.viewport.is_none() && !dynamic_viewport {
+            return Err(PipelineError::MissingViewport);
+        }
+
+        let device = &kernel.device;
+
+        let layout = unsafe {
+            device.create_pipeline_layout(
+                &vk::PipelineLayoutCreateInfo::default()
+                    .set_layouts(&self.set_layouts)
+                    .push_constant_ranges(&self.push_constant_ranges),
+                None,
+            )
+        }
+        .context("vkCreatePipelineLayout")?;
+
+        let stages = self
+            .stages
+            .iter()
+            .map(|(stage, module)| {
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(*stage)
+                    .module(module.handle())
+                    .name(module.entry_point_name())
+            })
+            .collect::<Vec<_>>();
+
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(&self.vertex_bindings)
+            .vertex_attribute_descriptions(&self.vertex_attributes);
+
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::default()
+            .topology(self.topology)
+            .primitive_restart_enable(self.primitive_restart);
+
+        let viewports = self.viewport.map(|(viewport, _)| [viewport]);
+        let scissors = self.viewport.map(|(_, scissor)| [scissor]);
+        let viewport_state = match (&viewports, &scissors) {
+            (Some(viewports), Some(scissors)) => vk::PipelineViewportStateCreateInfo::default()
+                .viewports(viewports)
+                .scissors(scissors),
+            _ => vk::PipelineViewportStateCreateInfo::default()
+                .viewport_count(1)
+                .scissor_count(1),
+        };
+
+        let mut rasterization_state = vk::PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(self.polygon_mode)
+            .cull_mode(self.cull_mode)
+            .front_face(self.front_face)
+            .line_width(self.line_width);
+        if let Some((constant_factor, clamp, slope_factor)) = self.depth_bias {
+            rasterization_state = rasterization_state
+                .depth_bias_enable(true)
+                .depth_bias_constant_factor(constant_factor)
+                .depth_bias_clamp(clamp)
+                .depth_bias_slope_factor(slope_factor);
+        }
+
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::default()
+            .rasterization_samples(self.samples);
+
+        let mut depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::default()
+            .depth_test_enable(self.depth_test.is_some())
+            .depth_write_enable(self.depth_write)
+            .depth_compare_op(self.depth_test.unwrap_or(vk::CompareOp::ALWAYS))
+            .max_depth_bounds(1.0);
+        if let Some((front, back)) = self.stencil {
+            depth_stencil_state = depth_stencil_state
+                .stencil_test_enable(true)
+                .front(front)
+                .back(back);
+        }
+
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::default()
+            .attachments(&self.blend_attachments);
+
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::default()
+            .dynamic_states(&self.dynamic_states);
+
+        let create_info = vk::GraphicsPipelineCreateInfo::default()
+            .stages(&stages)
+            .vertex_input_state(&vertex_input_state)
+            .input_assembly_state(&input_assembly_state)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state)
+            .layout(layout)
+            .render_pass(self.render_pass)
+            .subpass(self.subpass);
+
+        let pipeline = match unsafe { device.create_graphics_pipelines(cache, &[create_info], None) } {
+            Ok(pipelines) => pipelines[0],
+            Err((_, err)) => {
+                unsafe { device.destroy_pipeline_layout(layout, None) };
+                return Err(VulkanCallError { call: "vkCreateGraphicsPipelines", result: err }.into());
+            }
+        };
+
+        Ok(GraphicsPipeline {
+            kernel: kernel.clone(),
+            layout,
+            pipeline,
+        })
+    }
+}
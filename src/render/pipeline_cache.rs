@@ -0,0 +1,223 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use ash::vk;
+
+use super::error::VulkanCallError;
+
+/// Environment variable that overrides pipeline cache directory
+pub const PIPELINE_CACHE_DIR_ENV_VAR: &str = "WAT3RS_CACHE_DIR";
+
+/// Size of `VK_PIPELINE_CACHE_HEADER_VERSION_ONE` header
+const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+/// Pipeline cache saving error
+#[derive(Clone, Debug)]
+pub enum PipelineCacheError {
+    VulkanError(VulkanCallError),
+    IoError {
+        path: PathBuf,
+        error: Arc<std::io::Error>,
+    },
+}
+
+impl std::fmt::Display for PipelineCacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
+            Self::IoError { path, error } => {
+                f.write_fmt(format_args!("failed to write {}: {error}", path.display()))
+            }
+        }
+    }
+}
+
+impl std::error::Error for PipelineCacheError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::VulkanError(err) => Some(err),
+            Self::IoError { error, .. } => Some(error.as_ref()),
+        }
+    }
+}
+
+impl From<VulkanCallError> for PipelineCacheError {
+    fn from(value: VulkanCallError) -> Self {
+        Self::VulkanError(value)
+    }
+}
+
+/// Default pipeline cache directory getting function
+/// `WAT3RS_CACHE_DIR` environment variable is used if set, platform user cache directory otherwise.
+pub fn default_pipeline_cache_dir() -> Option<PathBuf> {
+    let non_empty = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+
+    if let Some(dir) = non_empty(PIPELINE_CACHE_DIR_ENV_VAR) {
+        return Some(dir);
+    }
+
+    let user_cache_dir = if cfg!(windows) {
+        non_empty("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        non_empty("HOME").map(|home| home.join("Library/Caches"))
+    } else {
+        non_empty("XDG_CACHE_HOME").or_else(|| non_empty("HOME").map(|home| home.join(".cache")))
+    };
+
+    user_cache_dir.map(|dir| dir.join("wat3rs"))
+}
+
+/// Pipeline cache file path getting function
+/// Each physical device has its own file, so switching devices doesn't discard caches.
+pub(super) fn pipeline_cache_path(dir: &Path, properties: &vk::PhysicalDeviceProperties) -> PathBuf {
+    dir.join(format!(
+        "pipeline_cache_{:04x}_{:04x}.bin",
+        properties.vendor_id, properties.device_id
+    ))
+}
+
+/// Pipeline cache data header validation function
+/// Data is valid if it's created by the same driver for the same device.
+pub(super) fn validate_header(data: &[u8], properties: &vk::PhysicalDeviceProperties) -> Result<(), &'static str> {
+    if data.len() < HEADER_SIZE {
+        return Err("data is shorter than header");
+    }
+
+    // Header fields are little-endian regardless of host byte order
+    let field = |index: usize| u32::from_le_bytes(data[index * 4..index * 4 + 4].try_into().unwrap());
+
+    let header_size = field(0);
+    if (header_size as usize) < HEADER_SIZE || header_size as usize > data.len() {
+        return Err("invalid header size");
+    }
+    if vk::PipelineCacheHeaderVersion::from_raw(field(1) as i32) != vk::PipelineCacheHeaderVersion::ONE {
+        return Err("unsupported header version");
+    }
+    if field(2) != properties.vendor_id {
+        return Err("vendor ID mismatch");
+    }
+    if field(3) != properties.device_id {
+        return Err("device ID mismatch");
+    }
+    if data[16..HEADER_SIZE] != properties.pipeline_cache_uuid {
+        return Err("pipeline cache UUID mismatch");
+    }
+
+    Ok(())
+}
+
+/// Pipeline cache data loading function
+/// Returns empty data if file doesn't exist or is created for another device or driver.
+pub(super) fn load(path: &Path, properties: &vk::PhysicalDeviceProperties) -> Vec<u8> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(err) => {
+            log::warn!("failed to read pipeline cache {}: {err}", path.display());
+            return Vec::new();
+        }
+    };
+
+    match validate_header(&data, properties) {
+        Ok(()) => data,
+        Err(reason) => {
+            log::info!("pipeline cache {} is discarded: {reason}", path.display());
+            Vec::new()
+        }
+    }
+}
+
+/// Pipeline cache data storing function
+/// Data is written to temporary file first, so interrupted write doesn't corrupt existing cache.
+pub(super) fn store(path: &Path, data: &[u8]) -> Result<(), PipelineCacheError> {
+    let write = || -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let temporary_path = path.with_extension("tmp");
+        std::fs::write(&temporary_path, data)?;
+        std::fs::rename(&temporary_path, path)
+    };
+
+    write().map_err(|error| PipelineCacheError::IoError {
+        path: path.to_path_buf(),
+        error: Arc::new(error),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties() -> vk::PhysicalDeviceProperties {
+        vk::PhysicalDeviceProperties {
+            vendor_id: 0x10de,
+            device_id: 0x2684,
+            pipeline_cache_uuid: std::array::from_fn(|index| index as u8 + 1),
+            ..Default::default()
+        }
+    }
+
+    /// Header with trailing cache data building function
+    fn header(properties: &vk::PhysicalDeviceProperties) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        data.extend_from_slice(&(vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32).to_le_bytes());
+        data.extend_from_slice(&properties.vendor_id.to_le_bytes());
+        data.extend_from_slice(&properties.device_id.to_le_bytes());
+        data.extend_from_slice(&properties.pipeline_cache_uuid);
+        data.extend_from_slice(&[0xAB; 64]);
+        data
+    }
+
+    #[test]
+    fn valid_header() {
+        let properties = properties();
+        let data = header(&properties);
+
+        assert_eq!(validate_header(&data, &properties), Ok(()));
+        assert_eq!(validate_header(&data[..HEADER_SIZE], &properties), Ok(()));
+        // Header is little-endian on any host
+        assert_eq!(data[0..4], [32, 0, 0, 0]);
+    }
+
+    #[test]
+    fn mismatched_header() {
+        let properties = properties();
+        let data = header(&properties);
+
+        let other_vendor = vk::PhysicalDeviceProperties { vendor_id: 0x1002, ..properties };
+        assert_eq!(validate_header(&data, &other_vendor), Err("vendor ID mismatch"));
+
+        let other_device = vk::PhysicalDeviceProperties { device_id: 0x2685, ..properties };
+        assert_eq!(validate_header(&data, &other_device), Err("device ID mismatch"));
+
+        let mut other_uuid = properties;
+        other_uuid.pipeline_cache_uuid[vk::UUID_SIZE - 1] ^= 1;
+        assert_eq!(validate_header(&data, &other_uuid), Err("pipeline cache UUID mismatch"));
+
+        let mut other_version = data.clone();
+        other_version[4..8].copy_from_slice(&2u32.to_le_bytes());
+        assert_eq!(validate_header(&other_version, &properties), Err("unsupported header version"));
+    }
+
+    #[test]
+    fn truncated_header() {
+        let properties = properties();
+        let data = header(&properties);
+
+        assert_eq!(validate_header(&[], &properties), Err("data is shorter than header"));
+        assert_eq!(validate_header(&data[..HEADER_SIZE - 1], &properties), Err("data is shorter than header"));
+
+        let mut oversized = data.clone();
+        oversized[0..4].copy_from_slice(&(data.len() as u32 + 1).to_le_bytes());
+        assert_eq!(validate_header(&oversized, &properties), Err("invalid header size"));
+
+        let mut undersized = data;
+        undersized[0..4].copy_from_slice(&16u32.to_le_bytes());
+        assert_eq!(validate_header(&undersized, &properties), Err("invalid header size"));
+    }
+}
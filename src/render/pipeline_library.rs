@@ -0,0 +1,147 @@
+use std::{collections::HashMap, sync::Arc};
+
+use ash::vk;
+
+use super::{
+    kernel::Kernel,
+    pipeline::{GraphicsPipeline, GraphicsPipelineBuilder, PipelineError},
+    shader::{ShaderError, ShaderModule},
+};
+
+/// Pipeline builder configuring function, applied after shader stages are set
+type ConfigureFn = dyn for<'a> Fn(GraphicsPipelineBuilder<'a>) -> GraphicsPipelineBuilder<'a>;
+
+/// Library pipeline identifier
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineId(usize);
+
+/// Pipeline with description required to rebuild it
+#[cfg_attr(not(feature = "hot-reload"), allow(dead_code))]
+struct LibraryPipeline {
+    render_pass: vk::RenderPass,
+    subpass: u32,
+    stages: Vec<(vk::ShaderStageFlags, String)>,
+    configure: Box<ConfigureFn>,
+    pipeline: GraphicsPipeline,
+}
+
+/// Library of pipelines built from embedded shaders
+/// Pipelines are kept with their descriptions, so they may be rebuilt when shaders change.
+pub struct PipelineLibrary {
+    kernel: Arc<Kernel>,
+    shaders: HashMap<String, ShaderModule>,
+    pipelines: Vec<LibraryPipeline>,
+}
+
+impl PipelineLibrary {
+    pub fn new(kernel: Arc<Kernel>) -> Self {
+        Self {
+            kernel,
+            shaders: HashMap::new(),
+            pipelines: Vec::new(),
+        }
+    }
+
+    /// Shader module loading function, module is created from embedded shader on first use
+    pub fn shader(&mut self, name: &str) -> Result<&ShaderModule, ShaderError> {
+        if !self.shaders.contains_key(name) {
+            let module = ShaderModule::embedded(&self.kernel, name)?;
+            self.shaders.insert(name.to_string(), module);
+        }
+
+        Ok(&self.shaders[name])
+    }
+
+    /// Pipeline building function
+    /// All pipeline shaders must be loaded.
+    fn build(
+        &self,
+        render_pass: vk::RenderPass,
+        subpass: u32,
+        stages: &[(vk::ShaderStageFlags, String)],
+        configure: &ConfigureFn,
+    ) -> Result<GraphicsPipeline, PipelineError> {
+        let mut builder = GraphicsPipelineBuilder::new(render_pass, subpass);
+        for (stage, name) in stages {
+            builder = builder.stage(*stage, &self.shaders[name]);
+        }
+
+        configure(builder).build(&self.kernel, self.kernel.pipeline_cache)
+    }
+
+    /// Graphics pipeline create function
+    /// `stages` are (stage, embedded shader name) pairs, other pipeline state is set by `configure`.
+    pub fn create_graphics_pipeline(
+        &mut self,
+        render_pass: vk::RenderPass,
+        subpass: u32,
+        stages: &[(vk::ShaderStageFlags, &str)],
+        configure: impl for<'a> Fn(GraphicsPipelineBuilder<'a>) -> GraphicsPipelineBuilder<'a> + 'static,
+    ) -> Result<PipelineId, PipelineError> {
+        for (_, name) in stages {
+            self.shader(name)?;
+        }
+
+        let stages = stages
+            .iter()
+            .map(|(stage, name)| (*stage, name.to_string()))
+            .collect::<Vec<_>>();
+        let configure = Box::new(configure);
+        let pipeline = self.build(render_pass, subpass, &stages, configure.as_ref())?;
+
+        self.pipelines.push(LibraryPipeline {
+            render_pass,
+            subpass,
+            stages,
+            configure,
+            pipeline,
+        });
+
+        Ok(PipelineId(self.pipelines.len() - 1))
+    }
+
+    /// Pipeline getting function
+    pub fn pipeline(&self, id: PipelineId) -> &GraphicsPipeline {
+        &self.pipelines[id.0].pipeline
+    }
+
+    /// Shader replacing and dependent pipelines rebuilding function
+    /// Pipelines failed to rebuild keep their previous version, errors are logged.
+    /// Returns replaced pipelines that may still be used by frames in flight.
+    #[cfg(feature = "hot-reload")]
+    pub(super) fn reload_shaders(
+        &mut self,
+        shaders: Vec<super::shader_compiler::CompiledShader>,
+    ) -> Vec<GraphicsPipeline> {
+        let mut reloaded = Vec::new();
+
+        for shader in shaders {
+            match ShaderModule::from_words(&self.kernel, &shader.words) {
+                Ok(module) => {
+                    log::info!("shader \"{}\" reloaded", shader.name);
+                    self.shaders.insert(shader.name.clone(), module);
+                    reloaded.push(shader.name);
+                }
+                Err(err) => log::error!("failed to reload shader \"{}\": {err}", shader.name),
+            }
+        }
+
+        let mut replaced = Vec::new();
+
+        for index in 0..self.pipelines.len() {
+            let entry = &self.pipelines[index];
+            if !entry.stages.iter().any(|(_, name)| reloaded.contains(name)) {
+                continue;
+            }
+
+            match self.build(entry.render_pass, entry.subpass, &entry.stages, entry.configure.as_ref()) {
+                Ok(pipeline) => {
+                    replaced.push(std::mem::replace(&mut self.pipelines[index].pipeline, pipeline));
+                }
+                Err(err) => log::error!("failed to rebuild pipeline, previous one is kept: {err}"),
+            }
+        }
+
+        replaced
+    }
+}
@@ -1,36 +1,70 @@
 use ash::{khr, vk};
 
+/// Queue family indices
+/// Dedicated transfer and compute families are optional. If one of them is not
+/// available, the corresponding work is submitted to the main queue.
 pub struct QueueFamilyIndices {
+    /// Graphics, transfer and compute family index
     pub main: u32,
-    pub present: u32,
+    /// Presentation family index, `None` for headless kernels
+    pub present: Option<u32>,
+    /// Dedicated transfer family (without graphics support) index
+    pub transfer: Option<u32>,
+    /// Dedicated compute family (without graphics support) index
+    pub compute: Option<u32>,
 }
 
 impl QueueFamilyIndices {
+    /// Queue family indices finding function
+    /// Presentation support is required only if surface is passed.
     pub fn new(
         physical_device: vk::PhysicalDevice,
-        surface: vk::SurfaceKHR,
         instance: &ash::Instance,
-        surface_instance: &khr::surface::Instance,
+        surface: Option<(vk::SurfaceKHR, &khr::surface::Instance)>,
     ) -> Option<Self> {
         let queue_families =
             unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
         let mut main_family_index = None;
-        let mut present_family_index = None;
+        let mut present_family_indices = Vec::new();
+        let mut transfer_family_index = None;
+        let mut compute_family_index = None;
 
         for (index, family) in queue_families
             .iter()
             .enumerate()
             .map(|(i, f)| (i as u32, f))
         {
+            if family.queue_count == 0 {
+                continue;
+            }
+
+            let flags = family.queue_flags;
+
             if main_family_index.is_none()
-                && family.queue_flags.contains(
+                && flags.contains(
                     vk::QueueFlags::GRAPHICS | vk::QueueFlags::TRANSFER | vk::QueueFlags::COMPUTE,
                 )
             {
                 main_family_index = Some(index);
-                continue;
             }
 
+            if !flags.contains(vk::QueueFlags::GRAPHICS) {
+                // Families without compute are preferred for transfers, as they usually map to DMA engines
+                if flags.contains(vk::QueueFlags::TRANSFER)
+                    && (transfer_family_index.is_none() || !flags.contains(vk::QueueFlags::COMPUTE))
+                {
+                    transfer_family_index = Some(index);
+                }
+
+                if compute_family_index.is_none() && flags.contains(vk::QueueFlags::COMPUTE) {
+                    compute_family_index = Some(index);
+                }
+            }
+
+            let Some((surface, surface_instance)) = surface else {
+                continue;
+            };
+
             if unsafe {
                 surface_instance.get_physical_device_surface_support(
                     physical_device,
@@ -40,17 +74,70 @@ impl QueueFamilyIndices {
             }
             .unwrap_or(false)
             {
-                present_family_index = Some(index);
+                present_family_indices.push(index);
             }
         }
 
-        if present_family_index.is_none() {
-            present_family_index = main_family_index;
+        let main = main_family_index?;
+
+        // Main family is preferred for presentation to avoid image ownership transfers
+        let present = match surface {
+            Some(_) => Some(if present_family_indices.contains(&main) {
+                main
+            } else {
+                *present_family_indices.first()?
+            }),
+            None => None,
+        };
+
+        // Compute family shouldn't be shared with transfer one if there is a choice
+        if compute_family_index.is_some() && compute_family_index == transfer_family_index {
+            let other_compute_family = queue_families
+                .iter()
+                .enumerate()
+                .map(|(i, f)| (i as u32, f))
+                .find(|(index, family)| {
+                    Some(*index) != transfer_family_index
+                        && family.queue_count > 0
+                        && family.queue_flags.contains(vk::QueueFlags::COMPUTE)
+                        && !family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+                });
+
+            if let Some((index, _)) = other_compute_family {
+                compute_family_index = Some(index);
+            }
         }
 
         Some(Self {
-            main: main_family_index?,
-            present: present_family_index?,
+            main,
+            present,
+            transfer: transfer_family_index,
+            compute: compute_family_index,
         })
     }
+
+    /// Transfer family index getting function
+    /// Returns main family index if there is no dedicated transfer family.
+    pub fn transfer_or_main(&self) -> u32 {
+        self.transfer.unwrap_or(self.main)
+    }
+
+    /// Compute family index getting function
+    /// Returns main family index if there is no dedicated compute family.
+    pub fn compute_or_main(&self) -> u32 {
+        self.compute.unwrap_or(self.main)
+    }
+
+    /// Unique used family indices getting function
+    pub fn unique(&self) -> Vec<u32> {
+        let mut indices = vec![self.main];
+
+        for index in [self.present, self.transfer, self.compute].into_iter().flatten() {
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+
+        indices
+    }
 }
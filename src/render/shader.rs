@@ -0,0 +1,183 @@
+use std::{
+    ffi::{CStr, CString},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use ash::vk;
+
+use super::{
+    error::{VulkanCallError, VulkanResultExt},
+    kernel::Kernel,
+};
+
+/// SPIR-V magic number
+pub const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// Shader loading error
+#[derive(Clone, Debug)]
+pub enum ShaderError {
+    VulkanError(VulkanCallError),
+    /// Shader file reading error
+    IoError {
+        path: PathBuf,
+        error: Arc<std::io::Error>,
+    },
+    /// Code is not a valid SPIR-V module
+    InvalidSpirv(&'static str),
+    /// No embedded shader with such name
+    NotFound(String),
+}
+
+impl std::fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
+            Self::IoError { path, error } => {
+                f.write_fmt(format_args!("failed to read {}: {error}", path.display()))
+            }
+            Self::InvalidSpirv(reason) => f.write_fmt(format_args!("invalid SPIR-V: {reason}")),
+            Self::NotFound(name) => f.write_fmt(format_args!("embedded shader \"{name}\" not found")),
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::VulkanError(err) => Some(err),
+            Self::IoError { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<VulkanCallError> for ShaderError {
+    fn from(value: VulkanCallError) -> Self {
+        Self::VulkanError(value)
+    }
+}
+
+/// Shaders compiled from `shaders/` directory by the build script
+mod embedded {
+    include!(concat!(env!("OUT_DIR"), "/shaders.rs"));
+}
+
+/// Embedded shader SPIR-V getting function
+/// Shader name is its path relative to `shaders/` directory (e.g. "mesh.vert"),
+/// permutations are named by `<shader>:<permutation>` (e.g. "mesh.frag:unlit").
+pub fn embedded_shader(name: &str) -> Option<&'static [u8]> {
+    embedded::SHADERS
+        .binary_search_by(|(shader_name, _)| (*shader_name).cmp(name))
+        .ok()
+        .map(|index| embedded::SHADERS[index].1)
+}
+
+/// Embedded shader names getting function
+pub fn embedded_shader_names() -> impl Iterator<Item = &'static str> {
+    embedded::SHADERS.iter().map(|(name, _)| *name)
+}
+
+/// SPIR-V words from bytes reading function
+/// Bytes may be unaligned and have any endianness, it's detected by the magic number.
+pub fn read_spirv(bytes: &[u8]) -> Result<Vec<u32>, ShaderError> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(ShaderError::InvalidSpirv("code size is not a multiple of 4"));
+    }
+    // Header consists of magic, version, generator, bound and schema words
+    if bytes.len() < 20 {
+        return Err(ShaderError::InvalidSpirv("code is shorter than SPIR-V header"));
+    }
+
+    let mut words = bytes
+        .chunks_exact(4)
+        .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect::<Vec<_>>();
+
+    if words[0] == SPIRV_MAGIC.swap_bytes() {
+        for word in &mut words {
+            *word = word.swap_bytes();
+        }
+    } else if words[0] != SPIRV_MAGIC {
+        return Err(ShaderError::InvalidSpirv("invalid magic number"));
+    }
+
+    Ok(words)
+}
+
+/// Shader module
+/// Module is destroyed on drop, so it may be dropped right after pipeline creation.
+pub struct ShaderModule {
+    kernel: Arc<Kernel>,
+    module: vk::ShaderModule,
+    entry_point: CString,
+}
+
+impl ShaderModule {
+    /// Shader module from SPIR-V words create function
+    pub fn from_words(kernel: &Arc<Kernel>, words: &[u32]) -> Result<Self, ShaderError> {
+        if words.len() < 5 {
+            return Err(ShaderError::InvalidSpirv("code is shorter than SPIR-V header"));
+        }
+        if words[0] != SPIRV_MAGIC {
+            return Err(ShaderError::InvalidSpirv("invalid magic number"));
+        }
+
+        let module = unsafe {
+            kernel
+                .device
+                .create_shader_module(&vk::ShaderModuleCreateInfo::default().code(words), None)
+        }
+        .context("vkCreateShaderModule")?;
+
+        Ok(Self {
+            kernel: kernel.clone(),
+            module,
+            entry_point: c"main".to_owned(),
+        })
+    }
+
+    /// Shader module from SPIR-V bytes create function
+    pub fn from_bytes(kernel: &Arc<Kernel>, bytes: &[u8]) -> Result<Self, ShaderError> {
+        Self::from_words(kernel, &read_spirv(bytes)?)
+    }
+
+    /// Shader module from SPIR-V file create function
+    pub fn from_file(kernel: &Arc<Kernel>, path: impl AsRef<Path>) -> Result<Self, ShaderError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|error| ShaderError::IoError {
+            path: path.to_path_buf(),
+            error: Arc::new(error),
+        })?;
+
+        Self::from_bytes(kernel, &bytes)
+    }
+
+    /// Shader module from embedded shader create function
+    pub fn embedded(kernel: &Arc<Kernel>, name: &str) -> Result<Self, ShaderError> {
+        let bytes = embedded_shader(name).ok_or_else(|| ShaderError::NotFound(name.to_string()))?;
+        Self::from_bytes(kernel, bytes)
+    }
+
+    /// Entry point name setting function, "main" is used by default
+    pub fn entry_point(mut self, entry_point: &CStr) -> Self {
+        self.entry_point = entry_point.to_owned();
+        self
+    }
+
+    /// Module handle getting function
+    pub fn handle(&self) -> vk::ShaderModule {
+        self.module
+    }
+
+    /// Entry point name getting function
+    pub fn entry_point_name(&self) -> &CStr {
+        &self.entry_point
+    }
+}
+
+impl Drop for ShaderModule {
+    fn drop(&mut self) {
+        unsafe { self.kernel.device.destroy_shader_module(self.module, None) };
+    }
+}
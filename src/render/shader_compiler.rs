@@ -0,0 +1,480 @@
+//! GLSL/HLSL to SPIR-V shader compiler
+//! Used by the build script to compile `shaders/` directory, so it must depend only on `std`, `naga`
+//! and (with `hlsl` feature) `shaderc`.
+//! # Note
+//! GLSL is compiled by naga. naga has no HLSL frontend, so HLSL is compiled by shaderc, which is
+//! built from source and requires CMake. `.hlsl` files fail the build if `hlsl` feature is disabled.
+//! HLSL file names have stage suffix (e.g. `mesh.vert.hlsl`) and `main` entry point.
+//! Only vertex, fragment and compute shaders are supported.
+
+use std::path::{Path, PathBuf};
+
+/// Extensions of files that are only included by other shaders
+pub const INCLUDE_EXTENSIONS: &[&str] = &["glsl", "hlsli"];
+
+/// Extension of HLSL shader files
+pub const HLSL_EXTENSION: &str = "hlsl";
+
+/// Shader compilation error
+#[derive(Clone, Debug)]
+pub struct CompileError {
+    pub path: PathBuf,
+    /// 1-based line number, 0 if error isn't bound to a line
+    pub line: u32,
+    /// 1-based column number, 0 if error isn't bound to a column
+    pub column: u32,
+    pub message: String,
+}
+
+impl CompileError {
+    fn new(path: &Path, line: u32, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            line,
+            column: 0,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}", self.path.display()))?;
+        if self.line != 0 {
+            f.write_fmt(format_args!(":{}", self.line))?;
+        }
+        if self.column != 0 {
+            f.write_fmt(format_args!(":{}", self.column))?;
+        }
+        f.write_fmt(format_args!(": {}", self.message))
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Compiled shader
+pub struct CompiledShader {
+    /// Shader name, path relative to the shader root with `:permutation` suffix for permutations
+    pub name: String,
+    pub words: Vec<u32>,
+}
+
+/// Result of a shader source file compilation
+pub struct CompiledSource {
+    /// Base shader and all of its permutations
+    pub shaders: Vec<CompiledShader>,
+    /// All files the shader is built from, including the source file itself
+    pub dependencies: Vec<PathBuf>,
+}
+
+/// HLSL source file checking function
+pub fn is_hlsl(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some(HLSL_EXTENSION)
+}
+
+/// Shader stage by file extension getting function
+/// Stage of HLSL file is defined by extension preceding `.hlsl` one.
+/// Returns `Ok(None)` for files that shouldn't be compiled (e.g. includes).
+pub fn shader_stage(path: &Path) -> Result<Option<naga::ShaderStage>, CompileError> {
+    let hlsl = is_hlsl(path);
+    let stage_path = if hlsl { Path::new(path.file_stem().unwrap_or_default()) } else { path };
+    let extension = stage_path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+
+    match extension {
+        "vert" => Ok(Some(naga::ShaderStage::Vertex)),
+        "frag" => Ok(Some(naga::ShaderStage::Fragment)),
+        "comp" => Ok(Some(naga::ShaderStage::Compute)),
+        "geom" | "tesc" | "tese" => Err(CompileError::new(
+            path,
+            0,
+            format!("'.{extension}' shader stage is not supported by the compiler"),
+        )),
+        _ if hlsl => Err(CompileError::new(
+            path,
+            0,
+            "HLSL shader file name must have stage suffix (.vert.hlsl, .frag.hlsl or .comp.hlsl)",
+        )),
+        _ => Ok(None),
+    }
+}
+
+/// Shader source files in directory recursive finding function
+/// Files are sorted by path, include files are skipped.
+pub fn find_shaders(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    fn visit(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.is_dir() {
+                visit(&path, files)?;
+            } else if !path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| INCLUDE_EXTENSIONS.contains(&ext))
+            {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    visit(root, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// Shader name (path relative to root with '/' separators) getting function
+pub fn shader_name(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Shader permutation, declared in source by a `// permutation: <name> <DEFINE>[=<VALUE>]...` line
+struct Permutation {
+    name: String,
+    defines: Vec<(String, String)>,
+}
+
+/// Source with includes expanded
+struct ExpandedSource {
+    text: String,
+    /// Origin (file, line) of each expanded source line
+    lines: Vec<(PathBuf, u32)>,
+    files: Vec<PathBuf>,
+}
+
+impl ExpandedSource {
+    /// Error in expanded source location to original file error converting function
+    fn error(&self, span: naga::Span, message: String) -> CompileError {
+        if !span.is_defined() {
+            return CompileError {
+                path: self.files[0].clone(),
+                line: 0,
+                column: 0,
+                message,
+            };
+        }
+
+        let location = span.location(&self.text);
+        match self.lines.get(location.line_number as usize - 1) {
+            Some((path, line)) => CompileError {
+                path: path.clone(),
+                line: *line,
+                column: location.line_position,
+                message,
+            },
+            None => CompileError::new(&self.files[0], 0, message),
+        }
+    }
+}
+
+/// Source file with includes recursive expanding function
+/// Each file is included at most once, so include guards aren't required.
+fn expand(root: &Path, path: &Path, source: &mut ExpandedSource, stack: &mut Vec<PathBuf>) -> Result<(), CompileError> {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if stack.contains(&canonical) || source.files.contains(&canonical) {
+        return Ok(());
+    }
+
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| CompileError::new(path, 0, format!("failed to read file: {err}")))?;
+
+    source.files.push(canonical.clone());
+    stack.push(canonical);
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index as u32 + 1;
+
+        let Some(include) = line.trim_start().strip_prefix("#include") else {
+            source.text.push_str(line);
+            source.text.push('\n');
+            source.lines.push((path.to_path_buf(), line_number));
+            continue;
+        };
+
+        let include = include.trim();
+        let (name, local) = if let Some(name) = include.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            (name, true)
+        } else if let Some(name) = include.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+            (name, false)
+        } else {
+            return Err(CompileError::new(
+                path,
+                line_number,
+                format!("malformed include directive '{}'", line.trim()),
+            ));
+        };
+
+        // Quoted includes are searched relative to the including file first, then to the root
+        let local_path = path.parent().map(|dir| dir.join(name)).filter(|path| local && path.is_file());
+        let include_path = local_path.unwrap_or_else(|| root.join(name));
+
+        if !include_path.is_file() {
+            return Err(CompileError::new(
+                path,
+                line_number,
+                format!("included file '{name}' not found"),
+            ));
+        }
+
+        expand(root, &include_path, source, stack)?;
+    }
+
+    stack.pop();
+
+    Ok(())
+}
+
+/// Files shader source is built from (source file itself and all includes) getting function
+pub fn dependencies(root: &Path, path: &Path) -> Result<Vec<PathBuf>, CompileError> {
+    let mut source = ExpandedSource {
+        text: String::new(),
+        lines: Vec::new(),
+        files: Vec::new(),
+    };
+    expand(root, path, &mut source, &mut Vec::new())?;
+
+    Ok(source.files)
+}
+
+/// Permutation declarations parsing function
+fn parse_permutations(path: &Path, text: &str) -> Result<Vec<Permutation>, CompileError> {
+    let mut permutations = Vec::<Permutation>::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let Some(declaration) = line.trim_start().strip_prefix("// permutation:") else {
+            continue;
+        };
+
+        let mut words = declaration.split_whitespace();
+        let Some(name) = words.next() else {
+            return Err(CompileError::new(path, index as u32 + 1, "permutation name expected"));
+        };
+
+        if permutations.iter().any(|permutation| permutation.name == name) {
+            return Err(CompileError::new(
+                path,
+                index as u32 + 1,
+                format!("permutation '{name}' is declared twice"),
+            ));
+        }
+
+        let defines = words
+            .map(|define| match define.split_once('=') {
+                Some((key, value)) => (key.to_string(), value.to_string()),
+                None => (define.to_string(), "1".to_string()),
+            })
+            .collect();
+
+        permutations.push(Permutation {
+            name: name.to_string(),
+            defines,
+        });
+    }
+
+    Ok(permutations)
+}
+
+/// Expanded HLSL source to SPIR-V compiling function
+#[cfg(feature = "hlsl")]
+fn compile_expanded_hlsl(
+    source: &ExpandedSource,
+    stage: naga::ShaderStage,
+    defines: &[(String, String)],
+) -> Result<Vec<u32>, Vec<CompileError>> {
+    // Expanded source is compiled under this name, so error lines are mapped back by `source.lines`
+    const SOURCE_NAME: &str = "expanded.hlsl";
+
+    let error = |message: String| vec![CompileError::new(&source.files[0], 0, message)];
+
+    let kind = match stage {
+        naga::ShaderStage::Vertex => shaderc::ShaderKind::Vertex,
+        naga::ShaderStage::Fragment => shaderc::ShaderKind::Fragment,
+        naga::ShaderStage::Compute => shaderc::ShaderKind::Compute,
+        naga::ShaderStage::Task | naga::ShaderStage::Mesh => {
+            return Err(error(format!("{stage:?} HLSL shaders are not supported")));
+        }
+    };
+
+    let (Some(mut compiler), Some(mut options)) = (shaderc::Compiler::new(), shaderc::CompileOptions::new()) else {
+        return Err(error("failed to initialize shaderc".to_string()));
+    };
+    options.set_source_language(shaderc::SourceLanguage::HLSL);
+    options.set_target_env(shaderc::TargetEnv::Vulkan, shaderc::EnvVersion::Vulkan1_0 as u32);
+    for (name, value) in defines {
+        options.add_macro_definition(name, Some(value.as_str()));
+    }
+
+    match compiler.compile_into_spirv(&source.text, kind, SOURCE_NAME, "main", Some(&options)) {
+        Ok(artifact) => Ok(artifact.as_binary().to_vec()),
+        // Messages have `<name>:<line>: error: <message>` format
+        Err(shaderc::Error::CompilationError(_, messages)) => Err(messages
+            .lines()
+            .filter(|message| !message.trim().is_empty())
+            .map(|message| {
+                let located = message
+                    .strip_prefix(SOURCE_NAME)
+                    .and_then(|rest| rest.strip_prefix(':'))
+                    .and_then(|rest| rest.split_once(':'))
+                    .and_then(|(line, rest)| Some((line.parse::<usize>().ok()?, rest.trim())));
+
+                match located.and_then(|(line, rest)| Some((source.lines.get(line.checked_sub(1)?)?, rest))) {
+                    Some(((path, line), rest)) => CompileError::new(path, *line, rest),
+                    None => CompileError::new(&source.files[0], 0, message.trim()),
+                }
+            })
+            .collect()),
+        Err(err) => Err(error(format!("shaderc error: {err}"))),
+    }
+}
+
+/// Expanded HLSL source to SPIR-V compiling function
+#[cfg(not(feature = "hlsl"))]
+fn compile_expanded_hlsl(
+    source: &ExpandedSource,
+    _stage: naga::ShaderStage,
+    _defines: &[(String, String)],
+) -> Result<Vec<u32>, Vec<CompileError>> {
+    Err(vec![CompileError::new(
+        &source.files[0],
+        0,
+        "HLSL shaders require `hlsl` feature of the crate",
+    )])
+}
+
+/// Expanded source to SPIR-V compiling function
+fn compile_expanded(
+    source: &ExpandedSource,
+    stage: naga::ShaderStage,
+    defines: &[(String, String)],
+) -> Result<Vec<u32>, Vec<CompileError>> {
+    let options = naga::front::glsl::Options {
+        stage,
+        defines: defines.iter().cloned().collect(),
+    };
+
+    let module = naga::front::glsl::Frontend::default()
+        .parse(&options, &source.text)
+        .map_err(|errors| {
+            errors
+                .errors
+                .into_iter()
+                .map(|error| source.error(error.meta, error.kind.to_string()))
+                .collect::<Vec<_>>()
+        })?;
+
+    let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+        .validate(&module)
+        .map_err(|error| {
+            let span = error.spans().next().map(|(span, _)| *span).unwrap_or_default();
+            // Validation errors are nested, so the whole source chain is reported
+            let mut messages = vec![error.to_string()];
+            let mut cause = std::error::Error::source(&error);
+            while let Some(inner) = cause {
+                messages.push(inner.to_string());
+                cause = inner.source();
+            }
+            let message = messages.join(": ");
+            vec![source.error(span, message)]
+        })?;
+
+    // GLSL for Vulkan already uses Vulkan coordinate space
+    let options = naga::back::spv::Options {
+        flags: naga::back::spv::WriterFlags::LABEL_VARYINGS,
+        ..Default::default()
+    };
+
+    naga::back::spv::write_vec(&module, &info, &options, None)
+        .map_err(|error| vec![CompileError::new(&source.files[0], 0, format!("SPIR-V generation failed: {error}"))])
+}
+
+/// Shader source file compiling function
+/// Compiles base shader and all permutations declared in the file.
+pub fn compile(root: &Path, path: &Path) -> Result<CompiledSource, Vec<CompileError>> {
+    let Some(stage) = shader_stage(path).map_err(|error| vec![error])? else {
+        return Ok(CompiledSource {
+            shaders: Vec::new(),
+            dependencies: Vec::new(),
+        });
+    };
+
+    let mut source = ExpandedSource {
+        text: String::new(),
+        lines: Vec::new(),
+        files: Vec::new(),
+    };
+    expand(root, path, &mut source, &mut Vec::new()).map_err(|error| vec![error])?;
+
+    let main_text = std::fs::read_to_string(path)
+        .map_err(|err| vec![CompileError::new(path, 0, format!("failed to read file: {err}"))])?;
+    let permutations = parse_permutations(path, &main_text).map_err(|error| vec![error])?;
+
+    let compile_expanded = if is_hlsl(path) { compile_expanded_hlsl } else { compile_expanded };
+
+    let name = shader_name(root, path);
+    let mut shaders = vec![CompiledShader {
+        name: name.clone(),
+        words: compile_expanded(&source, stage, &[])?,
+    }];
+
+    let mut errors = Vec::new();
+    for permutation in permutations {
+        match compile_expanded(&source, stage, &permutation.defines) {
+            Ok(words) => shaders.push(CompiledShader {
+                name: format!("{name}:{}", permutation.name),
+                words,
+            }),
+            Err(permutation_errors) => errors.extend(permutation_errors.into_iter().map(|mut error| {
+                error.message = format!("{} (permutation '{}')", error.message, permutation.name);
+                error
+            })),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    Ok(CompiledSource {
+        shaders,
+        dependencies: source.files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages() {
+        let stage = |path: &str| shader_stage(Path::new(path)).map_err(|error| error.message);
+
+        assert_eq!(stage("mesh.vert"), Ok(Some(naga::ShaderStage::Vertex)));
+        assert_eq!(stage("post/blur.comp"), Ok(Some(naga::ShaderStage::Compute)));
+        assert_eq!(stage("mesh.frag.hlsl"), Ok(Some(naga::ShaderStage::Fragment)));
+        assert_eq!(stage("include/common.glsl"), Ok(None));
+        assert_eq!(stage("include/common.hlsli"), Ok(None));
+        assert!(stage("mesh.hlsl").is_err());
+        assert!(stage("mesh.geom").is_err());
+        assert!(stage("mesh.tesc.hlsl").is_err());
+    }
+
+    #[test]
+    fn include_files_are_skipped() {
+        let root = std::env::temp_dir().join(format!("wat3rs-shader-compiler-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("include")).unwrap();
+        for name in ["mesh.vert", "mesh.frag.hlsl", "include/common.glsl", "include/common.hlsli"] {
+            std::fs::write(root.join(name), "").unwrap();
+        }
+
+        let shaders = find_shaders(&root).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(shaders, [root.join("mesh.frag.hlsl"), root.join("mesh.vert")]);
+    }
+}
@@ -1,106 +1,507 @@
 use std::sync::Arc;
 
-use ash::{khr::surface, vk};
+use ash::{khr, vk};
 
-use super::kernel::Kernel;
+use super::{
+    error::{VulkanCallError, VulkanResultExt},
+    kernel::Kernel,
+};
+
+/// Swapchain configuration
+#[derive(Clone, Debug)]
+pub struct SwapchainConfig {
+    present_modes: Vec<vk::PresentModeKHR>,
+    vsync: Option<bool>,
+    image_count: Option<u32>,
+    surface_formats: Vec<vk::SurfaceFormatKHR>,
+    image_usage: vk::ImageUsageFlags,
+}
+
+impl Default for SwapchainConfig {
+    fn default() -> Self {
+        Self {
+            present_modes: vec![vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::FIFO],
+            vsync: None,
+            image_count: None,
+            surface_formats: vec![
+                vk::SurfaceFormatKHR {
+                    format: vk::Format::B8G8R8A8_SRGB,
+                    color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+                },
+                vk::SurfaceFormatKHR {
+                    format: vk::Format::R8G8B8A8_SRGB,
+                    color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+                },
+            ],
+            image_usage: vk::ImageUsageFlags::empty(),
+        }
+    }
+}
+
+impl SwapchainConfig {
+    /// Preferred present modes setting function
+    /// Modes are listed in priority order, FIFO is used if none of them is supported.
+    pub fn present_modes(mut self, present_modes: &[vk::PresentModeKHR]) -> Self {
+        self.present_modes = present_modes.to_vec();
+        self
+    }
+
+    /// Vertical synchronization setting function, overrides preferred present modes
+    /// If enabled, only FIFO and FIFO_RELAXED modes are selected from preferred present modes.
+    /// If disabled, uncapped IMMEDIATE mode is preferred, then MAILBOX and FIFO (e.g. for benchmarking).
+    pub fn vsync(mut self, vsync: bool) -> Self {
+        self.vsync = Some(vsync);
+        self
+    }
+
+    /// Desired swapchain image count setting function
+    /// Count is clamped to surface capabilities, minimal supported count + 1 is used by default.
+    pub fn image_count(mut self, image_count: u32) -> Self {
+        self.image_count = Some(image_count);
+        self
+    }
+
+    /// Preferred surface formats setting function
+    /// Formats are listed in priority order. If none of them is supported, the first supported
+    /// format with one of preferred `vk::Format`s is used, and the first supported one otherwise.
+    pub fn surface_formats(mut self, surface_formats: &[vk::SurfaceFormatKHR]) -> Self {
+        self.surface_formats = surface_formats.to_vec();
+        self
+    }
+
+    /// Extra swapchain image usage setting function
+    /// COLOR_ATTACHMENT and TRANSFER_DST usages are always enabled.
+    pub fn image_usage(mut self, image_usage: vk::ImageUsageFlags) -> Self {
+        self.image_usage = image_usage;
+        self
+    }
+
+    /// Present mode selection function
+    fn select_present_mode(&self, supported: &[vk::PresentModeKHR]) -> vk::PresentModeKHR {
+        const UNCAPPED_PRESENT_MODES: &[vk::PresentModeKHR] = &[
+            vk::PresentModeKHR::IMMEDIATE,
+            vk::PresentModeKHR::MAILBOX,
+        ];
+
+        let present_modes = match self.vsync {
+            Some(false) => UNCAPPED_PRESENT_MODES,
+            _ => &self.present_modes[..],
+        };
+
+        present_modes
+            .iter()
+            .copied()
+            .filter(|mode| {
+                self.vsync != Some(true)
+                    || *mode == vk::PresentModeKHR::FIFO
+                    || *mode == vk::PresentModeKHR::FIFO_RELAXED
+            })
+            .find(|mode| supported.contains(mode))
+            // FIFO support is required by specification
+            .unwrap_or(vk::PresentModeKHR::FIFO)
+    }
+
+    /// Surface format selection function
+    /// Returns `None` if surface supports no formats.
+    fn select_surface_format(&self, supported: &[vk::SurfaceFormatKHR]) -> Option<vk::SurfaceFormatKHR> {
+        self.surface_formats
+            .iter()
+            .find(|format| supported.contains(format))
+            .or_else(|| {
+                supported.iter().find(|supported_format| {
+                    self.surface_formats
+                        .iter()
+                        .any(|format| format.format == supported_format.format)
+                })
+            })
+            .or(supported.first())
+            .copied()
+    }
+
+    /// Image count selection function
+    fn select_image_count(&self, capabilities: &vk::SurfaceCapabilitiesKHR) -> u32 {
+        let image_count = self
+            .image_count
+            .unwrap_or(capabilities.min_image_count + 1)
+            .max(capabilities.min_image_count);
+
+        // Zero maximal image count means that there is no limit
+        if capabilities.max_image_count == 0 {
+            image_count
+        } else {
+            image_count.min(capabilities.max_image_count)
+        }
+    }
+}
+
+/// Composite alpha mode selection function
+/// Opaque composition is preferred, but some platforms (e.g. Android) support only other modes.
+fn select_composite_alpha(capabilities: &vk::SurfaceCapabilitiesKHR) -> vk::CompositeAlphaFlagsKHR {
+    [
+        vk::CompositeAlphaFlagsKHR::OPAQUE,
+        vk::CompositeAlphaFlagsKHR::INHERIT,
+        vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED,
+        vk::CompositeAlphaFlagsKHR::POST_MULTIPLIED,
+    ]
+    .into_iter()
+    .find(|&mode| capabilities.supported_composite_alpha.contains(mode))
+    // At least one mode is supported by specification
+    .unwrap_or(vk::CompositeAlphaFlagsKHR::OPAQUE)
+}
 
 pub struct Swapchain {
     kernel: Arc<Kernel>,
+    surface: vk::SurfaceKHR,
+    instance_ext_surface: khr::surface::Instance,
+    device_ext_swapchain: khr::swapchain::Device,
+    present_queue: vk::Queue,
+    config: SwapchainConfig,
     swapchain: vk::SwapchainKHR,
     images: Vec<vk::Image>,
+    image_views: Vec<vk::ImageView>,
+    /// Per-image semaphores signaled by rendering and waited by presentation
+    /// Semaphore is reused only after its image is acquired again, so its previous presentation is done.
+    render_finished_semaphores: Vec<vk::Semaphore>,
+    format: vk::Format,
+    extent: vk::Extent2D,
 }
 
+#[derive(Clone, Debug)]
 pub enum SwapchainCreateError {
-    VulkanError(vk::Result),
+    VulkanError(VulkanCallError),
+    NoSurface,
+    UnsupportedImageUsage(vk::ImageUsageFlags),
+    /// Surface reports no supported formats
+    NoSurfaceFormats,
+}
+
+impl std::fmt::Display for SwapchainCreateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
+            Self::NoSurface => f.write_str("kernel has no surface to present to"),
+            Self::UnsupportedImageUsage(usage) => {
+                f.write_fmt(format_args!("unsupported swapchain image usage: {usage:?}"))
+            }
+            Self::NoSurfaceFormats => f.write_str("surface supports no formats"),
+        }
+    }
 }
 
-impl From<vk::Result> for SwapchainCreateError {
-    fn from(value: vk::Result) -> Self {
+impl std::error::Error for SwapchainCreateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::VulkanError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<VulkanCallError> for SwapchainCreateError {
+    fn from(value: VulkanCallError) -> Self {
         Self::VulkanError(value)
     }
 }
 
 impl Swapchain {
-    pub fn new(kernel: Arc<Kernel>) -> Result<Self, SwapchainCreateError> {
+    /// Swapchain create function
+    /// Swapchain handle is created only if surface has non-zero extent, so
+    /// `recreate` call is required for handle-less swapchains after surface resize.
+    pub fn new(
+        kernel: Arc<Kernel>,
+        config: SwapchainConfig,
+        extent: vk::Extent2D,
+    ) -> Result<Self, SwapchainCreateError> {
+        let (
+            Some(surface),
+            Some(instance_ext_surface),
+            Some(device_ext_swapchain),
+            Some(present_queue),
+        ) = (
+            kernel.surface,
+            kernel.instance_ext_surface.clone(),
+            kernel.device_ext_swapchain.clone(),
+            kernel.present_queue,
+        ) else {
+            return Err(SwapchainCreateError::NoSurface);
+        };
+
+        let mut swapchain = Swapchain {
+            kernel,
+            surface,
+            instance_ext_surface,
+            device_ext_swapchain,
+            present_queue,
+            config,
+            swapchain: vk::SwapchainKHR::null(),
+            images: Vec::new(),
+            image_views: Vec::new(),
+            render_finished_semaphores: Vec::new(),
+            format: vk::Format::UNDEFINED,
+            extent: vk::Extent2D::default(),
+        };
+
+        swapchain.recreate(extent)?;
+
+        Ok(swapchain)
+    }
+
+    /// Swapchain configuration setting function
+    /// Configuration is applied during next `recreate` call.
+    pub fn set_config(&mut self, config: SwapchainConfig) {
+        self.config = config;
+    }
+
+    /// Swapchain recreation function
+    /// Returns true if swapchain is recreated and false if surface has zero extent
+    /// (e.g. window is minimized), so there is nothing to render to.
+    /// # Note
+    /// Old swapchain images must not be used by GPU during this call.
+    pub fn recreate(&mut self, extent: vk::Extent2D) -> Result<bool, SwapchainCreateError> {
+        let kernel = &self.kernel;
+
         let present_mode = {
             let present_modes = unsafe {
-                kernel
-                    .instance_ext_surface
-                    .get_physical_device_surface_present_modes(kernel.physical_device, kernel.surface)
-                    ?
+                self.instance_ext_surface
+                    .get_physical_device_surface_present_modes(kernel.physical_device, self.surface)
+                    .context("vkGetPhysicalDeviceSurfacePresentModesKHR")?
             };
 
-            if present_modes.contains(&vk::PresentModeKHR::MAILBOX) {
-                vk::PresentModeKHR::MAILBOX
-            } else {
-                vk::PresentModeKHR::FIFO
-            }
+            self.config.select_present_mode(&present_modes)
         };
         let surface_format = {
             let surface_formats = unsafe {
-                kernel
-                    .instance_ext_surface
-                    .get_physical_device_surface_formats(kernel.physical_device, kernel.surface)
-                    ?
+                self.instance_ext_surface
+                    .get_physical_device_surface_formats(kernel.physical_device, self.surface)
+                    .context("vkGetPhysicalDeviceSurfaceFormatsKHR")?
             };
 
-            const WANTED_FORMAT: vk::SurfaceFormatKHR = vk::SurfaceFormatKHR {
-                color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
-                format: vk::Format::B8G8R8A8_SRGB
-            };
-
-            if surface_formats.contains(&WANTED_FORMAT) {
-                WANTED_FORMAT
-            } else {
-                *surface_formats
-                    .iter()
-                    .find(|format| format.format == vk::Format::B8G8R8A8_SRGB)
-                    .unwrap_or(&surface_formats[0])
-            }
+            self.config
+                .select_surface_format(&surface_formats)
+                .ok_or(SwapchainCreateError::NoSurfaceFormats)?
         };
         let surface_capabilities = unsafe {
-            kernel
-                .instance_ext_surface
-                .get_physical_device_surface_capabilities(kernel.physical_device, kernel.surface)
-                ?
+            self.instance_ext_surface
+                .get_physical_device_surface_capabilities(kernel.physical_device, self.surface)
+                .context("vkGetPhysicalDeviceSurfaceCapabilitiesKHR")?
         };
 
+        // u32::MAX current extent means that surface size is determined by the swapchain
+        let extent = if surface_capabilities.current_extent.width == u32::MAX {
+            vk::Extent2D {
+                width: extent.width.clamp(
+                    surface_capabilities.min_image_extent.width,
+                    surface_capabilities.max_image_extent.width,
+                ),
+                height: extent.height.clamp(
+                    surface_capabilities.min_image_extent.height,
+                    surface_capabilities.max_image_extent.height,
+                ),
+            }
+        } else {
+            surface_capabilities.current_extent
+        };
+
+        if extent.width == 0 || extent.height == 0 {
+            return Ok(false);
+        }
+
+        let image_usage = vk::ImageUsageFlags::COLOR_ATTACHMENT
+            | vk::ImageUsageFlags::TRANSFER_DST
+            | self.config.image_usage;
+
+        if !surface_capabilities.supported_usage_flags.contains(image_usage) {
+            return Err(SwapchainCreateError::UnsupportedImageUsage(
+                image_usage & !surface_capabilities.supported_usage_flags,
+            ));
+        }
+
+        let min_image_count = self.config.select_image_count(&surface_capabilities);
+
+        let queue_family_indices = [
+            kernel.queue_family_indices.main,
+            kernel.queue_family_indices.present.unwrap_or(kernel.queue_family_indices.main),
+        ];
+
         let swapchain = unsafe {
-            kernel
-                .device_ext_swapchain
-                .create_swapchain(
-                    &vk::SwapchainCreateInfoKHR::default()
-                        .image_format(surface_format.format)
-                        .image_color_space(surface_format.color_space)
-                        .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
-                        .present_mode(present_mode)
-                        .pre_transform(surface_capabilities.current_transform),
-                    None
-                )
-                ?
+            let create_info = vk::SwapchainCreateInfoKHR::default()
+                .surface(self.surface)
+                .min_image_count(min_image_count)
+                .image_format(surface_format.format)
+                .image_color_space(surface_format.color_space)
+                .image_extent(extent)
+                .image_array_layers(1)
+                .image_usage(image_usage)
+                .pre_transform(surface_capabilities.current_transform)
+                .composite_alpha(select_composite_alpha(&surface_capabilities))
+                .present_mode(present_mode)
+                .clipped(true)
+                .old_swapchain(self.swapchain);
+
+            let create_info = if queue_family_indices[0] != queue_family_indices[1] {
+                create_info
+                    .image_sharing_mode(vk::SharingMode::CONCURRENT)
+                    .queue_family_indices(&queue_family_indices)
+            } else {
+                create_info.image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            };
+
+            self.device_ext_swapchain
+                .create_swapchain(&create_info, None)
+                .context("vkCreateSwapchainKHR")?
         };
 
-        let images = unsafe {
-            kernel
-                .device_ext_swapchain
-                .get_swapchain_images(swapchain)
-                ?
+        let images = match unsafe { self.device_ext_swapchain.get_swapchain_images(swapchain) } {
+            Ok(images) => images,
+            Err(err) => {
+                unsafe { self.device_ext_swapchain.destroy_swapchain(swapchain, None) };
+                return Err(VulkanCallError { call: "vkGetSwapchainImagesKHR", result: err }.into());
+            }
         };
 
-        Ok(Swapchain {
-            kernel,
-            swapchain,
-            images,
-        })
+        let mut image_views = Vec::with_capacity(images.len());
+        for image in images.iter().copied() {
+            let view_result = unsafe {
+                kernel.device.create_image_view(
+                    &vk::ImageViewCreateInfo::default()
+                        .image(image)
+                        .view_type(vk::ImageViewType::TYPE_2D)
+                        .format(surface_format.format)
+                        .subresource_range(
+                            vk::ImageSubresourceRange::default()
+                                .aspect_mask(vk::ImageAspectFlags::COLOR)
+                                .level_count(1)
+                                .layer_count(1),
+                        ),
+                    None,
+                )
+            };
+
+            match view_result {
+                Ok(view) => image_views.push(view),
+                Err(err) => {
+                    unsafe {
+                        for view in image_views {
+                            kernel.device.destroy_image_view(view, None);
+                        }
+                        self.device_ext_swapchain.destroy_swapchain(swapchain, None);
+                    }
+                    return Err(VulkanCallError { call: "vkCreateImageView", result: err }.into());
+                }
+            }
+        }
+
+        let mut render_finished_semaphores = Vec::with_capacity(images.len());
+        for _ in 0..images.len() {
+            match unsafe { kernel.device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None) } {
+                Ok(semaphore) => render_finished_semaphores.push(semaphore),
+                Err(err) => {
+                    unsafe {
+                        for semaphore in render_finished_semaphores {
+                            kernel.device.destroy_semaphore(semaphore, None);
+                        }
+                        for view in image_views {
+                            kernel.device.destroy_image_view(view, None);
+                        }
+                        self.device_ext_swapchain.destroy_swapchain(swapchain, None);
+                    }
+                    return Err(VulkanCallError { call: "vkCreateSemaphore", result: err }.into());
+                }
+            }
+        }
+
+        // Old swapchain is retired by new swapchain creation, so it may be destroyed now
+        unsafe { self.destroy_handles() };
+
+        self.swapchain = swapchain;
+        self.images = images;
+        self.image_views = image_views;
+        self.render_finished_semaphores = render_finished_semaphores;
+        self.format = surface_format.format;
+        self.extent = extent;
+
+        Ok(true)
+    }
+
+    /// Swapchain handle, image views and semaphores destroy function
+    /// # Safety
+    /// Swapchain images and semaphores must not be used by GPU
+    unsafe fn destroy_handles(&mut self) {
+        for view in self.image_views.drain(..) {
+            self.kernel.device.destroy_image_view(view, None);
+        }
+        for semaphore in self.render_finished_semaphores.drain(..) {
+            self.kernel.device.destroy_semaphore(semaphore, None);
+        }
+
+        self.device_ext_swapchain
+            .destroy_swapchain(self.swapchain, None);
+        self.swapchain = vk::SwapchainKHR::null();
+        self.images.clear();
+    }
+
+    /// Swapchain handle getting function
+    pub fn handle(&self) -> vk::SwapchainKHR {
+        self.swapchain
+    }
+
+    /// Swapchain images getting function
+    pub fn images(&self) -> &[vk::Image] {
+        &self.images
+    }
+
+    /// Swapchain image views getting function
+    pub fn image_views(&self) -> &[vk::ImageView] {
+        &self.image_views
+    }
+
+    /// Render finished semaphore of swapchain image getting function
+    /// Semaphore must be signaled by the last submission rendering to the image and is waited by `present`.
+    pub fn render_finished_semaphore(&self, image_index: u32) -> vk::Semaphore {
+        self.render_finished_semaphores[image_index as usize]
+    }
+
+    /// Swapchain image format getting function
+    pub fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    /// Swapchain image extent getting function
+    /// Extent may differ from requested one, as it's clamped to surface capabilities.
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    /// Next presentable image acquiring function
+    /// Returns index of the acquired image and swapchain suboptimality flag.
+    /// # Note
+    /// Raw `vk::Result` is returned, as ERROR_OUT_OF_DATE_KHR must be handled by caller.
+    pub fn acquire_next_image(&self, semaphore: vk::Semaphore) -> Result<(u32, bool), vk::Result> {
+        unsafe {
+            self.device_ext_swapchain
+                .acquire_next_image(self.swapchain, u64::MAX, semaphore, vk::Fence::null())
+        }
+    }
+
+    /// Image presentation function
+    /// Returns swapchain suboptimality flag.
+    /// # Note
+    /// Raw `vk::Result` is returned, as ERROR_OUT_OF_DATE_KHR must be handled by caller.
+    pub fn present(&self, image_index: u32, wait_semaphore: vk::Semaphore) -> Result<bool, vk::Result> {
+        unsafe {
+            self.device_ext_swapchain.queue_present(
+                self.present_queue,
+                &vk::PresentInfoKHR::default()
+                    .wait_semaphores(&[wait_semaphore])
+                    .swapchains(&[self.swapchain])
+                    .image_indices(&[image_index]),
+            )
+        }
     }
 }
 
 impl Drop for Swapchain {
     fn drop(&mut self) {
-        unsafe {
-            self.kernel
-                .device_ext_swapchain
-                .destroy_swapchain(self.swapchain, None)
-        }
+        unsafe { self.destroy_handles() };
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,935 @@
+use std::sync::Arc;
+
+use ash::vk;
+
+use crate::utility::math::Ext2;
+
+use super::{
+    allocator::{Allocator, MemoryLocation},
+    buffer::{Buffer, ResourceCreateError},
+    error::{VulkanCallError, VulkanResultExt},
+    image::{format_block_extent, format_block_size, Image},
+    kernel::Kernel,
+};
+
+/// Staging offset alignment of buffer uploads, `vkCmdCopyBuffer` has no alignment requirements
+/// Image uploads are aligned by `image_staging_alignment` instead.
+const STAGING_ALIGNMENT: u64 = 16;
+
+/// Greatest common divisor calculation function
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Least common multiple calculation function
+fn lcm(a: u64, b: u64) -> u64 {
+    a / gcd(a, b) * b
+}
+
+/// Tightly packed image layer size calculation function
+/// Data length is checked to match size of all layers. Only single aspect formats with known texel
+/// block size can be uploaded, as buffer to image copy region must name exactly one aspect.
+fn image_layer_size(
+    format: vk::Format,
+    extent: Ext2<u32>,
+    array_layers: u32,
+    data_len: usize,
+) -> Result<u64, UploadError> {
+    let block_size = format_block_size(format).ok_or(UploadError::InvalidData(
+        "image format has unknown texel block size or several aspects",
+    ))?;
+    let block_extent = format_block_extent(format);
+    let layer_size = extent.w.div_ceil(block_extent.w) as u64 * extent.h.div_ceil(block_extent.h) as u64 * block_size;
+
+    if data_len as u64 != layer_size * array_layers as u64 {
+        return Err(UploadError::InvalidData("data length must match first mip level size of all layers"));
+    }
+
+    Ok(layer_size)
+}
+
+/// Upload completion ticket
+/// Tickets are increasing, so ticket is complete if some later ticket is.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UploadTicket(u64);
+
+impl UploadTicket {
+    /// Ticket value getting function
+    pub fn value(self) -> u64 {
+        self.0
+    }
+}
+
+/// Uploader configuration
+#[derive(Copy, Clone, Debug)]
+pub struct UploaderConfig {
+    staging_size: u64,
+    slot_count: usize,
+}
+
+impl Default for UploaderConfig {
+    fn default() -> Self {
+        Self {
+            staging_size: 16 * 1024 * 1024,
+            slot_count: 3,
+        }
+    }
+}
+
+impl UploaderConfig {
+    /// Single staging buffer size setting function
+    /// Uploads that don't fit into staging buffer get a temporary one.
+    pub fn staging_size(mut self, staging_size: u64) -> Self {
+        self.staging_size = staging_size;
+        self
+    }
+
+    /// Count of staging buffers (and so batches in flight) setting function
+    pub fn slot_count(mut self, slot_count: usize) -> Self {
+        self.slot_count = slot_count;
+        self
+    }
+}
+
+/// Upload error
+#[derive(Clone, Debug)]
+pub enum UploadError {
+    VulkanError(VulkanCallError),
+    /// Staging buffer creation error
+    ResourceCreateError(ResourceCreateError),
+    /// Destination resource lacks usage required for upload
+    MissingUsage(&'static str),
+    /// Image format doesn't support linear filtered blits, so mipmaps can't be generated
+    UnsupportedMipmapFormat(vk::Format),
+    /// Uploaded data doesn't match the destination
+    InvalidData(&'static str),
+}
+
+impl std::fmt::Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::VulkanError(vk_err) => f.write_fmt(format_args!("vulkan error: {vk_err}")),
+            Self::ResourceCreateError(err) => f.write_fmt(format_args!("staging buffer creation error: {err}")),
+            Self::MissingUsage(usage) => f.write_fmt(format_args!("destination lacks {usage} usage")),
+            Self::UnsupportedMipmapFormat(format) => f.write_fmt(format_args!(
+                "format {format:?} doesn't support linear blits for mipmap generation"
+            )),
+            Self::InvalidData(reason) => f.write_fmt(format_args!("invalid upload data: {reason}")),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::VulkanError(err) => Some(err),
+            Self::ResourceCreateError(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<VulkanCallError> for UploadError {
+    fn from(value: VulkanCallError) -> Self {
+        Self::VulkanError(value)
+    }
+}
+
+impl From<ResourceCreateError> for UploadError {
+    fn from(value: ResourceCreateError) -> Self {
+        Self::ResourceCreateError(value)
+    }
+}
+
+/// Image that gets mipmaps generated during batch submission
+struct PendingMipmaps {
+    image: vk::Image,
+    width: u32,
+    height: u32,
+    mip_levels: u32,
+    array_layers: u32,
+}
+
+/// Batch of uploads with its staging buffer and submission objects
+struct UploadSlot {
+    staging: Buffer<u8>,
+    /// Temporary staging buffers for uploads that don't fit into `staging`
+    overflow: Vec<Buffer<u8>>,
+    /// Command pool of the transfer family (main family if there's no dedicated one)
+    transfer_pool: vk::CommandPool,
+    transfer_command_buffer: vk::CommandBuffer,
+    /// Command pool of the main family, used for ownership acquisition and mipmap generation
+    /// if transfer family is dedicated
+    main_pool: Option<vk::CommandPool>,
+    main_command_buffer: vk::CommandBuffer,
+    /// Transfer to main queue submission semaphore
+    semaphore: vk::Semaphore,
+    fence: vk::Fence,
+    ticket: u64,
+}
+
+impl UploadSlot {
+    fn new(kernel: &Kernel, allocator: &Arc<Allocator>, staging_size: u64) -> Result<Self, UploadError> {
+        let device = &kernel.device;
+        let staging = Buffer::new(
+            allocator,
+            "upload staging",
+            staging_size as usize,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            MemoryLocation::CpuToGpu,
+        )?;
+
+        // Slot is destroyed by `destroy` in case of error, so null handles are set first
+        let mut slot = Self {
+            staging,
+            overflow: Vec::new(),
+            transfer_pool: vk::CommandPool::null(),
+            transfer_command_buffer: vk::CommandBuffer::null(),
+            main_pool: None,
+            main_command_buffer: vk::CommandBuffer::null(),
+            semaphore: vk::Semaphore::null(),
+            fence: vk::Fence::null(),
+            ticket: 0,
+        };
+
+        let result = (|| unsafe {
+            let indices = &kernel.queue_family_indices;
+
+            (slot.transfer_pool, slot.transfer_command_buffer) =
+                Self::create_command_buffer(device, indices.transfer_or_main())?;
+
+            if indices.transfer.is_some() {
+                let (pool, command_buffer) = Self::create_command_buffer(device, indices.main)?;
+                slot.main_pool = Some(pool);
+                slot.main_command_buffer = command_buffer;
+            }
+
+            slot.semaphore = device
+                .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+                .context("vkCreateSemaphore")?;
+            slot.fence = device
+                .create_fence(&vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED), None)
+                .context("vkCreateFence")?;
+
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => Ok(slot),
+            Err(err) => {
+                unsafe { slot.destroy(kernel) };
+                Err(err)
+            }
+        }
+    }
+
+    unsafe fn create_command_buffer(
+        device: &ash::Device,
+        queue_family_index: u32,
+    ) -> Result<(vk::CommandPool, vk::CommandBuffer), VulkanCallError> {
+        let pool = device.create_command_pool(
+            &vk::CommandPoolCreateInfo::default()
+                .queue_family_index(queue_family_index)
+                .flags(vk::CommandPoolCreateFlags::TRANSIENT),
+            None,
+        ).context("vkCreateCommandPool")?;
+
+        let command_buffer = device.allocate_command_buffers(
+            &vk::CommandBufferAllocateInfo::default()
+                .command_pool(pool)
+                .level(vk::CommandBufferLevel::PRIMARY)
+                .command_buffer_count(1),
+        );
+
+        match command_buffer {
+            Ok(command_buffers) => Ok((pool, command_buffers[0])),
+            Err(err) => {
+                device.destroy_command_pool(pool, None);
+                Err(VulkanCallError { call: "vkAllocateCommandBuffers", result: err })
+            }
+        }
+    }
+
+    /// Slot objects destroy function
+    /// # Safety
+    /// Slot objects must not be used by GPU
+    unsafe fn destroy(&mut self, kernel: &Kernel) {
+        let device = &kernel.device;
+
+        device.destroy_fence(self.fence, None);
+        device.destroy_semaphore(self.semaphore, None);
+        if let Some(main_pool) = self.main_pool {
+            device.destroy_command_pool(main_pool, None);
+        }
+        device.destroy_command_pool(self.transfer_pool, None);
+        self.overflow.clear();
+    }
+}
+
+/// Staging upload manager
+/// Copies are batched and submitted by `flush` call. If the kernel has a dedicated transfer
+/// family, copies are executed on the transfer queue and ownership of the uploaded resources
+/// is transferred to the main family.
+/// # Note
+/// Destination resources must be kept alive until the upload ticket is complete.
+pub struct Uploader {
+    kernel: Arc<Kernel>,
+    allocator: Arc<Allocator>,
+    config: UploaderConfig,
+    slots: Vec<UploadSlot>,
+    current_slot: usize,
+    /// Current slot staging buffer write offset
+    staging_offset: u64,
+    /// True if current slot command buffers are in recording state
+    recording: bool,
+    /// Ownership release barriers, recorded at the end of the transfer command buffer
+    buffer_releases: Vec<vk::BufferMemoryBarrier<'static>>,
+    image_releases: Vec<vk::ImageMemoryBarrier<'static>>,
+    /// Ownership acquisition (or visibility, if there's no ownership transfer) barriers
+    buffer_acquires: Vec<vk::BufferMemoryBarrier<'static>>,
+    image_acquires: Vec<vk::ImageMemoryBarrier<'static>>,
+    pending_mipmaps: Vec<PendingMipmaps>,
+    next_ticket: u64,
+}
+
+impl Uploader {
+    pub fn new(allocator: &Arc<Allocator>, config: UploaderConfig) -> Result<Self, UploadError> {
+        let kernel = allocator.kernel().clone();
+        let mut slots = Vec::with_capacity(config.slot_count.max(1));
+
+        for _ in 0..config.slot_count.max(1) {
+            match UploadSlot::new(&kernel, allocator, config.staging_size.max(STAGING_ALIGNMENT)) {
+                Ok(slot) => slots.push(slot),
+                Err(err) => {
+                    for slot in &mut slots {
+                        unsafe { slot.destroy(&kernel) };
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(Self {
+            kernel,
+            allocator: allocator.clone(),
+            config,
+            slots,
+            current_slot: 0,
+            staging_offset: 0,
+            recording: false,
+            buffer_releases: Vec::new(),
+            image_releases: Vec::new(),
+            buffer_acquires: Vec::new(),
+            image_acquires: Vec::new(),
+            pending_mipmaps: Vec::new(),
+            next_ticket: 1,
+        })
+    }
+
+    /// Dedicated transfer family checking function
+    fn transfers_ownership(&self) -> bool {
+        self.kernel.queue_family_indices.transfer.is_some()
+    }
+
+    /// Ticket the next flush will return getting function
+    pub fn pending_ticket(&self) -> UploadTicket {
+        UploadTicket(self.next_ticket)
+    }
+
+    /// Ticket completion checking function
+    pub fn is_complete(&self, ticket: UploadTicket) -> Result<bool, UploadError> {
+        if ticket.0 >= self.next_ticket {
+            return Ok(false);
+        }
+
+        // Slot is reused only after its previous batch is complete
+        match self.slots.iter().find(|slot| slot.ticket == ticket.0) {
+            Some(slot) => unsafe { self.kernel.device.get_fence_status(slot.fence) }
+                .context("vkGetFenceStatus")
+                .map_err(UploadError::from),
+            None => Ok(true),
+        }
+    }
+
+    /// Ticket completion waiting function
+    /// Pending uploads are flushed if ticket isn't submitted yet.
+    pub fn wait(&mut self, ticket: UploadTicket) -> Result<(), UploadError> {
+        if ticket.0 >= self.next_ticket {
+            self.flush()?;
+        }
+
+        let fences = self
+            .slots
+            .iter()
+            .filter(|slot| slot.ticket != 0 && slot.ticket <= ticket.0)
+            .map(|slot| slot.fence)
+            .collect::<Vec<_>>();
+
+        if !fences.is_empty() {
+            unsafe { self.kernel.device.wait_for_fences(&fences, true, u64::MAX) }
+                .context("vkWaitForFences")?;
+        }
+
+        Ok(())
+    }
+
+    /// Current slot recording starting function
+    /// Blocks only if all slots are in flight.
+    fn begin(&mut self) -> Result<(), UploadError> {
+        if self.recording {
+            return Ok(());
+        }
+
+        let device = &self.kernel.device;
+        let slot = &mut self.slots[self.current_slot];
+
+        unsafe {
+            device
+                .wait_for_fences(&[slot.fence], true, u64::MAX)
+                .context("vkWaitForFences")?;
+            slot.overflow.clear();
+
+            device
+                .reset_command_pool(slot.transfer_pool, vk::CommandPoolResetFlags::empty())
+                .context("vkResetCommandPool")?;
+            device.begin_command_buffer(
+                slot.transfer_command_buffer,
+                &vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+            ).context("vkBeginCommandBuffer")?;
+
+            if let Some(main_pool) = slot.main_pool {
+                device
+                    .reset_command_pool(main_pool, vk::CommandPoolResetFlags::empty())
+                    .context("vkResetCommandPool")?;
+                device.begin_command_buffer(
+                    slot.main_command_buffer,
+                    &vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT),
+                ).context("vkBeginCommandBuffer")?;
+            }
+        }
+
+        self.staging_offset = 0;
+        self.recording = true;
+
+        Ok(())
+    }
+
+    /// Data to staging memory writing function
+    /// Returns staging buffer and offset of the data in it, offset is multiple of `alignment`.
+    fn stage(&mut self, data: &[u8], alignment: u64) -> Result<(vk::Buffer, u64), UploadError> {
+        let offset = self.staging_offset.next_multiple_of(alignment);
+
+        // Current batch is submitted if its staging buffer is full, so the next one gets a fresh buffer
+        if offset + data.len() as u64 > self.config.staging_size && offset != 0 && data.len() as u64 <= self.config.staging_size {
+            self.flush()?;
+            self.begin()?;
+            return self.stage(data, alignment);
+        }
+
+        let slot = &mut self.slots[self.current_slot];
+
+        if offset + data.len() as u64 > slot.staging.size() {
+            let mut overflow = Buffer::new(
+                &self.allocator,
+                "upload overflow staging",
+                data.len(),
+                vk::BufferUsageFlags::TRANSFER_SRC,
+                MemoryLocation::CpuToGpu,
+            )?;
+            overflow
+                .write(0, data)
+                .map_err(|_| UploadError::InvalidData("overflow staging buffer is not writable"))?;
+
+            let handle = overflow.handle();
+            slot.overflow.push(overflow);
+
+            return Ok((handle, 0));
+        }
+
+        slot.staging
+            .write(offset as usize, data)
+            .map_err(|_| UploadError::InvalidData("staging buffer is not writable"))?;
+        self.staging_offset = offset + data.len() as u64;
+
+        Ok((slot.staging.handle(), offset))
+    }
+
+    /// Buffer upload function
+    /// `offset` is measured in buffer elements.
+    pub fn upload_buffer<T: bytemuck::Pod>(
+        &mut self,
+        dst: &Buffer<T>,
+        offset: usize,
+        data: &[T],
+    ) -> Result<(), UploadError> {
+        if !dst.usage().contains(vk::BufferUsageFlags::TRANSFER_DST) {
+            return Err(UploadError::MissingUsage("TRANSFER_DST"));
+        }
+        if offset.checked_add(data.len()).is_none_or(|end| end > dst.len()) {
+            return Err(UploadError::InvalidData("data exceeds buffer bounds"));
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        self.begin()?;
+
+        let (staging, staging_offset) = self.stage(bytemuck::cast_slice(data), STAGING_ALIGNMENT)?;
+        let element_size = std::mem::size_of::<T>() as u64;
+        let dst_offset = offset as u64 * element_size;
+        let size = data.len() as u64 * element_size;
+
+        let slot = &self.slots[self.current_slot];
+        unsafe {
+            self.kernel.device.cmd_copy_buffer(
+                slot.transfer_command_buffer,
+                staging,
+                dst.handle(),
+                &[vk::BufferCopy {
+                    src_offset: staging_offset,
+                    dst_offset,
+                    size,
+                }],
+            );
+        }
+
+        let (src_family, dst_family) = self.ownership_families();
+        let barrier = vk::BufferMemoryBarrier::default()
+            .src_queue_family_index(src_family)
+            .dst_queue_family_index(dst_family)
+            .buffer(dst.handle())
+            .offset(dst_offset)
+            .size(size);
+
+        if self.transfers_ownership() {
+            self.buffer_releases
+                .push(barrier.src_access_mask(vk::AccessFlags::TRANSFER_WRITE));
+            self.buffer_acquires
+                .push(barrier.dst_access_mask(vk::AccessFlags::MEMORY_READ));
+        } else {
+            self.buffer_acquires.push(
+                barrier
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::MEMORY_READ),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Image upload staging offset alignment getting function
+    /// `vkCmdCopyBufferToImage` requires offsets to be multiple of texel block size and of 4,
+    /// optimal copy offset alignment of the device is respected too.
+    fn image_staging_alignment(&self, block_size: u64) -> u64 {
+        let optimal_alignment = self
+            .kernel
+            .physical_device_properties
+            .limits
+            .optimal_buffer_copy_offset_alignment
+            .max(1);
+
+        lcm(lcm(block_size, 4), optimal_alignment)
+    }
+
+    /// Image upload function
+    /// `data` contains tightly packed first mip level of all image layers, combined depth/stencil formats
+    /// aren't supported. Image is left in
+    /// `SHADER_READ_ONLY_OPTIMAL` layout, other mip levels are generated by blits if `generate_mipmaps` is set.
+    pub fn upload_image(&mut self, dst: &Image, data: &[u8], generate_mipmaps: bool) -> Result<(), UploadError> {
+        let generate_mipmaps = generate_mipmaps && dst.mip_levels() > 1;
+
+        if !dst.usage().contains(vk::ImageUsageFlags::TRANSFER_DST) {
+            return Err(UploadError::MissingUsage("TRANSFER_DST"));
+        }
+        if generate_mipmaps && !dst.usage().contains(vk::ImageUsageFlags::TRANSFER_SRC) {
+            return Err(UploadError::MissingUsage("TRANSFER_SRC"));
+        }
+        let layer_size = image_layer_size(dst.format(), dst.extent(), dst.array_layers(), data.len())?;
+
+        if generate_mipmaps {
+            let format_properties = unsafe {
+                self.kernel
+                    .instance
+                    .get_physical_device_format_properties(self.kernel.physical_device, dst.format())
+            };
+
+            if !format_properties
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR | vk::FormatFeatureFlags::BLIT_SRC | vk::FormatFeatureFlags::BLIT_DST)
+            {
+                return Err(UploadError::UnsupportedMipmapFormat(dst.format()));
+            }
+        }
+
+        // Block size is known, as layer size was calculated
+        let block_size = format_block_size(dst.format()).unwrap_or(1);
+        let alignment = self.image_staging_alignment(block_size);
+
+        self.begin()?;
+
+        // Every layer copy offset must be aligned, so layers are padded if their size isn't aligned
+        let layer_stride = layer_size.next_multiple_of(alignment);
+        let (staging, staging_offset) = if layer_stride == layer_size {
+            self.stage(data, alignment)?
+        } else {
+            let mut padded = vec![0u8; (layer_stride * dst.array_layers() as u64) as usize];
+            for (layer, layer_data) in data.chunks_exact(layer_size as usize).enumerate() {
+                let offset = layer * layer_stride as usize;
+                padded[offset..offset + layer_size as usize].copy_from_slice(layer_data);
+            }
+            self.stage(&padded, alignment)?
+        };
+        let aspect = dst.aspect();
+        let extent = dst.extent();
+        let device = &self.kernel.device;
+        let command_buffer = self.slots[self.current_slot].transfer_command_buffer;
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[vk::ImageMemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::empty())
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .old_layout(vk::ImageLayout::UNDEFINED)
+                    .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(dst.handle())
+                    .subresource_range(dst.subresource_range())],
+            );
+
+            let regions = (0..dst.array_layers())
+                .map(|layer| vk::BufferImageCopy {
+                    buffer_offset: staging_offset + layer as u64 * layer_stride,
+                    buffer_row_length: 0,
+                    buffer_image_height: 0,
+                    image_subresource: vk::ImageSubresourceLayers {
+                        aspect_mask: aspect,
+                        mip_level: 0,
+                        base_array_layer: layer,
+                        layer_count: 1,
+                    },
+                    image_offset: vk::Offset3D::default(),
+                    image_extent: vk::Extent3D {
+                        width: extent.w,
+                        height: extent.h,
+                        depth: 1,
+                    },
+                })
+                .collect::<Vec<_>>();
+
+            device.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging,
+                dst.handle(),
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            );
+        }
+
+        // Mipmaps are generated on the main queue, as dedicated transfer queues can't blit
+        let final_layout = if generate_mipmaps {
+            self.pending_mipmaps.push(PendingMipmaps {
+                image: dst.handle(),
+                width: extent.w,
+                height: extent.h,
+                mip_levels: dst.mip_levels(),
+                array_layers: dst.array_layers(),
+            });
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL
+        } else {
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        };
+
+        let (src_family, dst_family) = self.ownership_families();
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(final_layout)
+            .src_queue_family_index(src_family)
+            .dst_queue_family_index(dst_family)
+            .image(dst.handle())
+            .subresource_range(dst.subresource_range());
+        let dst_access = if generate_mipmaps {
+            vk::AccessFlags::TRANSFER_READ | vk::AccessFlags::TRANSFER_WRITE
+        } else {
+            vk::AccessFlags::SHADER_READ
+        };
+
+        if self.transfers_ownership() {
+            self.image_releases
+                .push(barrier.src_access_mask(vk::AccessFlags::TRANSFER_WRITE));
+            self.image_acquires.push(barrier.dst_access_mask(dst_access));
+        } else {
+            self.image_acquires.push(
+                barrier
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(dst_access),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Ownership transfer source and destination families getting function
+    fn ownership_families(&self) -> (u32, u32) {
+        let indices = &self.kernel.queue_family_indices;
+
+        match indices.transfer {
+            Some(transfer) => (transfer, indices.main),
+            None => (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED),
+        }
+    }
+
+    /// Mipmap generation commands recording function
+    /// Image levels must be in `TRANSFER_DST_OPTIMAL` layout, all of them are left in `SHADER_READ_ONLY_OPTIMAL`.
+    unsafe fn record_mipmaps(device: &ash::Device, command_buffer: vk::CommandBuffer, mipmaps: &PendingMipmaps) {
+        let level_barrier = |level: u32| {
+            vk::ImageMemoryBarrier::default()
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(mipmaps.image)
+                .subresource_range(
+                    vk::ImageSubresourceRange::default()
+                        .aspect_mask(vk::ImageAspectFlags::COLOR)
+                        .base_mip_level(level)
+                        .level_count(1)
+                        .layer_count(mipmaps.array_layers),
+                )
+        };
+        let level_extent = |level: u32| vk::Offset3D {
+            x: (mipmaps.width >> level).max(1) as i32,
+            y: (mipmaps.height >> level).max(1) as i32,
+            z: 1,
+        };
+        let level_layers = |level: u32| vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: level,
+            base_array_layer: 0,
+            layer_count: mipmaps.array_layers,
+        };
+
+        for level in 1..mipmaps.mip_levels {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[level_barrier(level - 1)
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+                    .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                    .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)],
+            );
+
+            device.cmd_blit_image(
+                command_buffer,
+                mipmaps.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                mipmaps.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[vk::ImageBlit {
+                    src_subresource: level_layers(level - 1),
+                    src_offsets: [vk::Offset3D::default(), level_extent(level - 1)],
+                    dst_subresource: level_layers(level),
+                    dst_offsets: [vk::Offset3D::default(), level_extent(level)],
+                }],
+                vk::Filter::LINEAR,
+            );
+
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::ALL_COMMANDS,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[level_barrier(level - 1)
+                    .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                    .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                    .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)],
+            );
+        }
+
+        device.cmd_pipeline_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[],
+            &[level_barrier(mipmaps.mip_levels - 1)
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)],
+        );
+    }
+
+    /// Recorded uploads submitting function
+    /// Returns ticket of the submitted batch (or of the last one if there is nothing to submit).
+    pub fn flush(&mut self) -> Result<UploadTicket, UploadError> {
+        if !self.recording {
+            return Ok(UploadTicket(self.next_ticket - 1));
+        }
+
+        let device = &self.kernel.device;
+        let slot = &mut self.slots[self.current_slot];
+        let transfers_ownership = slot.main_pool.is_some();
+        let main_command_buffer = if transfers_ownership {
+            slot.main_command_buffer
+        } else {
+            slot.transfer_command_buffer
+        };
+
+        unsafe {
+            if transfers_ownership {
+                device.cmd_pipeline_barrier(
+                    slot.transfer_command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &self.buffer_releases,
+                    &self.image_releases,
+                );
+            }
+
+            if !self.buffer_acquires.is_empty() || !self.image_acquires.is_empty() {
+                device.cmd_pipeline_barrier(
+                    main_command_buffer,
+                    if transfers_ownership {
+                        vk::PipelineStageFlags::TOP_OF_PIPE
+                    } else {
+                        vk::PipelineStageFlags::TRANSFER
+                    },
+                    vk::PipelineStageFlags::ALL_COMMANDS,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &self.buffer_acquires,
+                    &self.image_acquires,
+                );
+            }
+
+            for mipmaps in &self.pending_mipmaps {
+                Self::record_mipmaps(device, main_command_buffer, mipmaps);
+            }
+
+            device
+                .end_command_buffer(slot.transfer_command_buffer)
+                .context("vkEndCommandBuffer")?;
+            if transfers_ownership {
+                device
+                    .end_command_buffer(slot.main_command_buffer)
+                    .context("vkEndCommandBuffer")?;
+            }
+
+            device.reset_fences(&[slot.fence]).context("vkResetFences")?;
+
+            if transfers_ownership {
+                device.queue_submit(
+                    self.kernel.transfer_queue,
+                    &[vk::SubmitInfo::default()
+                        .command_buffers(&[slot.transfer_command_buffer])
+                        .signal_semaphores(&[slot.semaphore])],
+                    vk::Fence::null(),
+                ).context("vkQueueSubmit")?;
+
+                device.queue_submit(
+                    self.kernel.main_queue,
+                    &[vk::SubmitInfo::default()
+                        .wait_semaphores(&[slot.semaphore])
+                        .wait_dst_stage_mask(&[vk::PipelineStageFlags::ALL_COMMANDS])
+                        .command_buffers(&[slot.main_command_buffer])],
+                    slot.fence,
+                ).context("vkQueueSubmit")?;
+            } else {
+                device.queue_submit(
+                    self.kernel.main_queue,
+                    &[vk::SubmitInfo::default().command_buffers(&[slot.transfer_command_buffer])],
+                    slot.fence,
+                ).context("vkQueueSubmit")?;
+            }
+        }
+
+        let ticket = self.next_ticket;
+        slot.ticket = ticket;
+        self.next_ticket += 1;
+        self.current_slot = (self.current_slot + 1) % self.slots.len();
+        self.recording = false;
+        self.buffer_releases.clear();
+        self.image_releases.clear();
+        self.buffer_acquires.clear();
+        self.image_acquires.clear();
+        self.pending_mipmaps.clear();
+
+        Ok(UploadTicket(ticket))
+    }
+}
+
+impl Drop for Uploader {
+    fn drop(&mut self) {
+        let device = &self.kernel.device;
+
+        unsafe {
+            let fences = self.slots.iter().map(|slot| slot.fence).collect::<Vec<_>>();
+            _ = device.wait_for_fences(&fences, true, u64::MAX);
+
+            for slot in &mut self.slots {
+                slot.destroy(&self.kernel);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_layer_sizes() {
+        let layer_size =
+            |format, w, h, array_layers, data_len| image_layer_size(format, Ext2::new(w, h), array_layers, data_len);
+
+        assert_eq!(layer_size(vk::Format::R8G8B8A8_UNORM, 1024, 1024, 1, 4 << 20).unwrap(), 4 << 20);
+        assert_eq!(layer_size(vk::Format::R16_SFLOAT, 3, 5, 6, 180).unwrap(), 30);
+        // 5x5 texels are stored in 2x2 blocks
+        assert_eq!(layer_size(vk::Format::BC1_RGB_UNORM_BLOCK, 5, 5, 1, 32).unwrap(), 32);
+        assert_eq!(layer_size(vk::Format::BC7_UNORM_BLOCK, 4, 4, 2, 32).unwrap(), 16);
+        assert_eq!(layer_size(vk::Format::D32_SFLOAT, 2, 2, 1, 16).unwrap(), 16);
+    }
+
+    #[test]
+    fn image_data_length_mismatch() {
+        let extent = Ext2::new(1024, 1024);
+
+        for data_len in [0, 4, (4 << 20) - 1, (4 << 20) + 4, 4 << 21] {
+            assert!(matches!(
+                image_layer_size(vk::Format::R8G8B8A8_UNORM, extent, 1, data_len),
+                Err(UploadError::InvalidData(_))
+            ));
+        }
+        // Data of single layer is too short for layered image
+        assert!(image_layer_size(vk::Format::R8G8B8A8_UNORM, extent, 2, 4 << 20).is_err());
+    }
+
+    #[test]
+    fn combined_depth_stencil_rejected() {
+        for format in [vk::Format::D24_UNORM_S8_UINT, vk::Format::D32_SFLOAT_S8_UINT] {
+            assert!(matches!(
+                image_layer_size(format, Ext2::new(1, 1), 1, 5),
+                Err(UploadError::InvalidData(_))
+            ));
+        }
+    }
+}
@@ -1,3 +1,6 @@
+// Baseline utility code style, kept as is
+#![allow(clippy::from_over_into, clippy::needless_return, clippy::should_implement_trait)]
+
 pub mod math;
 pub mod rand;
 